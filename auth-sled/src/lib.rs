@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use std::convert::TryFrom;
 use webb_auth::AuthDb;
 use webb_proposals::TypedChainId;
@@ -12,13 +12,20 @@ pub struct SledAuthDb {
 
 impl SledAuthDb {
     /// Open a new SledStore.
+    ///
+    /// Claims stored in the legacy `claims-{chain_id}` trees are migrated
+    /// to the typed claims trees on open. Pending claim reservations and
+    /// claim tickets left over from a previous run are kept, so that they
+    /// can be settled from the transactions they recorded.
     pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
-        Ok(Self {
+        let store = Self {
             db: sled::Config::new()
                 .use_compression(false)
                 .path(path)
                 .open()?,
-        })
+        };
+        store.migrate_legacy_claims()?;
+        Ok(store)
     }
 
     pub fn user_info_tree(&self) -> Result<sled::Tree, Error> {
@@ -28,12 +35,127 @@ impl SledAuthDb {
     pub fn claims_tree(
         &self,
         chain_id: TypedChainId,
+        asset: ClaimAsset,
+    ) -> Result<sled::Tree, Error> {
+        self.db
+            .open_tree(claims_tree_name(chain_id, asset))
+            .map_err(Into::into)
+    }
+
+    /// Returns the claims trees of every asset claimed on the given chain.
+    pub fn chain_claims_trees(
+        &self,
+        chain_id: TypedChainId,
+    ) -> Result<Vec<sled::Tree>, Error> {
+        let native = claims_tree_name(chain_id, ClaimAsset::Native);
+        let others = format!("{native}-");
+        self.db
+            .tree_names()
+            .into_iter()
+            .filter(|name| {
+                name.as_ref() == native.as_bytes()
+                    || name.starts_with(others.as_bytes())
+            })
+            .map(|name| self.db.open_tree(name).map_err(Into::into))
+            .collect()
+    }
+
+    pub fn pending_claims_tree(
+        &self,
+        chain_id: TypedChainId,
+        asset: ClaimAsset,
     ) -> Result<sled::Tree, Error> {
         self.db
-            .open_tree(format!("claims-{}", chain_id.chain_id()))
+            .open_tree(format!("pending-{}", claims_tree_name(chain_id, asset)))
             .map_err(Into::into)
     }
 
+    /// Returns every reserved claim.
+    pub fn pending_claims(&self) -> Result<Vec<PendingClaim>, Error> {
+        let mut pending_claims = Vec::new();
+        for name in self.db.tree_names() {
+            if !name.starts_with(b"pending-claims-") {
+                continue;
+            }
+            let tree = self.db.open_tree(name)?;
+            for row in tree.iter() {
+                let (_, value) = row?;
+                pending_claims.push(serde_json::from_slice(&value)?);
+            }
+        }
+        Ok(pending_claims)
+    }
+
+    pub fn claim_tickets_tree(&self) -> Result<sled::Tree, Error> {
+        self.db.open_tree("claim-tickets").map_err(Into::into)
+    }
+
+    /// Returns the claim tickets that do not have a final status yet.
+    pub fn unfinished_claim_tickets(&self) -> Result<Vec<ClaimTicket>, Error> {
+        let mut tickets = Vec::new();
+        for row in self.claim_tickets_tree()?.iter() {
+            let (_, value) = row?;
+            let ticket: ClaimTicket = serde_json::from_slice(&value)?;
+            if !ticket.status.is_final() {
+                tickets.push(ticket);
+            }
+        }
+        Ok(tickets)
+    }
+
+    /// Moves the claims stored in the legacy `claims-{chain_id}` trees,
+    /// which did not record the chain type, into the typed claims trees.
+    ///
+    /// The chain type of every claim is inferred from the claimed address.
+    /// Claims already present in the typed trees are kept. Claims of an
+    /// unknown address, whose chain type cannot be inferred, are left in
+    /// the legacy tree, which is only dropped once it is empty. Returns the
+    /// number of migrated claims.
+    pub fn migrate_legacy_claims(&self) -> Result<usize, Error> {
+        let legacy_trees = self
+            .db
+            .tree_names()
+            .into_iter()
+            .filter_map(|name| {
+                let name = std::str::from_utf8(&name).ok()?.to_owned();
+                let chain_id =
+                    name.strip_prefix("claims-")?.parse::<u32>().ok()?;
+                Some((name, chain_id))
+            })
+            .collect::<Vec<_>>();
+
+        let mut migrated = 0;
+        for (name, chain_id) in legacy_trees {
+            let legacy_tree = self.db.open_tree(&name)?;
+            for row in legacy_tree.iter() {
+                let (key, value) = row?;
+                let claim: ClaimsData = serde_json::from_slice(&value)?;
+                let typed_chain_id = match claim.address {
+                    UniversalWalletAddress::Ethereum(_) => {
+                        TypedChainId::Evm(chain_id)
+                    }
+                    UniversalWalletAddress::Substrate(_) => {
+                        TypedChainId::Substrate(chain_id)
+                    }
+                    UniversalWalletAddress::Unknown => continue,
+                };
+                let swapped = self
+                    .claims_tree(typed_chain_id, ClaimAsset::Native)?
+                    .compare_and_swap(&key, None::<&[u8]>, Some(value))?;
+                if swapped.is_ok() {
+                    migrated += 1;
+                }
+                // The claim is now in the typed tree, either migrated or
+                // superseded by a newer claim.
+                legacy_tree.remove(key)?;
+            }
+            if legacy_tree.is_empty() {
+                self.db.drop_tree(&name)?;
+            }
+        }
+        Ok(migrated)
+    }
+
     /// Open a new SledStore in a temporary directory.
     #[cfg(test)]
     pub fn open_for_tests() -> Result<Self, Error> {
@@ -78,10 +200,11 @@ impl AuthDb for SledAuthDb {
         &self,
         id: u64,
         typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
         claim: ClaimsData,
     ) -> Result<DateTime<Utc>, Self::Error> {
         let id = u64_to_i64(id)?;
-        let last_claim_tree = self.claims_tree(typed_chain_id)?;
+        let last_claim_tree = self.claims_tree(typed_chain_id, asset)?;
         let claims_data_bytes = serde_json::to_vec(&claim)?;
         last_claim_tree.insert(id.to_be_bytes(), claims_data_bytes)?;
         Ok(claim.last_claimed_date)
@@ -91,9 +214,10 @@ impl AuthDb for SledAuthDb {
         &self,
         id: u64,
         typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
     ) -> Result<Option<ClaimsData>, Self::Error> {
         let id = u64_to_i64(id)?;
-        let last_claim_tree = self.claims_tree(typed_chain_id)?;
+        let last_claim_tree = self.claims_tree(typed_chain_id, asset)?;
         last_claim_tree
             .get(id.to_be_bytes())
             .map_err(Into::into)
@@ -102,6 +226,188 @@ impl AuthDb for SledAuthDb {
                     .transpose()
             })
     }
+
+    async fn next_claim_ticket_id(&self) -> Result<u64, Self::Error> {
+        // Random rather than sequential, so that the tickets of other users
+        // cannot be enumerated. Ids are stored as positive i64 keys.
+        Ok(rand::random::<u64>() >> 1)
+    }
+
+    async fn insert_claim_ticket(
+        &self,
+        ticket: &ClaimTicket,
+    ) -> Result<bool, Self::Error> {
+        let id = u64_to_i64(ticket.id)?;
+        let claim_tickets_tree = self.claim_tickets_tree()?;
+        let ticket_bytes = serde_json::to_vec(ticket)?;
+        let swapped = claim_tickets_tree.compare_and_swap(
+            id.to_be_bytes(),
+            None::<&[u8]>,
+            Some(ticket_bytes),
+        )?;
+        Ok(swapped.is_ok())
+    }
+
+    async fn put_claim_ticket(
+        &self,
+        ticket: &ClaimTicket,
+    ) -> Result<(), Self::Error> {
+        let id = u64_to_i64(ticket.id)?;
+        let claim_tickets_tree = self.claim_tickets_tree()?;
+        let ticket_bytes = serde_json::to_vec(ticket)?;
+        claim_tickets_tree.insert(id.to_be_bytes(), ticket_bytes)?;
+        Ok(())
+    }
+
+    async fn get_claim_ticket(
+        &self,
+        id: u64,
+    ) -> Result<Option<ClaimTicket>, Self::Error> {
+        let id = u64_to_i64(id)?;
+        let claim_tickets_tree = self.claim_tickets_tree()?;
+        claim_tickets_tree
+            .get(id.to_be_bytes())
+            .map_err(Into::into)
+            .and_then(|row| {
+                row.map(|row| serde_json::from_slice(&row).map_err(Into::into))
+                    .transpose()
+            })
+    }
+
+    async fn reserve_claim(
+        &self,
+        id: u64,
+        typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
+        claim: ClaimsData,
+        cooldown: Duration,
+    ) -> Result<ClaimReservation, Self::Error> {
+        let key = u64_to_i64(id)?.to_be_bytes();
+        let pending_tree = self.pending_claims_tree(typed_chain_id, asset)?;
+        let pending_claim = PendingClaim {
+            claim: claim.clone(),
+            typed_chain_id,
+            asset,
+            sent: Vec::new(),
+        };
+        let pending_claim_bytes = serde_json::to_vec(&pending_claim)?;
+        let swapped = pending_tree.compare_and_swap(
+            key,
+            None::<&[u8]>,
+            Some(pending_claim_bytes),
+        )?;
+        if swapped.is_err() {
+            return Ok(ClaimReservation::Pending);
+        }
+
+        let last_claim =
+            self.get_last_claim_data(id, typed_chain_id, asset).await?;
+        match last_claim {
+            Some(last_claim)
+                if claim.last_claimed_date
+                    < last_claim.last_claimed_date + cooldown =>
+            {
+                pending_tree.remove(key)?;
+                Ok(ClaimReservation::TooEarly(last_claim))
+            }
+            _ => Ok(ClaimReservation::Reserved),
+        }
+    }
+
+    async fn record_claim_tx(
+        &self,
+        id: u64,
+        typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
+        tx: SentTx,
+    ) -> Result<(), Self::Error> {
+        let key = u64_to_i64(id)?.to_be_bytes();
+        let pending_tree = self.pending_claims_tree(typed_chain_id, asset)?;
+        // Retry until no concurrent update or release gets in between.
+        loop {
+            let Some(current) = pending_tree.get(key)? else {
+                return Ok(());
+            };
+            let mut pending_claim: PendingClaim =
+                serde_json::from_slice(&current)?;
+            if pending_claim.sent.contains(&tx) {
+                return Ok(());
+            }
+            pending_claim.sent.push(tx.clone());
+            let swapped = pending_tree.compare_and_swap(
+                key,
+                Some(current),
+                Some(serde_json::to_vec(&pending_claim)?),
+            )?;
+            if swapped.is_ok() {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn get_pending_claim(
+        &self,
+        id: u64,
+        typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
+    ) -> Result<Option<PendingClaim>, Self::Error> {
+        let id = u64_to_i64(id)?;
+        let pending_tree = self.pending_claims_tree(typed_chain_id, asset)?;
+        pending_tree
+            .get(id.to_be_bytes())
+            .map_err(Into::into)
+            .and_then(|row| {
+                row.map(|row| serde_json::from_slice(&row).map_err(Into::into))
+                    .transpose()
+            })
+    }
+
+    async fn commit_claim(
+        &self,
+        id: u64,
+        typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
+        claim: ClaimsData,
+    ) -> Result<DateTime<Utc>, Self::Error> {
+        // Store the claim before clearing the reservation, so a concurrent
+        // reservation always observes one of the two.
+        let last_claimed_date = self
+            .put_last_claim_data(id, typed_chain_id, asset, claim)
+            .await?;
+        self.release_claim(id, typed_chain_id, asset).await?;
+        Ok(last_claimed_date)
+    }
+
+    async fn release_claim(
+        &self,
+        id: u64,
+        typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
+    ) -> Result<(), Self::Error> {
+        let id = u64_to_i64(id)?;
+        let pending_tree = self.pending_claims_tree(typed_chain_id, asset)?;
+        pending_tree.remove(id.to_be_bytes())?;
+        Ok(())
+    }
+}
+
+/// Returns the name of the claims tree of the given asset on the given
+/// chain. Native token claims keep the tree name they had before claims
+/// were tracked per asset.
+fn claims_tree_name(typed_chain_id: TypedChainId, asset: ClaimAsset) -> String {
+    let chain = match typed_chain_id {
+        TypedChainId::Evm(chain_id) => format!("claims-evm-{chain_id}"),
+        TypedChainId::Substrate(chain_id) => {
+            format!("claims-substrate-{chain_id}")
+        }
+        other => format!("claims-{other:?}"),
+    };
+    match asset {
+        ClaimAsset::Native => chain,
+        ClaimAsset::Token(_) | ClaimAsset::Asset(_) => {
+            format!("{chain}-{asset}")
+        }
+    }
 }
 
 pub fn u64_to_i64(value: u64) -> Result<i64, Error> {
@@ -123,3 +429,154 @@ pub enum Error {
     #[error("Invalid Serialization: {0}")]
     Serde(#[from] serde_json::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claim(identity: u64, address: UniversalWalletAddress) -> ClaimsData {
+        ClaimsData {
+            identity,
+            address,
+            last_claimed_date: Utc::now(),
+        }
+    }
+
+    fn insert(tree: &sled::Tree, claim: &ClaimsData) {
+        let key = u64_to_i64(claim.identity).unwrap().to_be_bytes();
+        tree.insert(key, serde_json::to_vec(claim).unwrap())
+            .unwrap();
+    }
+
+    fn get(tree: &sled::Tree, identity: u64) -> Option<ClaimsData> {
+        let key = u64_to_i64(identity).unwrap().to_be_bytes();
+        tree.get(key)
+            .unwrap()
+            .map(|row| serde_json::from_slice(&row).unwrap())
+    }
+
+    #[test]
+    fn migrates_legacy_claims_and_keeps_unknown_addresses() {
+        let db = SledAuthDb::open_for_tests().unwrap();
+        let legacy = db.db.open_tree("claims-1").unwrap();
+        let evm = claim(1, UniversalWalletAddress::Ethereum([1; 20]));
+        let substrate = claim(2, UniversalWalletAddress::Substrate([2; 32]));
+        let unknown = claim(3, UniversalWalletAddress::Unknown);
+        let stale = claim(4, UniversalWalletAddress::Ethereum([4; 20]));
+        for claim in [&evm, &substrate, &unknown, &stale] {
+            insert(&legacy, claim);
+        }
+        let evm_tree = db
+            .claims_tree(TypedChainId::Evm(1), ClaimAsset::Native)
+            .unwrap();
+        let newer = claim(4, UniversalWalletAddress::Ethereum([5; 20]));
+        insert(&evm_tree, &newer);
+
+        assert_eq!(db.migrate_legacy_claims().unwrap(), 2);
+
+        let substrate_tree = db
+            .claims_tree(TypedChainId::Substrate(1), ClaimAsset::Native)
+            .unwrap();
+        assert_eq!(get(&evm_tree, 1), Some(evm));
+        assert_eq!(get(&substrate_tree, 2), Some(substrate));
+        assert_eq!(get(&evm_tree, 4), Some(newer));
+        assert_eq!(get(&evm_tree, 3), None);
+        // Only the claim of an unknown address is left behind.
+        assert_eq!(legacy.len(), 1);
+        assert_eq!(get(&legacy, 3), Some(unknown.clone()));
+
+        // Migrating again changes nothing.
+        assert_eq!(db.migrate_legacy_claims().unwrap(), 0);
+        assert_eq!(get(&legacy, 3), Some(unknown));
+    }
+
+    #[tokio::test]
+    async fn records_the_transactions_of_pending_claims() {
+        let db = SledAuthDb::open_for_tests().unwrap();
+        let chain = TypedChainId::Evm(1);
+        let claim = claim(1, UniversalWalletAddress::Ethereum([1; 20]));
+        let cooldown = Duration::hours(1);
+        let reservation = db
+            .reserve_claim(
+                1,
+                chain,
+                ClaimAsset::Native,
+                claim.clone(),
+                cooldown,
+            )
+            .await
+            .unwrap();
+        assert_eq!(reservation, ClaimReservation::Reserved);
+        let tx = SentTx {
+            tx_hash: "0x01".to_string(),
+            from: "0x02".to_string(),
+            nonce: 3,
+        };
+        for _ in 0..2 {
+            db.record_claim_tx(1, chain, ClaimAsset::Native, tx.clone())
+                .await
+                .unwrap();
+        }
+        let pending_claim = PendingClaim {
+            claim,
+            typed_chain_id: chain,
+            asset: ClaimAsset::Native,
+            sent: vec![tx.clone()],
+        };
+        assert_eq!(
+            db.get_pending_claim(1, chain, ClaimAsset::Native)
+                .await
+                .unwrap(),
+            Some(pending_claim.clone())
+        );
+        assert_eq!(db.pending_claims().unwrap(), vec![pending_claim]);
+
+        // Released claims no longer record transactions.
+        db.release_claim(1, chain, ClaimAsset::Native)
+            .await
+            .unwrap();
+        db.record_claim_tx(1, chain, ClaimAsset::Native, tx)
+            .await
+            .unwrap();
+        assert!(db.pending_claims().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn inserts_claim_tickets_under_free_ids_only() {
+        let db = SledAuthDb::open_for_tests().unwrap();
+        let claim = claim(1, UniversalWalletAddress::Ethereum([1; 20]));
+        let mut ticket = ClaimTicket {
+            id: db.next_claim_ticket_id().await.unwrap(),
+            identity: claim.identity,
+            address: claim.address,
+            typed_chain_id: TypedChainId::Evm(1),
+            status: ClaimStatus::Queued,
+            created_at: claim.last_claimed_date,
+            updated_at: claim.last_claimed_date,
+            assets: vec![ClaimAsset::Native],
+            tx_hashes: Vec::new(),
+        };
+        assert!(db.insert_claim_ticket(&ticket).await.unwrap());
+
+        let first = ticket.clone();
+        ticket.identity = 2;
+        assert!(!db.insert_claim_ticket(&ticket).await.unwrap());
+        assert_eq!(db.get_claim_ticket(ticket.id).await.unwrap(), Some(first));
+    }
+
+    #[test]
+    fn drops_fully_migrated_legacy_trees() {
+        let db = SledAuthDb::open_for_tests().unwrap();
+        let legacy = db.db.open_tree("claims-1").unwrap();
+        insert(
+            &legacy,
+            &claim(1, UniversalWalletAddress::Ethereum([1; 20])),
+        );
+        assert_eq!(db.migrate_legacy_claims().unwrap(), 1);
+        assert!(!db
+            .db
+            .tree_names()
+            .iter()
+            .any(|name| name.as_ref() == b"claims-1"));
+    }
+}
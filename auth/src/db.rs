@@ -1,7 +1,13 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use webb_proposals::TypedChainId;
 
-use crate::{model::ClaimsData, UserInfo};
+use crate::{
+    model::{
+        ClaimAsset, ClaimReservation, ClaimTicket, ClaimsData, PendingClaim,
+        SentTx,
+    },
+    UserInfo,
+};
 
 #[async_trait::async_trait]
 pub trait AuthDb {
@@ -22,6 +28,7 @@ pub trait AuthDb {
         &self,
         id: u64,
         typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
         claim: ClaimsData,
     ) -> Result<DateTime<Utc>, Self::Error>;
 
@@ -29,5 +36,80 @@ pub trait AuthDb {
         &self,
         id: u64,
         typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
     ) -> Result<Option<ClaimsData>, Self::Error>;
+
+    /// Atomically marks a claim of `asset` as pending, unless another claim
+    /// of the same user for the same asset on the same chain is pending or
+    /// the last claim of that asset is more recent than `cooldown`.
+    ///
+    /// A [`ClaimReservation::Reserved`] claim must later be either
+    /// committed with [`AuthDb::commit_claim`] or released with
+    /// [`AuthDb::release_claim`].
+    async fn reserve_claim(
+        &self,
+        id: u64,
+        typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
+        claim: ClaimsData,
+        cooldown: Duration,
+    ) -> Result<ClaimReservation, Self::Error>;
+
+    /// Stores a reserved claim as the last claim and clears the
+    /// reservation.
+    async fn commit_claim(
+        &self,
+        id: u64,
+        typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
+        claim: ClaimsData,
+    ) -> Result<DateTime<Utc>, Self::Error>;
+
+    /// Clears a reservation without storing the claim.
+    async fn release_claim(
+        &self,
+        id: u64,
+        typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
+    ) -> Result<(), Self::Error>;
+
+    /// Records a transaction sent for a reserved claim, so the claim can be
+    /// settled from its transactions if the faucet restarts. Does nothing if
+    /// the claim is not reserved.
+    async fn record_claim_tx(
+        &self,
+        id: u64,
+        typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
+        tx: SentTx,
+    ) -> Result<(), Self::Error>;
+
+    /// Returns the reservation of a claim, if any.
+    async fn get_pending_claim(
+        &self,
+        id: u64,
+        typed_chain_id: TypedChainId,
+        asset: ClaimAsset,
+    ) -> Result<Option<PendingClaim>, Self::Error>;
+
+    /// Returns a new random claim ticket id, which may already be taken,
+    /// see [`AuthDb::insert_claim_ticket`].
+    async fn next_claim_ticket_id(&self) -> Result<u64, Self::Error>;
+
+    /// Stores a new claim ticket, unless its id is taken. Returns whether it
+    /// was stored.
+    async fn insert_claim_ticket(
+        &self,
+        ticket: &ClaimTicket,
+    ) -> Result<bool, Self::Error>;
+
+    async fn put_claim_ticket(
+        &self,
+        ticket: &ClaimTicket,
+    ) -> Result<(), Self::Error>;
+
+    async fn get_claim_ticket(
+        &self,
+        id: u64,
+    ) -> Result<Option<ClaimTicket>, Self::Error>;
 }
@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use ethers_core::types::Address;
 use sp_core::crypto::{AccountId32, Ss58Codec};
+use webb_proposals::TypedChainId;
 
 #[derive(
     Copy,
@@ -128,6 +129,130 @@ pub struct ClaimsData {
     pub last_claimed_date: DateTime<Utc>,
 }
 
+/// An asset paid out by a claim. Claims of different assets on the same
+/// chain are reserved and cooled down independently.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Eq,
+    Hash,
+    PartialEq,
+    Default,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(tag = "type", content = "value")]
+pub enum ClaimAsset {
+    /// The native token of the chain.
+    #[default]
+    Native,
+    /// An ERC20 token, identified by its contract address.
+    Token(Address),
+    /// A non-native Substrate asset, identified by its asset id.
+    Asset(u32),
+}
+
+impl core::fmt::Display for ClaimAsset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Native => write!(f, "native"),
+            Self::Token(address) => write!(f, "token-{address:?}"),
+            Self::Asset(asset_id) => write!(f, "asset-{asset_id}"),
+        }
+    }
+}
+
+/// The status of a claim that is processed in the background.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", content = "value")]
+pub enum ClaimStatus {
+    /// The transaction is waiting in the queue of its chain.
+    Queued,
+    /// The transaction was sent to the network.
+    Broadcast { tx_hash: String },
+    /// The transaction was included in a block, but is not final yet.
+    InBlock { block_hash: String },
+    /// The transaction was included on chain.
+    Included { tx_result: serde_json::Value },
+    /// The transaction was not included in time, but is still tracked and
+    /// may be included later.
+    TimedOut {
+        /// The code of the error, as in the error responses of the faucet.
+        #[serde(default)]
+        code: Option<String>,
+        reason: String,
+    },
+    /// The claim failed and was not paid.
+    Failed {
+        /// The code of the error, as in the error responses of the faucet.
+        #[serde(default)]
+        code: Option<String>,
+        reason: String,
+    },
+}
+
+impl ClaimStatus {
+    /// Returns `true` if the claim will not change its status anymore.
+    #[must_use]
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Included { .. } | Self::Failed { .. })
+    }
+}
+
+/// A ticket tracking a claim that is processed in the background.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ClaimTicket {
+    pub id: u64,
+    pub identity: u64,
+    pub address: UniversalWalletAddress,
+    pub typed_chain_id: TypedChainId,
+    pub status: ClaimStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// The assets the claim pays out.
+    #[serde(default)]
+    pub assets: Vec<ClaimAsset>,
+    /// The hashes of every transaction sent for the claim, including
+    /// replacements.
+    #[serde(default)]
+    pub tx_hashes: Vec<String>,
+}
+
+/// A transaction sent for a reserved claim.
+#[derive(
+    Clone, Debug, Eq, Hash, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+pub struct SentTx {
+    pub tx_hash: String,
+    /// The hot wallet the transaction was sent from.
+    pub from: String,
+    pub nonce: u64,
+}
+
+/// A reserved claim, along with the transactions sent to pay it out, so
+/// that it can be settled after a restart.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PendingClaim {
+    #[serde(flatten)]
+    pub claim: ClaimsData,
+    pub typed_chain_id: TypedChainId,
+    pub asset: ClaimAsset,
+    pub sent: Vec<SentTx>,
+}
+
+/// The outcome of trying to reserve a claim.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ClaimReservation {
+    /// The claim is reserved and must be committed or released.
+    Reserved,
+    /// Another claim of the same user for the same asset on the same chain
+    /// is in progress.
+    Pending,
+    /// The user already claimed within the cooldown period.
+    TooEarly(ClaimsData),
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Invalid Access: {0}")]
@@ -52,14 +52,16 @@ fn main() -> Result<()> {
             "processing chain claims",
         );
 
-        let chain_accounts = db.claims_tree(*chain)?.iter().flat_map(|kv| {
-            kv.ok()
-                .and_then(|(_, v)| {
-                    serde_json::from_slice::<ClaimsData>(&v).ok()
-                })
-                .map(|c| c.address)
-        });
-        accounts.extend(chain_accounts);
+        for tree in db.chain_claims_trees(*chain)? {
+            let chain_accounts = tree.iter().flat_map(|kv| {
+                kv.ok()
+                    .and_then(|(_, v)| {
+                        serde_json::from_slice::<ClaimsData>(&v).ok()
+                    })
+                    .map(|c| c.address)
+            });
+            accounts.extend(chain_accounts);
+        }
         tracing::debug!("Total accounts (so far): {}", accounts.len());
     }
     let evm_accounts = accounts
@@ -10,16 +10,56 @@ type EvmChain = { Evm: number };
 type SubstrateChain = { Substrate: number };
 type Chain = EvmChain | SubstrateChain;
 
+type ClaimStatus =
+  | { type: "Queued" }
+  | { type: "Broadcast"; value: { tx_hash: string } }
+  | { type: "InBlock"; value: { block_hash: string } }
+  | { type: "Included"; value: { tx_result: unknown } }
+  | { type: "TimedOut"; value: { code: string | null; reason: string } }
+  | { type: "Failed"; value: { code: string | null; reason: string } };
+
+const describeStatus = (status: ClaimStatus): string => {
+  switch (status.type) {
+    case "Queued":
+      return "Claim queued";
+    case "Broadcast":
+      return `Transaction submitted: ${status.value.tx_hash}`;
+    case "InBlock":
+      return `Transaction in block: ${status.value.block_hash}`;
+    case "Included":
+      return "Funds claimed successfully";
+    case "TimedOut":
+      return `Transaction still pending, waiting for it to be included: ${status.value.reason}`;
+    case "Failed":
+      return `Error claiming funds: ${status.value.reason}`;
+  }
+};
+
 const ClaimFundsForm: React.FC<ClaimFundsFormProps> = ({ accessToken }) => {
   const [chain, setChain] = useState<Chain>({ Evm: 3884533461 });
   const [address, setAddress] = useState<string>("");
   const [onlyNativeToken, setOnlyNativeToken] = useState<boolean>(true);
+  const [withNativeToken, setWithNativeToken] = useState<boolean>(true);
+  const [progress, setProgress] = useState<string | null>(null);
+
+  const followClaim = (backendUrl: string, ticketId: number) => {
+    const events = new EventSource(`${backendUrl}/claims/${ticketId}/events`);
+    events.addEventListener("status", (event) => {
+      const status = JSON.parse((event as MessageEvent).data) as ClaimStatus;
+      console.log("Claim status:", status);
+      setProgress(describeStatus(status));
+      if (status.type === "Included" || status.type === "Failed") {
+        events.close();
+      }
+    });
+    events.onerror = () => events.close();
+  };
 
   const claimFunds = async () => {
     // Implement your claim funds logic here
     let result;
+    const backendUrl = process.env.REACT_APP_BACKEND_URL || "http://localhost:8000";
     try {
-      const backendUrl = process.env.REACT_APP_BACKEND_URL || "http://localhost:8000";
       result = await axios.post(
         "/faucet",
         JSON.stringify({
@@ -37,6 +77,8 @@ const ClaimFundsForm: React.FC<ClaimFundsFormProps> = ({ accessToken }) => {
               id: (chain as EvmChain).Evm || (chain as SubstrateChain).Substrate,
             },
             onlyNativeToken,
+            withNativeToken: !onlyNativeToken && withNativeToken,
+            asynchronous: true,
           },
         }),
         {
@@ -57,11 +99,11 @@ const ClaimFundsForm: React.FC<ClaimFundsFormProps> = ({ accessToken }) => {
       return;
     }
 
-    // Parse the response and display the result to the user
-    console.log("Funds claimed successfully");
-    console.log((result as AxiosResponse).data);
-
-    alert("Funds claimed successfully");
+    // Follow the progress of the claim until it completes
+    const data = (result as AxiosResponse).data;
+    console.log("Claim queued", data);
+    setProgress(describeStatus({ type: "Queued" }));
+    followClaim(backendUrl, data.ticket_id);
   };
 
   const handleSubmit = (event: React.FormEvent<HTMLFormElement>) => {
@@ -111,6 +153,18 @@ const ClaimFundsForm: React.FC<ClaimFundsFormProps> = ({ accessToken }) => {
       <label htmlFor="only-native-token">
         {onlyNativeToken ? "Only Native Token" : "ERC20/Assets Tokens"}
       </label>
+
+      {!onlyNativeToken && (
+        <>
+          <input
+            id="with-native-token"
+            type="checkbox"
+            onChange={(event) => setWithNativeToken(event.target.checked)}
+            checked={withNativeToken}
+          />
+          <label htmlFor="with-native-token">Include Native Token for Gas</label>
+        </>
+      )}
       <button
         className="submit-button"
         disabled={accessToken === null ? true : false}
@@ -118,6 +172,7 @@ const ClaimFundsForm: React.FC<ClaimFundsFormProps> = ({ accessToken }) => {
       >
         Claim
       </button>
+      {progress && <p className="claim-progress">{progress}</p>}
     </form >
   );
 };
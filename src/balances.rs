@@ -0,0 +1,631 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::State;
+use serde::Serialize;
+use serde_json::json;
+use webb::evm::contract::protocol_solidity::erc20_preset_minter_pauser::ERC20PresetMinterPauserContract;
+use webb::evm::ethers::providers::Middleware;
+use webb::evm::ethers::types::U256;
+use webb::evm::ethers::utils::parse_units;
+use webb::substrate::subxt::utils::AccountId32;
+use webb::substrate::subxt::{OnlineClient, PolkadotConfig};
+use webb::substrate::tangle_runtime::api as RuntimeApi;
+use webb_auth::model::ClaimAsset;
+use webb_proposals::TypedChainId;
+
+use crate::error::Error;
+use crate::signers::SubstrateSigner;
+use crate::txes::assets;
+use crate::txes::networks::{DispenseMode, Network, NetworkRegistry};
+use crate::txes::types::{EthersClient, EvmWallets, SubstrateSigners};
+use crate::AppConfig;
+
+/// The balance of a hot wallet in one asset, as last seen by the balance
+/// monitor.
+#[derive(Clone, Debug, Serialize)]
+pub struct AssetBalance {
+    /// The address of the hot wallet.
+    pub wallet: String,
+    pub asset: ClaimAsset,
+    /// The balance in the smallest unit of the asset.
+    pub balance: U256,
+    /// The balance below which claims of the asset are refused.
+    pub threshold: U256,
+    /// The native balance below which claims of other assets are refused,
+    /// as the wallet could not pay their fees. Zero for other assets.
+    pub fee_reserve: U256,
+    pub updated_at: DateTime<Utc>,
+    /// The time after which the balance is too old to be relied on, as the
+    /// monitor failed to fetch it since.
+    pub expires_at: DateTime<Utc>,
+    /// The error of the last failed fetch of the balance, if it failed
+    /// since it was last fetched.
+    pub error: Option<String>,
+}
+
+impl AssetBalance {
+    /// Returns `true` if the wallet cannot pay out claims of the asset.
+    pub fn is_empty(&self) -> bool {
+        self.balance < self.threshold
+    }
+
+    /// Returns `true` if the wallet cannot pay the fees of claims of other
+    /// assets.
+    pub fn lacks_fees(&self) -> bool {
+        self.balance < self.fee_reserve
+    }
+
+    /// Returns `true` if the balance was not fetched for too long to be
+    /// relied on.
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
+}
+
+type BalanceKey = (TypedChainId, String, ClaimAsset);
+
+/// The latest balances of the hot wallets on every network, updated in the
+/// background by [`monitor_balances`].
+#[derive(Clone, Default)]
+pub struct Balances {
+    balances: Arc<Mutex<HashMap<BalanceKey, AssetBalance>>>,
+}
+
+impl Balances {
+    /// Records the latest balance of an asset, returning the previous one.
+    pub fn update(
+        &self,
+        typed_chain_id: TypedChainId,
+        balance: AssetBalance,
+    ) -> Option<AssetBalance> {
+        let key = (typed_chain_id, balance.wallet.clone(), balance.asset);
+        self.balances
+            .lock()
+            .expect("balances lock poisoned")
+            .insert(key, balance)
+    }
+
+    /// Records the error of a failed fetch of a balance, keeping the
+    /// previous balance until it expires.
+    pub fn update_failed(
+        &self,
+        typed_chain_id: TypedChainId,
+        wallet: &str,
+        asset: ClaimAsset,
+        error: &Error,
+    ) {
+        let key = (typed_chain_id, wallet.to_string(), asset);
+        if let Some(balance) = self
+            .balances
+            .lock()
+            .expect("balances lock poisoned")
+            .get_mut(&key)
+        {
+            balance.error = Some(error.to_string());
+        }
+    }
+
+    /// Returns the latest balances of every wallet and asset of a network.
+    pub fn of_chain(&self, typed_chain_id: TypedChainId) -> Vec<AssetBalance> {
+        self.balances
+            .lock()
+            .expect("balances lock poisoned")
+            .iter()
+            .filter(|((chain, _, _), _)| *chain == typed_chain_id)
+            .map(|(_, balance)| balance.clone())
+            .collect()
+    }
+
+    /// Checks that some hot wallet can pay out claims of every one of
+    /// `assets`. Balances that are not known yet, or expired, are assumed
+    /// to be funded.
+    pub fn check(
+        &self,
+        typed_chain_id: TypedChainId,
+        assets: &[ClaimAsset],
+    ) -> Result<(), Error> {
+        let balances = self.balances.lock().expect("balances lock poisoned");
+        let wallets = balances
+            .keys()
+            .filter(|(chain, _, _)| *chain == typed_chain_id)
+            .map(|(_, wallet, _)| wallet.as_str())
+            .collect::<HashSet<_>>();
+        let unpayable = wallets
+            .into_iter()
+            .map(|wallet| {
+                unpayable_asset(&balances, typed_chain_id, wallet, assets)
+            })
+            .collect::<Vec<_>>();
+        match unpayable.first() {
+            Some(Some(asset)) if unpayable.iter().all(Option::is_some) => {
+                Err(Error::FaucetEmpty {
+                    typed_chain_id,
+                    asset: *asset,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the first of `assets` a hot wallet cannot pay out claims of,
+    /// if any, or the native token if it cannot pay their fees.
+    pub fn empty_asset(
+        &self,
+        typed_chain_id: TypedChainId,
+        wallet: &str,
+        assets: &[ClaimAsset],
+    ) -> Option<ClaimAsset> {
+        let balances = self.balances.lock().expect("balances lock poisoned");
+        unpayable_asset(&balances, typed_chain_id, wallet, assets)
+    }
+}
+
+/// Returns the first of `assets` a hot wallet cannot pay out claims of, if
+/// any. Claims without the native token also need the native balance for
+/// their fees.
+fn unpayable_asset(
+    balances: &HashMap<BalanceKey, AssetBalance>,
+    typed_chain_id: TypedChainId,
+    wallet: &str,
+    assets: &[ClaimAsset],
+) -> Option<ClaimAsset> {
+    let known = |asset: ClaimAsset| {
+        balances
+            .get(&(typed_chain_id, wallet.to_string(), asset))
+            .filter(|balance| !balance.is_expired())
+    };
+    let empty = assets
+        .iter()
+        .copied()
+        .find(|asset| known(*asset).is_some_and(AssetBalance::is_empty));
+    empty.or_else(|| {
+        let lacks_fees = !assets.contains(&ClaimAsset::Native)
+            && known(ClaimAsset::Native).is_some_and(AssetBalance::lacks_fees);
+        lacks_fees.then_some(ClaimAsset::Native)
+    })
+}
+
+/// The number of rounds of the balance monitor a balance is relied on for,
+/// once the monitor fails to fetch it again.
+const EXPIRY_ROUNDS: u32 = 3;
+
+/// The gas the native balance of an EVM hot wallet has to pay for, at the
+/// current gas price, for claims of tokens to be paid out when the network
+/// sets no `min_fee_balance` or `max_gas_limit`.
+const TOKEN_CLAIM_GAS: u64 = 100_000;
+
+/// Fetches the balances of the hot wallets on every network every
+/// `interval`, logging the assets that run out and the ones that cannot be
+/// fetched.
+#[allow(clippy::too_many_arguments)]
+pub async fn monitor_balances(
+    networks: NetworkRegistry,
+    native_token_amount: f64,
+    token_amount: u64,
+    evm_wallets: HashMap<u64, EvmWallets>,
+    substrate_providers: HashMap<u64, OnlineClient<PolkadotConfig>>,
+    substrate_wallets: HashMap<u64, SubstrateSigners>,
+    balances: Balances,
+    interval: Duration,
+) {
+    println!("Balance monitor started");
+    let amounts = ClaimAmounts {
+        native_token_amount,
+        token_amount,
+        lifetime: chrono::Duration::from_std(interval * EXPIRY_ROUNDS)
+            .unwrap_or(chrono::Duration::max_value()),
+    };
+    loop {
+        for network in networks.enabled() {
+            let mut results = Vec::new();
+            match network.typed_chain_id {
+                TypedChainId::Evm(chain_id) => {
+                    let Some(wallets) = evm_wallets.get(&chain_id.into())
+                    else {
+                        continue;
+                    };
+                    for provider in wallets.iter() {
+                        results.push(
+                            evm_balances(network, &amounts, provider).await,
+                        );
+                    }
+                }
+                TypedChainId::Substrate(chain_id) => {
+                    let chain_id = u64::from(chain_id);
+                    let (Some(api), Some(wallets)) = (
+                        substrate_providers.get(&chain_id),
+                        substrate_wallets.get(&chain_id),
+                    ) else {
+                        continue;
+                    };
+                    for signer in wallets.iter() {
+                        let account = signer.account_id();
+                        results.push(
+                            substrate_balances(
+                                network, &amounts, api, &account,
+                            )
+                            .await,
+                        );
+                    }
+                }
+                _ => continue,
+            }
+            for res in results {
+                let WalletBalances { wallet, fetched } = match res {
+                    Ok(fetched) => fetched,
+                    Err(e) => {
+                        eprintln!(
+                            "Error fetching the balances on network {}: {e}",
+                            network.name
+                        );
+                        continue;
+                    }
+                };
+                for (asset, res) in fetched {
+                    let balance = match res {
+                        Ok(balance) => balance,
+                        Err(e) => {
+                            eprintln!(
+                                "Error fetching the balance of {asset} of {wallet} on network {}: {e}",
+                                network.name
+                            );
+                            balances.update_failed(
+                                network.typed_chain_id,
+                                &wallet,
+                                asset,
+                                &e,
+                            );
+                            continue;
+                        }
+                    };
+                    let emptied = balance.is_empty();
+                    let previous =
+                        balances.update(network.typed_chain_id, balance);
+                    if emptied && !previous.is_some_and(AssetBalance::is_empty)
+                    {
+                        rocket::log::private::warn!(
+                            "Hot wallet {wallet} ran out of {asset} on network {}",
+                            network.name
+                        );
+                    }
+                }
+            }
+        }
+        rocket::tokio::time::sleep(interval).await;
+    }
+}
+
+/// The balances of a hot wallet fetched in one round, each asset failing on
+/// its own.
+struct WalletBalances {
+    wallet: String,
+    fetched: Vec<(ClaimAsset, Result<AssetBalance, Error>)>,
+}
+
+/// The default amounts of a claim, which are the default thresholds of the
+/// balances, and the time fetched balances are relied on for.
+struct ClaimAmounts {
+    native_token_amount: f64,
+    token_amount: u64,
+    lifetime: chrono::Duration,
+}
+
+impl ClaimAmounts {
+    /// Returns a balance fetched now.
+    fn fetched(
+        &self,
+        wallet: &str,
+        asset: ClaimAsset,
+        balance: U256,
+        threshold: U256,
+    ) -> AssetBalance {
+        let updated_at = Utc::now();
+        AssetBalance {
+            wallet: wallet.to_string(),
+            asset,
+            balance,
+            threshold,
+            fee_reserve: U256::zero(),
+            updated_at,
+            expires_at: updated_at
+                .checked_add_signed(self.lifetime)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            error: None,
+        }
+    }
+
+    /// Returns the threshold of the native balance, in the smallest unit of
+    /// the native token.
+    fn native_threshold(&self, network: &Network) -> Result<U256, Error> {
+        let amount = network
+            .min_native_balance
+            .unwrap_or(self.native_token_amount);
+        native_units(network, amount)
+    }
+
+    /// Returns the threshold of a token balance, in the smallest unit of the
+    /// token.
+    fn token_threshold(
+        &self,
+        min_balance: Option<u64>,
+        amount: Option<u64>,
+        decimals: u8,
+    ) -> U256 {
+        let amount = min_balance.or(amount).unwrap_or(self.token_amount);
+        U256::from(amount) * U256::exp10(usize::from(decimals))
+    }
+}
+
+/// Converts an amount of whole native tokens of a network to its smallest
+/// unit.
+fn native_units(network: &Network, amount: f64) -> Result<U256, Error> {
+    parse_units(amount, u32::from(network.decimals))
+        .map(Into::into)
+        .map_err(|e| Error::Custom(e.to_string()))
+}
+
+/// Fetches the native and ERC20 balances of a hot wallet on an EVM
+/// network. Minted tokens are not held by the faucet, so they are skipped.
+async fn evm_balances(
+    network: &Network,
+    amounts: &ClaimAmounts,
+    provider: &EthersClient,
+) -> Result<WalletBalances, Error> {
+    let account = provider
+        .default_sender()
+        .ok_or(Error::Custom("Provider must have signer".to_string()))?;
+    let wallet = format!("{account:?}");
+    let native = async {
+        let balance = provider
+            .get_balance(account, None)
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?;
+        let fee_reserve = match network.min_fee_balance {
+            Some(amount) => native_units(network, amount)?,
+            None => {
+                let gas_price = provider
+                    .get_gas_price()
+                    .await
+                    .map_err(|e| Error::Custom(e.to_string()))?;
+                let gas = network.max_gas_limit.unwrap_or(TOKEN_CLAIM_GAS);
+                gas_price.saturating_mul(gas.into())
+            }
+        };
+        Ok::<_, Error>(AssetBalance {
+            fee_reserve,
+            ..amounts.fetched(
+                &wallet,
+                ClaimAsset::Native,
+                balance,
+                amounts.native_threshold(network)?,
+            )
+        })
+    };
+    let mut fetched = vec![(ClaimAsset::Native, native.await)];
+    let held_tokens = network
+        .tokens()
+        .into_iter()
+        .filter(|token| token.mode == DispenseMode::Transfer);
+    for token in held_tokens {
+        let asset = ClaimAsset::Token(token.address);
+        let contract = ERC20PresetMinterPauserContract::new(
+            token.address,
+            provider.clone(),
+        );
+        let balance = async {
+            let decimals = contract.decimals().call().await.map_err(|e| {
+                Error::Custom(format!("Failed to fetch decimals: {e}"))
+            })?;
+            let balance =
+                contract.balance_of(account).call().await.map_err(|e| {
+                    Error::Custom(format!("Failed to fetch balance: {e}"))
+                })?;
+            let threshold = amounts.token_threshold(
+                token.min_balance,
+                token.amount,
+                decimals,
+            );
+            Ok::<_, Error>(amounts.fetched(&wallet, asset, balance, threshold))
+        };
+        fetched.push((asset, balance.await));
+    }
+    Ok(WalletBalances { wallet, fetched })
+}
+
+/// Fetches the native and asset balances of a hot wallet on a Substrate
+/// network.
+async fn substrate_balances(
+    network: &Network,
+    amounts: &ClaimAmounts,
+    api: &OnlineClient<PolkadotConfig>,
+    account: &AccountId32,
+) -> Result<WalletBalances, Error> {
+    let wallet = account.to_string();
+    let storage = api
+        .storage()
+        .at_latest()
+        .await
+        .map_err(|e| Error::Custom(e.to_string()))?;
+    let native = async {
+        let account_info = storage
+            .fetch_or_default(&RuntimeApi::storage().system().account(account))
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?;
+        let fee_reserve = network
+            .min_fee_balance
+            .map(|amount| native_units(network, amount))
+            .transpose()?
+            .unwrap_or_default();
+        Ok::<_, Error>(AssetBalance {
+            fee_reserve,
+            ..amounts.fetched(
+                &wallet,
+                ClaimAsset::Native,
+                account_info.data.free.into(),
+                amounts.native_threshold(network)?,
+            )
+        })
+    };
+    let mut fetched = vec![(ClaimAsset::Native, native.await)];
+    for asset in &network.assets {
+        let claim_asset = ClaimAsset::Asset(asset.asset_id);
+        let balance =
+            assets::balance(&storage, asset, account)
+                .await
+                .map(|balance| {
+                    let threshold = amounts.token_threshold(
+                        asset.min_balance,
+                        asset.amount,
+                        asset.decimals,
+                    );
+                    amounts.fetched(
+                        &wallet,
+                        claim_asset,
+                        balance.into(),
+                        threshold,
+                    )
+                });
+        fetched.push((claim_asset, balance));
+    }
+    Ok(WalletBalances { wallet, fetched })
+}
+
+/// Returns the latest balances of the hot wallets on every network, whether
+/// each wallet ran out of each asset or of the fees of the others, and
+/// whether each balance expired.
+#[get("/balances")]
+pub async fn balances(
+    app_config: &State<AppConfig>,
+    balances: &State<Balances>,
+) -> Result<status::Custom<String>, Error> {
+    let networks = app_config
+        .networks
+        .enabled()
+        .map(|network| {
+            let assets = balances
+                .of_chain(network.typed_chain_id)
+                .into_iter()
+                .map(|balance| {
+                    json!({
+                        "empty": balance.is_empty(),
+                        "lacks_fees": balance.lacks_fees(),
+                        "expired": balance.is_expired(),
+                        "balance": balance,
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "name": network.name,
+                "typed_chain_id": network.typed_chain_id,
+                "balances": assets,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(status::Custom(
+        Status::Ok,
+        json!({ "networks": networks }).to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use webb::evm::ethers::types::Address;
+
+    use super::*;
+
+    const CHAIN: TypedChainId = TypedChainId::Evm(5);
+
+    fn balance(
+        wallet: &str,
+        asset: ClaimAsset,
+        balance: u64,
+        threshold: u64,
+    ) -> AssetBalance {
+        let amounts = ClaimAmounts {
+            native_token_amount: 0.0,
+            token_amount: 0,
+            lifetime: chrono::Duration::minutes(1),
+        };
+        amounts.fetched(wallet, asset, balance.into(), threshold.into())
+    }
+
+    #[test]
+    fn refuses_token_claims_of_wallets_without_fees() {
+        let token = ClaimAsset::Token(Address::repeat_byte(1));
+        let balances = Balances::default();
+        balances.update(
+            CHAIN,
+            AssetBalance {
+                fee_reserve: 10.into(),
+                ..balance("a", ClaimAsset::Native, 5, 100)
+            },
+        );
+        balances.update(CHAIN, balance("a", token, 1000, 100));
+        assert_eq!(
+            balances.empty_asset(CHAIN, "a", &[token]),
+            Some(ClaimAsset::Native)
+        );
+        assert_eq!(
+            balances.empty_asset(CHAIN, "a", &[ClaimAsset::Native, token]),
+            Some(ClaimAsset::Native)
+        );
+        balances.update(
+            CHAIN,
+            AssetBalance {
+                fee_reserve: 1.into(),
+                ..balance("a", ClaimAsset::Native, 5, 100)
+            },
+        );
+        assert_eq!(balances.empty_asset(CHAIN, "a", &[token]), None);
+    }
+
+    #[test]
+    fn ignores_expired_balances() {
+        let balances = Balances::default();
+        let mut empty = balance("a", ClaimAsset::Native, 0, 100);
+        balances.update(CHAIN, empty.clone());
+        assert!(balances.check(CHAIN, &[ClaimAsset::Native]).is_err());
+        empty.expires_at = empty.updated_at;
+        balances.update(CHAIN, empty);
+        assert!(balances.check(CHAIN, &[ClaimAsset::Native]).is_ok());
+    }
+
+    #[test]
+    fn keeps_balances_that_fail_to_be_fetched() {
+        let balances = Balances::default();
+        balances.update(CHAIN, balance("a", ClaimAsset::Native, 0, 100));
+        let error = Error::Custom("unreachable".to_string());
+        balances.update_failed(CHAIN, "a", ClaimAsset::Native, &error);
+        balances.update_failed(CHAIN, "b", ClaimAsset::Native, &error);
+        let known = balances.of_chain(CHAIN);
+        assert_eq!(known.len(), 1);
+        assert_eq!(
+            known[0].error.as_deref(),
+            Some("Custom error: unreachable")
+        );
+        assert!(known[0].is_empty());
+    }
+
+    #[test]
+    fn checks_that_one_wallet_pays_every_asset() {
+        let token = ClaimAsset::Token(Address::repeat_byte(1));
+        let balances = Balances::default();
+        balances.update(CHAIN, balance("a", ClaimAsset::Native, 0, 100));
+        balances.update(CHAIN, balance("a", token, 1000, 100));
+        balances.update(CHAIN, balance("b", ClaimAsset::Native, 1000, 100));
+        balances.update(CHAIN, balance("b", token, 0, 100));
+        let both = [ClaimAsset::Native, token];
+        assert!(balances.check(CHAIN, &[ClaimAsset::Native]).is_ok());
+        assert!(balances.check(CHAIN, &[token]).is_ok());
+        assert!(matches!(
+            balances.check(CHAIN, &both),
+            Err(Error::FaucetEmpty { .. })
+        ));
+        assert!(Balances::default().check(CHAIN, &both).is_ok());
+    }
+}
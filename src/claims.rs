@@ -0,0 +1,584 @@
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::sync::broadcast::{self, error::RecvError};
+use rocket::tokio::sync::mpsc::UnboundedReceiver;
+use rocket::tokio::sync::oneshot;
+use rocket::{Shutdown, State};
+use serde_json::json;
+use webb_auth::{
+    model::{ClaimAsset, ClaimStatus, ClaimTicket, ClaimsData, SentTx},
+    AuthDb,
+};
+use webb_auth_sled::SledAuthDb;
+use webb_proposals::TypedChainId;
+
+use crate::error::Error;
+use crate::faucet::{transfer_result, QueuedTransfer};
+use crate::txes::types::{LateTransfer, TransferLeg, TxEvent, TxResult};
+
+/// Broadcasts the status changes of the claim tickets that are being
+/// processed to their subscribers.
+#[derive(Clone, Default)]
+pub struct ClaimEvents {
+    channels: Arc<Mutex<HashMap<u64, broadcast::Sender<ClaimStatus>>>>,
+}
+
+impl ClaimEvents {
+    /// Starts tracking the status changes of a claim ticket. Returns
+    /// `false` if the ticket is already tracked.
+    pub fn register(&self, id: u64) -> bool {
+        let mut channels =
+            self.channels.lock().expect("claim events lock poisoned");
+        match channels.entry(id) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(broadcast::channel(16).0);
+                true
+            }
+        }
+    }
+
+    /// Stops tracking the status changes of a claim ticket.
+    pub fn unregister(&self, id: u64) {
+        self.channels
+            .lock()
+            .expect("claim events lock poisoned")
+            .remove(&id);
+    }
+
+    /// Subscribes to the status changes of a claim ticket.
+    /// Returns `None` if the ticket is not being processed.
+    pub fn subscribe(
+        &self,
+        id: u64,
+    ) -> Option<broadcast::Receiver<ClaimStatus>> {
+        self.channels
+            .lock()
+            .expect("claim events lock poisoned")
+            .get(&id)
+            .map(broadcast::Sender::subscribe)
+    }
+
+    /// Publishes a status change of a claim ticket. The ticket stops being
+    /// tracked once its status is final.
+    pub fn publish(&self, id: u64, status: &ClaimStatus) {
+        let mut channels =
+            self.channels.lock().expect("claim events lock poisoned");
+        if let Some(sender) = channels.get(&id) {
+            let _ = sender.send(status.clone());
+        }
+        if status.is_final() {
+            channels.remove(&id);
+        }
+    }
+}
+
+/// Stores a new claim ticket under a random id, which is set on the ticket.
+///
+/// The ticket is tracked by `claim_events` before it is stored, so that no
+/// status change is missed by a subscriber that finds it.
+pub async fn create_claim_ticket(
+    auth_db: &SledAuthDb,
+    claim_events: &ClaimEvents,
+    ticket: &mut ClaimTicket,
+) -> Result<(), webb_auth_sled::Error> {
+    loop {
+        ticket.id = auth_db.next_claim_ticket_id().await?;
+        if !claim_events.register(ticket.id) {
+            continue;
+        }
+        match auth_db.insert_claim_ticket(ticket).await {
+            Ok(true) => return Ok(()),
+            // The id is taken by an older ticket, draw another one.
+            Ok(false) => claim_events.unregister(ticket.id),
+            Err(e) => {
+                claim_events.unregister(ticket.id);
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Waits for the result of a queued transfer, recording every transaction
+/// it sends on the reserved claim of the asset it pays out, so that the
+/// claim can be settled from them if the faucet restarts. If the transfer
+/// has a claim ticket, its status changes are persisted and published.
+pub async fn follow_transfer(
+    auth_db: &SledAuthDb,
+    claim: &ClaimsData,
+    typed_chain_id: TypedChainId,
+    event_receiver: &mut UnboundedReceiver<TxEvent>,
+    result_receiver: oneshot::Receiver<Result<TxResult, Error>>,
+    mut ticket: Option<(&ClaimEvents, &mut ClaimTicket)>,
+) -> Result<TxResult, Error> {
+    let result = transfer_result(result_receiver);
+    rocket::tokio::pin!(result);
+    loop {
+        rocket::tokio::select! {
+            // The events of the transfer are sent before its result, so they
+            // are all handled before the result is.
+            biased;
+            Some(event) = event_receiver.recv() => {
+                let status = match event {
+                    TxEvent::Submitted { asset, tx_hash, from, nonce } => {
+                        let tx_hash = format!("{tx_hash:?}");
+                        let sent = SentTx {
+                            tx_hash: tx_hash.clone(),
+                            from,
+                            nonce,
+                        };
+                        let res = auth_db
+                            .record_claim_tx(
+                                claim.identity,
+                                typed_chain_id,
+                                asset,
+                                sent,
+                            )
+                            .await;
+                        if let Err(e) = res {
+                            eprintln!(
+                                "Error recording transaction {tx_hash} of claim of {asset}: {e}"
+                            );
+                        }
+                        if let Some((_, ticket)) = &mut ticket {
+                            ticket.tx_hashes.push(tx_hash.clone());
+                        }
+                        ClaimStatus::Broadcast { tx_hash }
+                    }
+                    TxEvent::InBlock { block_hash } => ClaimStatus::InBlock {
+                        block_hash: format!("{block_hash:?}"),
+                    },
+                    // Only reported after the result.
+                    TxEvent::LateInclusion { .. } => continue,
+                };
+                if let Some((claim_events, ticket)) = &mut ticket {
+                    update_ticket(auth_db, claim_events, ticket, status)
+                        .await;
+                }
+            }
+            result = &mut result => break result,
+        }
+    }
+}
+
+/// Follows a queued transfer until it completes, persisting and publishing
+/// every status change of its claim ticket and committing or releasing the
+/// claim.
+///
+/// A transfer that timed out but is still tracked is marked as timed out,
+/// and becomes included if it makes it after all, or failed once it stops
+/// being tracked.
+pub async fn track_claim_ticket(
+    auth_db: SledAuthDb,
+    claim_events: ClaimEvents,
+    mut ticket: ClaimTicket,
+    claim: ClaimsData,
+    assets: Vec<ClaimAsset>,
+    transfer: QueuedTransfer,
+) {
+    let QueuedTransfer {
+        mut event_receiver,
+        result_receiver,
+    } = transfer;
+    let typed_chain_id = ticket.typed_chain_id;
+    let result = follow_transfer(
+        &auth_db,
+        &claim,
+        typed_chain_id,
+        &mut event_receiver,
+        result_receiver,
+        Some((&claim_events, &mut ticket)),
+    )
+    .await;
+
+    let res =
+        settle_claims(&auth_db, &claim, typed_chain_id, &assets, &result).await;
+    if let Err(e) = res {
+        eprintln!("Error settling claim of ticket {}: {e}", ticket.id);
+    }
+    let tracked = still_tracked_assets(&assets, &result);
+    let still_tracked = result.as_ref().is_err_and(Error::is_still_tracked);
+    let status = match result {
+        Ok(tx_result) => {
+            println!(
+                "{:?} Paid {} on chain: {:?}",
+                Utc::now().to_rfc3339(),
+                claim.address,
+                typed_chain_id
+            );
+            ClaimStatus::Included {
+                tx_result: serde_json::to_value(&tx_result).unwrap_or_default(),
+            }
+        }
+        Err(e) if still_tracked => ClaimStatus::TimedOut {
+            code: Some(format!("{:?}", e.code())),
+            reason: e.to_string(),
+        },
+        Err(e) => {
+            rocket::log::private::error!("Error transferring tokens: {e:?}");
+            failed_status(&e)
+        }
+    };
+    update_ticket(&auth_db, &claim_events, &mut ticket, status).await;
+
+    let late_result = track_late_inclusions(
+        auth_db.clone(),
+        claim,
+        typed_chain_id,
+        assets,
+        tracked,
+        event_receiver,
+    )
+    .await;
+    let status = match (late_result, &ticket.status) {
+        (Some(tx_result), _) => ClaimStatus::Included {
+            tx_result: serde_json::to_value(&tx_result).unwrap_or_default(),
+        },
+        (None, ClaimStatus::TimedOut { code, reason }) => ClaimStatus::Failed {
+            code: code.clone(),
+            reason: reason.clone(),
+        },
+        (None, _) => return,
+    };
+    update_ticket(&auth_db, &claim_events, &mut ticket, status).await;
+}
+
+/// Records the transfers of a claim that are included after its result
+/// was reported because they timed out, until the processor stops tracking
+/// them. The claims of the assets they pay out are committed.
+///
+/// The claims of the `tracked` assets, whose transfers timed out, were left
+/// reserved, and the ones that were never paid out are released. Returns
+/// the late transfers, if any.
+pub async fn track_late_inclusions(
+    auth_db: SledAuthDb,
+    claim: ClaimsData,
+    typed_chain_id: TypedChainId,
+    assets: Vec<ClaimAsset>,
+    tracked: Vec<ClaimAsset>,
+    mut event_receiver: UnboundedReceiver<TxEvent>,
+) -> Option<TxResult> {
+    let mut transfers = Vec::new();
+    while let Some(event) = event_receiver.recv().await {
+        let TxEvent::LateInclusion { asset, transfer } = event else {
+            continue;
+        };
+        println!(
+            "{:?} Paid {} on chain: {:?} after a timeout",
+            Utc::now().to_rfc3339(),
+            claim.address,
+            typed_chain_id
+        );
+        if assets.contains(&asset) {
+            let res = auth_db
+                .commit_claim(
+                    claim.identity,
+                    typed_chain_id,
+                    asset,
+                    claim.clone(),
+                )
+                .await;
+            if let Err(e) = res {
+                eprintln!("Error committing late claim of {asset}: {e}");
+            }
+        }
+        transfers.push((asset, transfer));
+    }
+
+    let late_result = late_result(transfers);
+    for asset in &tracked {
+        if late_result.as_ref().is_some_and(|r| r.paid(asset)) {
+            continue;
+        }
+        let res = auth_db
+            .release_claim(claim.identity, typed_chain_id, *asset)
+            .await;
+        if let Err(e) = res {
+            eprintln!("Error releasing claim of {asset}: {e}");
+        }
+    }
+    late_result
+}
+
+/// Combines the transfers of a claim that were included late into the
+/// result of the claim, if any.
+fn late_result(transfers: Vec<(ClaimAsset, LateTransfer)>) -> Option<TxResult> {
+    let mut evm_native = None;
+    let mut tokens = BTreeMap::new();
+    let mut substrate_native = None;
+    let mut assets = BTreeMap::new();
+    for (asset, transfer) in transfers {
+        match (asset, transfer) {
+            (ClaimAsset::Native, LateTransfer::Evm(receipt)) => {
+                evm_native = Some(TransferLeg::Succeeded(receipt));
+            }
+            (ClaimAsset::Token(address), LateTransfer::Evm(receipt)) => {
+                tokens.insert(address, TransferLeg::Succeeded(receipt));
+            }
+            (ClaimAsset::Native, LateTransfer::Substrate(transfer)) => {
+                substrate_native = Some(TransferLeg::Succeeded(transfer));
+            }
+            (
+                ClaimAsset::Asset(asset_id),
+                LateTransfer::Substrate(transfer),
+            ) => {
+                assets.insert(asset_id, TransferLeg::Succeeded(transfer));
+            }
+            (asset, _) => {
+                eprintln!("Ignoring late transfer of {asset} on another chain");
+            }
+        }
+    }
+    if evm_native.is_some() || !tokens.is_empty() {
+        Some(TxResult::EvmCombined {
+            native: evm_native,
+            tokens,
+        })
+    } else if substrate_native.is_some() || !assets.is_empty() {
+        Some(TxResult::SubstrateCombined {
+            native: substrate_native,
+            assets,
+        })
+    } else {
+        None
+    }
+}
+
+/// Returns the assets of a claim whose transfers timed out but are still
+/// tracked, as the whole transfer or as one of the transfers of a combined
+/// claim.
+pub fn still_tracked_assets(
+    assets: &[ClaimAsset],
+    result: &Result<TxResult, Error>,
+) -> Vec<ClaimAsset> {
+    assets
+        .iter()
+        .copied()
+        .filter(|asset| match result {
+            Ok(tx_result) => tx_result.still_tracked(asset),
+            Err(e) => e.is_still_tracked(),
+        })
+        .collect()
+}
+
+/// Commits the reserved claims of the assets paid out by `result` and
+/// releases the others.
+///
+/// The claims of the transfers that timed out but are still tracked stay
+/// reserved, see [`track_late_inclusions`].
+pub async fn settle_claims(
+    auth_db: &SledAuthDb,
+    claim: &ClaimsData,
+    typed_chain_id: TypedChainId,
+    assets: &[ClaimAsset],
+    result: &Result<TxResult, Error>,
+) -> Result<(), webb_auth_sled::Error> {
+    let tracked = still_tracked_assets(assets, result);
+    for asset in assets {
+        if tracked.contains(asset) {
+            continue;
+        }
+        if result.as_ref().is_ok_and(|tx_result| tx_result.paid(asset)) {
+            auth_db
+                .commit_claim(
+                    claim.identity,
+                    typed_chain_id,
+                    *asset,
+                    claim.clone(),
+                )
+                .await?;
+        } else {
+            auth_db
+                .release_claim(claim.identity, typed_chain_id, *asset)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Releases the reserved claims of `assets` without storing them.
+pub async fn release_claims(
+    auth_db: &SledAuthDb,
+    identity: u64,
+    typed_chain_id: TypedChainId,
+    assets: &[ClaimAsset],
+) -> Result<(), webb_auth_sled::Error> {
+    for asset in assets {
+        auth_db
+            .release_claim(identity, typed_chain_id, *asset)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Persists a new status of a claim ticket and publishes it.
+pub async fn update_ticket(
+    auth_db: &SledAuthDb,
+    claim_events: &ClaimEvents,
+    ticket: &mut ClaimTicket,
+    status: ClaimStatus,
+) {
+    ticket.status = status;
+    ticket.updated_at = Utc::now();
+    if let Err(e) = auth_db.put_claim_ticket(ticket).await {
+        eprintln!("Error updating claim ticket {}: {e}", ticket.id);
+    }
+    claim_events.publish(ticket.id, &ticket.status);
+}
+
+/// Returns the status of a claim whose transfer failed with `e`.
+pub fn failed_status(e: &Error) -> ClaimStatus {
+    ClaimStatus::Failed {
+        code: Some(format!("{:?}", e.code())),
+        reason: e.to_string(),
+    }
+}
+
+fn status_event(status: &ClaimStatus) -> Event {
+    Event::json(status).event("status")
+}
+
+#[get("/claims/<id>")]
+pub async fn claim_status(
+    id: u64,
+    auth_db: &State<SledAuthDb>,
+) -> Result<status::Custom<String>, Error> {
+    match auth_db.get_claim_ticket(id).await? {
+        Some(ticket) => Ok(status::Custom(
+            Status::Ok,
+            json!({
+                "ticket_id": ticket.id,
+                "wallet": ticket.address.to_string(),
+                "typed_chain_id": ticket.typed_chain_id,
+                "status": ticket.status,
+                "created_at": ticket.created_at,
+                "updated_at": ticket.updated_at,
+            })
+            .to_string(),
+        )),
+        None => Ok(status::Custom(
+            Status::NotFound,
+            json!({
+                "error": "Claim not found",
+                "ticket_id": id,
+            })
+            .to_string(),
+        )),
+    }
+}
+
+/// Streams the status changes of a claim ticket as Server-Sent Events,
+/// starting with its current status and ending with its final one.
+#[get("/claims/<id>/events")]
+pub async fn claim_events(
+    id: u64,
+    auth_db: &State<SledAuthDb>,
+    claim_events: &State<ClaimEvents>,
+    mut shutdown: Shutdown,
+) -> Result<Option<EventStream![]>, Error> {
+    // Subscribe before reading the ticket, so no status change is missed.
+    let subscription = claim_events.subscribe(id);
+    let ticket = match auth_db.get_claim_ticket(id).await? {
+        Some(ticket) => ticket,
+        None => return Ok(None),
+    };
+
+    Ok(Some(EventStream! {
+        yield status_event(&ticket.status);
+        if ticket.status.is_final() {
+            return;
+        }
+        let mut subscription = match subscription {
+            Some(subscription) => subscription,
+            None => return,
+        };
+        loop {
+            let status = rocket::tokio::select! {
+                status = subscription.recv() => match status {
+                    Ok(status) => status,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => continue,
+                },
+                _ = &mut shutdown => break,
+            };
+            yield status_event(&status);
+            if status.is_final() {
+                break;
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use webb::evm::ethers::types::{Address, TransactionReceipt};
+
+    use super::*;
+    use crate::txes::types::SubstrateTransfer;
+
+    #[test]
+    fn combines_late_evm_transfers() {
+        let token = Address::repeat_byte(1);
+        let result = late_result(vec![
+            (
+                ClaimAsset::Token(token),
+                LateTransfer::Evm(TransactionReceipt::default()),
+            ),
+            (
+                ClaimAsset::Asset(1),
+                LateTransfer::Substrate(SubstrateTransfer {
+                    block_hash: Default::default(),
+                    tx_hash: Default::default(),
+                }),
+            ),
+        ])
+        .unwrap();
+        assert!(matches!(result, TxResult::EvmCombined { .. }));
+        assert!(result.paid(&ClaimAsset::Token(token)));
+        assert!(!result.paid(&ClaimAsset::Native));
+    }
+
+    #[test]
+    fn combines_late_substrate_transfers() {
+        let transfer = SubstrateTransfer {
+            block_hash: Default::default(),
+            tx_hash: Default::default(),
+        };
+        let result = late_result(vec![(
+            ClaimAsset::Native,
+            LateTransfer::Substrate(transfer),
+        )])
+        .unwrap();
+        assert!(matches!(result, TxResult::SubstrateCombined { .. }));
+        assert!(result.paid(&ClaimAsset::Native));
+        assert!(!result.paid(&ClaimAsset::Asset(1)));
+        assert!(late_result(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn keeps_claims_of_tracked_transfers_reserved() {
+        let token = Address::repeat_byte(1);
+        let assets = [ClaimAsset::Native, ClaimAsset::Token(token)];
+        let result = Ok(TxResult::EvmCombined {
+            native: Some(TransferLeg::Succeeded(TransactionReceipt::default())),
+            tokens: [(token, TransferLeg::TimedOut("timed out".to_string()))]
+                .into(),
+        });
+        assert_eq!(
+            still_tracked_assets(&assets, &result),
+            [ClaimAsset::Token(token)]
+        );
+
+        let result = Err(Error::InclusionTimeout {
+            tx_hash: Default::default(),
+        });
+        assert_eq!(still_tracked_assets(&assets, &result), assets);
+        let result = Err(Error::SubmissionTimeout);
+        assert!(still_tracked_assets(&assets, &result).is_empty());
+    }
+}
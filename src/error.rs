@@ -16,9 +16,136 @@ pub enum Error {
     TwitterError(#[from] twitter_v2::error::Error),
     #[error("Custom error: {0}")]
     Custom(String),
+    #[error(
+        "Transaction {tx_hash:?} reverted: {}",
+        reason.as_deref().unwrap_or("no reason given")
+    )]
+    TransactionReverted {
+        tx_hash: webb::evm::ethers::types::H256,
+        reason: Option<String>,
+    },
+    #[error("Extrinsic {tx_hash:?} failed: {reason}")]
+    ExtrinsicFailed {
+        tx_hash: webb::evm::ethers::types::H256,
+        reason: String,
+    },
+    #[error(
+        "Timed out waiting for tx to be sent to the network, please try again"
+    )]
+    SubmissionTimeout,
+    #[error("Timed out waiting for tx {tx_hash:?} to be included in a block")]
+    InclusionTimeout {
+        tx_hash: webb::evm::ethers::types::H256,
+    },
+    #[error(
+        "Timed out waiting for block {block_hash:?} of tx {tx_hash:?} to be finalized"
+    )]
+    FinalizationTimeout {
+        tx_hash: webb::evm::ethers::types::H256,
+        block_hash: webb::evm::ethers::types::H256,
+    },
+    #[error(
+        "Timed out waiting for tx {tx_hash:?} to get {confirmations} confirmations"
+    )]
+    ConfirmationTimeout {
+        tx_hash: webb::evm::ethers::types::H256,
+        confirmations: u64,
+    },
+    #[error(
+        "The fee per gas of {fee_per_gas} wei is above the maximum of {max_fee_per_gas} wei, please try again later"
+    )]
+    FeeTooHigh {
+        fee_per_gas: webb::evm::ethers::types::U256,
+        max_fee_per_gas: webb::evm::ethers::types::U256,
+    },
+    #[error(
+        "The faucet ran out of {asset} on chain {typed_chain_id:?}, please try again later"
+    )]
+    FaucetEmpty {
+        typed_chain_id: webb_proposals::TypedChainId,
+        asset: webb_auth::model::ClaimAsset,
+    },
 }
 
-#[derive(Copy, Clone, Debug, serde::Serialize, serde::Deserialize)]
+impl Error {
+    /// Returns `true` if the transaction timed out, but is still tracked in
+    /// the background as it may yet be included.
+    pub fn is_still_tracked(&self) -> bool {
+        matches!(
+            self,
+            Error::InclusionTimeout { .. }
+                | Error::FinalizationTimeout { .. }
+                | Error::ConfirmationTimeout { .. }
+        )
+    }
+
+    /// Returns the code clients tell this error apart by.
+    pub fn code(&self) -> FaucetErrorCode {
+        match self {
+            Error::Oauth2(err) => match err.kind() {
+                rocket_oauth2::ErrorKind::InvalidUri(_) => {
+                    FaucetErrorCode::Oauth2InvalidUri
+                }
+                rocket_oauth2::ErrorKind::ExchangeFailure
+                | rocket_oauth2::ErrorKind::ExchangeError(_) => {
+                    FaucetErrorCode::Oauth2ExchangeFailure
+                }
+                rocket_oauth2::ErrorKind::Other => {
+                    FaucetErrorCode::Oauth2Unknown
+                }
+            },
+            Error::AuthDatabase(err) => match err {
+                webb_auth_sled::Error::Sled(_) => {
+                    FaucetErrorCode::DatabaseError
+                }
+                webb_auth_sled::Error::InvalidU65Id(_)
+                | webb_auth_sled::Error::InvalidI65Id(_)
+                | webb_auth_sled::Error::Serde(_) => {
+                    FaucetErrorCode::DataSerializationError
+                }
+            },
+            Error::TwitterError(_) => FaucetErrorCode::TwitterApiError,
+            Error::Custom(_) => FaucetErrorCode::CustomError,
+            Error::TransactionReverted { .. } => {
+                FaucetErrorCode::TransactionReverted
+            }
+            Error::ExtrinsicFailed { .. } => FaucetErrorCode::ExtrinsicFailed,
+            Error::SubmissionTimeout => FaucetErrorCode::SubmissionTimeout,
+            Error::InclusionTimeout { .. } => FaucetErrorCode::InclusionTimeout,
+            Error::FinalizationTimeout { .. } => {
+                FaucetErrorCode::FinalizationTimeout
+            }
+            Error::ConfirmationTimeout { .. } => {
+                FaucetErrorCode::ConfirmationTimeout
+            }
+            Error::FeeTooHigh { .. } => FaucetErrorCode::FeeTooHigh,
+            Error::FaucetEmpty { .. } => FaucetErrorCode::FaucetEmpty,
+        }
+    }
+
+    /// Returns the HTTP status of the response to this error.
+    pub fn status(&self) -> Status {
+        match self {
+            Error::Oauth2(_) | Error::TwitterError(_) | Error::Custom(_) => {
+                Status::BadRequest
+            }
+            Error::AuthDatabase(_)
+            | Error::TransactionReverted { .. }
+            | Error::ExtrinsicFailed { .. } => Status::InternalServerError,
+            Error::SubmissionTimeout
+            | Error::InclusionTimeout { .. }
+            | Error::FinalizationTimeout { .. }
+            | Error::ConfirmationTimeout { .. } => Status::GatewayTimeout,
+            Error::FeeTooHigh { .. } | Error::FaucetEmpty { .. } => {
+                Status::ServiceUnavailable
+            }
+        }
+    }
+}
+
+#[derive(
+    Copy, Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize,
+)]
 pub enum FaucetErrorCode {
     /// An error occurred during URI parsing or construction. This usually means
     /// the token exchange endpoint is incorrect. The attempted URI is included.
@@ -36,6 +163,24 @@ pub enum FaucetErrorCode {
     TwitterApiError = 300000,
     /// An Unknown error occurred.
     CustomError = 400000,
+    /// A transaction was included in a block, but reverted.
+    TransactionReverted = 500000,
+    /// An extrinsic was included in a block, but failed to dispatch.
+    ExtrinsicFailed = 500001,
+    /// The node did not accept a transaction in time.
+    SubmissionTimeout = 500002,
+    /// A transaction was not included in a block in time.
+    InclusionTimeout = 500003,
+    /// The block of a transaction was not finalized in time.
+    FinalizationTimeout = 500004,
+    /// The fees of a network are above the maximum the faucet pays.
+    FeeTooHigh = 500005,
+    /// The faucet account does not hold enough of an asset to pay out a
+    /// claim.
+    FaucetEmpty = 500006,
+    /// A transaction did not get its confirmations on an EVM network in
+    /// time.
+    ConfirmationTimeout = 500007,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -46,69 +191,10 @@ pub struct ErrorResponse {
 
 impl<'r, 'o: 'r> Responder<'r, 'o> for Error {
     fn respond_to(self, req: &'r Request<'_>) -> Result<'o> {
-        let (response, status) = match self {
-            Error::Oauth2(ref err) => match err.kind() {
-                rocket_oauth2::ErrorKind::InvalidUri(_) => (
-                    ErrorResponse {
-                        code: FaucetErrorCode::Oauth2InvalidUri,
-                        message: self.to_string(),
-                    },
-                    Status::BadRequest,
-                ),
-                rocket_oauth2::ErrorKind::ExchangeFailure
-                | rocket_oauth2::ErrorKind::ExchangeError(_) => (
-                    ErrorResponse {
-                        code: FaucetErrorCode::Oauth2ExchangeFailure,
-                        message: self.to_string(),
-                    },
-                    Status::BadRequest,
-                ),
-                rocket_oauth2::ErrorKind::Other => (
-                    ErrorResponse {
-                        code: FaucetErrorCode::Oauth2Unknown,
-                        message: self.to_string(),
-                    },
-                    Status::BadRequest,
-                ),
-            },
-            Error::AuthDatabase(ref err) => match err {
-                webb_auth_sled::Error::Sled(_) => (
-                    ErrorResponse {
-                        code: FaucetErrorCode::DatabaseError,
-                        message: self.to_string(),
-                    },
-                    Status::InternalServerError,
-                ),
-                webb_auth_sled::Error::InvalidU65Id(_)
-                | webb_auth_sled::Error::InvalidI65Id(_) => (
-                    ErrorResponse {
-                        code: FaucetErrorCode::DataSerializationError,
-                        message: self.to_string(),
-                    },
-                    Status::InternalServerError,
-                ),
-                webb_auth_sled::Error::Serde(_) => (
-                    ErrorResponse {
-                        code: FaucetErrorCode::DataSerializationError,
-                        message: self.to_string(),
-                    },
-                    Status::InternalServerError,
-                ),
-            },
-            Error::TwitterError(_) => (
-                ErrorResponse {
-                    code: FaucetErrorCode::TwitterApiError,
-                    message: self.to_string(),
-                },
-                Status::BadRequest,
-            ),
-            Error::Custom(_) => (
-                ErrorResponse {
-                    code: FaucetErrorCode::CustomError,
-                    message: self.to_string(),
-                },
-                Status::BadRequest,
-            ),
+        let status = self.status();
+        let response = ErrorResponse {
+            code: self.code(),
+            message: self.to_string(),
         };
 
         Response::build_from(Json(response).respond_to(req).unwrap())
@@ -116,3 +202,54 @@ impl<'r, 'o: 'r> Responder<'r, 'o> for Error {
             .ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use webb::evm::ethers::types::H256;
+
+    use super::*;
+
+    #[test]
+    fn timeouts_have_distinct_codes() {
+        let tx_hash = H256::repeat_byte(1);
+        let errors = [
+            Error::SubmissionTimeout,
+            Error::InclusionTimeout { tx_hash },
+            Error::FinalizationTimeout {
+                tx_hash,
+                block_hash: H256::repeat_byte(2),
+            },
+            Error::ConfirmationTimeout {
+                tx_hash,
+                confirmations: 12,
+            },
+        ];
+        let codes: Vec<_> = errors.iter().map(Error::code).collect();
+        assert_eq!(
+            codes,
+            [
+                FaucetErrorCode::SubmissionTimeout,
+                FaucetErrorCode::InclusionTimeout,
+                FaucetErrorCode::FinalizationTimeout,
+                FaucetErrorCode::ConfirmationTimeout,
+            ]
+        );
+        for error in &errors {
+            assert_eq!(error.status(), Status::GatewayTimeout);
+        }
+    }
+
+    #[test]
+    fn claim_tickets_store_the_codes_of_error_responses() {
+        // Failed claim tickets store the code as formatted with `Debug`.
+        let code = Error::FeeTooHigh {
+            fee_per_gas: 2.into(),
+            max_fee_per_gas: 1.into(),
+        }
+        .code();
+        assert_eq!(
+            serde_json::to_value(code).unwrap(),
+            serde_json::Value::String(format!("{code:?}"))
+        );
+    }
+}
@@ -2,7 +2,7 @@ use chrono::{Duration, Utc};
 
 use rocket::futures::{self, TryFutureExt};
 use rocket::http::Status;
-use rocket::tokio::sync::mpsc::UnboundedSender;
+use rocket::tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use rocket::tokio::sync::oneshot;
 use rocket::{response::status, serde::json::Json, State};
 use serde::Deserialize;
@@ -13,19 +13,30 @@ use twitter_v2::{
 };
 
 use webb::evm::ethers;
-use webb::evm::ethers::prelude::k256::ecdsa::SigningKey;
-use webb::evm::ethers::signers::Wallet;
 use webb::substrate::subxt::OnlineClient;
 use webb::substrate::subxt::PolkadotConfig;
-use webb_auth::{model::ClaimsData, AuthDb};
+use webb_auth::{
+    model::{
+        ClaimAsset, ClaimReservation, ClaimStatus, ClaimTicket, ClaimsData,
+    },
+    AuthDb,
+};
 use webb_auth_sled::SledAuthDb;
 
 use crate::auth;
+use crate::balances::Balances;
+use crate::claims::{
+    create_claim_ticket, failed_status, follow_transfer, release_claims,
+    settle_claims, still_tracked_assets, track_claim_ticket,
+    track_late_inclusions, update_ticket, ClaimEvents,
+};
 use crate::error::Error;
 use crate::helpers::address::MultiAddress;
-use crate::helpers::files::get_evm_token_address;
+use crate::txes::networks::{FaucetToken, Network, SubstrateAsset};
 use crate::txes::types::{
-    EthersClient, EvmProviders, SubstrateProviders, Transaction, TxResult,
+    AssetTransfer, EvmProviders, EvmWallets, FeeSettings, GasLimits,
+    SubstrateProviders, SubstrateTimeouts, SubstrateWallets, TokenTransfer,
+    Transaction, TxEvent, TxResult,
 };
 
 pub const WEBB_TWITTER_ACCOUNT_ID: u64 = 1355009685859033092;
@@ -45,24 +56,187 @@ pub struct FaucetRequest {
     typed_chain_id: webb_proposals::TypedChainId,
     #[serde(default)]
     only_native_token: bool,
+    /// Send the native token for gas along with the selected tokens.
+    #[serde(default)]
+    with_native_token: bool,
+    /// The symbols of the tokens to send. On EVM chains these are ERC20
+    /// tokens, and the first token configured for the network is sent if
+    /// empty. On Substrate chains these are assets, and the native token is
+    /// sent if empty.
+    #[serde(default)]
+    tokens: Vec<String>,
+    /// Return a claim ticket right after the transfer is queued, instead
+    /// of waiting for the transfer to complete.
+    #[serde(default)]
+    asynchronous: bool,
+}
+
+impl FaucetRequest {
+    /// Resolves what the request pays out on `network`.
+    pub fn payouts(&self, network: &Network) -> Result<ClaimPayouts, Error> {
+        let native_only = ClaimPayouts {
+            native: true,
+            tokens: Vec::new(),
+            assets: Vec::new(),
+        };
+        if self.only_native_token {
+            return Ok(native_only);
+        }
+        match network.typed_chain_id {
+            webb_proposals::TypedChainId::Evm(_) => Ok(ClaimPayouts {
+                native: self.with_native_token,
+                tokens: network.select_tokens(&self.tokens)?,
+                assets: Vec::new(),
+            }),
+            webb_proposals::TypedChainId::Substrate(_)
+                if !self.tokens.is_empty() =>
+            {
+                Ok(ClaimPayouts {
+                    native: self.with_native_token,
+                    tokens: Vec::new(),
+                    assets: network.select_assets(&self.tokens)?,
+                })
+            }
+            _ => Ok(native_only),
+        }
+    }
+}
+
+/// What a claim pays out on its chain.
+#[derive(Clone, Debug)]
+pub struct ClaimPayouts {
+    /// Whether the native token is sent.
+    pub native: bool,
+    /// The ERC20 tokens sent.
+    pub tokens: Vec<FaucetToken>,
+    /// The Substrate assets sent.
+    pub assets: Vec<SubstrateAsset>,
+}
+
+impl ClaimPayouts {
+    /// Returns the asset of every payout along with its cooldown.
+    pub fn assets(
+        &self,
+        app_config: &crate::AppConfig,
+    ) -> Vec<(ClaimAsset, std::time::Duration)> {
+        let native = self.native.then_some((
+            ClaimAsset::Native,
+            app_config.time_to_wait_between_claims,
+        ));
+        let tokens = self.tokens.iter().map(|token| {
+            (
+                ClaimAsset::Token(token.address),
+                token
+                    .cooldown
+                    .unwrap_or(app_config.time_to_wait_between_claims),
+            )
+        });
+        let assets = self.assets.iter().map(|asset| {
+            (
+                ClaimAsset::Asset(asset.asset_id),
+                asset
+                    .cooldown
+                    .unwrap_or(app_config.time_to_wait_between_claims),
+            )
+        });
+        native.into_iter().chain(tokens).chain(assets).collect()
+    }
+}
+
+/// The channels a queued transfer reports its progress and its result on.
+pub struct QueuedTransfer {
+    pub event_receiver: UnboundedReceiver<TxEvent>,
+    pub result_receiver: oneshot::Receiver<Result<TxResult, Error>>,
+}
+
+pub async fn transfer_result(
+    result_receiver: oneshot::Receiver<Result<TxResult, Error>>,
+) -> Result<TxResult, Error> {
+    match result_receiver.await {
+        Ok(res) => res,
+        Err(e) => Err(Error::Custom(format!(
+            "Transaction was not processed: {}",
+            e
+        ))),
+    }
+}
+
+/// A transfer that is built and ready to be sent to the transaction
+/// processing system.
+pub struct PreparedTransfer {
+    transaction: Transaction,
+    queued: QueuedTransfer,
+}
+
+impl PreparedTransfer {
+    /// Sends the transfer to the transaction processing system, without
+    /// waiting for it to be processed.
+    pub fn enqueue(
+        self,
+        tx_sender: &UnboundedSender<Transaction>,
+    ) -> Result<QueuedTransfer, Error> {
+        tx_sender.send(self.transaction).map_err(|_| {
+            Error::Custom(
+                "The transaction processing system is not running".to_string(),
+            )
+        })?;
+        Ok(self.queued)
+    }
+}
+
+/// Converts a whole amount of a token with `decimals` decimals to its
+/// smallest unit.
+fn base_units(amount: u64, decimals: u8) -> Result<u128, Error> {
+    10u128
+        .checked_pow(u32::from(decimals))
+        .and_then(|unit| u128::from(amount).checked_mul(unit))
+        .ok_or(Error::Custom(format!(
+            "The amount {amount} with {decimals} decimals is too large"
+        )))
 }
 
-pub async fn handle_token_transfer(
-    faucet_req: FaucetRequest,
+/// Builds the transfer requested by `faucet_req`, checking that it can be
+/// paid out on its chain. Nothing is sent until the transfer is enqueued.
+pub fn prepare_token_transfer(
+    faucet_req: &FaucetRequest,
+    payouts: &ClaimPayouts,
     app_config: &State<crate::AppConfig>,
-    evm_providers: &State<EvmProviders<EthersClient>>,
+    evm_providers: &State<EvmProviders<EvmWallets>>,
     substrate_providers: &State<
         SubstrateProviders<OnlineClient<PolkadotConfig>>,
     >,
-    _evm_wallet: &State<Wallet<SigningKey>>,
-    signer_pair: &State<subxt_signer::sr25519::Keypair>,
-    tx_sender: &State<UnboundedSender<Transaction>>,
-) -> Result<TxResult, Error> {
+    substrate_wallets: &State<SubstrateWallets>,
+) -> Result<PreparedTransfer, Error> {
     let (result_sender, result_receiver) = oneshot::channel();
-    match faucet_req.typed_chain_id {
+    let (event_sender, event_receiver) = mpsc::unbounded_channel();
+    let network = app_config
+        .networks
+        .from_typed_chain_id(faucet_req.typed_chain_id)
+        .ok_or(Error::Custom(format!(
+            "Unsupported chain id {:?}",
+            faucet_req.typed_chain_id
+        )))?;
+    let native_token_amount: ethers::types::U256 = ethers::utils::parse_units(
+        app_config.native_token_amount,
+        u32::from(network.decimals),
+    )
+    .map_err(|e| {
+        Error::Custom(format!(
+            "Invalid native token amount {}: {e}",
+            app_config.native_token_amount
+        ))
+    })?
+    .into();
+    let transaction = match faucet_req.typed_chain_id {
         webb_proposals::TypedChainId::Evm(chain_id) => {
-            // Create a provider for the chain id and instantiate the contract.
-            let provider = evm_providers
+            let dest = *faucet_req.wallet_address.ethereum().ok_or(
+                Error::Custom(format!(
+                    "{} is not an EVM address",
+                    faucet_req.wallet_address
+                )),
+            )?;
+            // Look up the hot wallets of the chain.
+            let wallets = evm_providers
                 .providers
                 .get(&chain_id.into())
                 .ok_or(Error::Custom(format!(
@@ -70,29 +244,47 @@ pub async fn handle_token_transfer(
                     chain_id
                 )))?
                 .clone();
-            let token_address = if faucet_req.only_native_token {
-                None
-            } else {
-                Some(get_evm_token_address(chain_id.into()).into())
-            };
-            let dest = *faucet_req.wallet_address.ethereum().unwrap();
-
-            // Send transaction to the processor.
-            tx_sender
-                .send(Transaction::Evm {
-                    provider,
-                    to: dest,
-                    amount: app_config.token_amount.into(),
-                    native_token_amount: ethers::utils::parse_ether(
-                        app_config.native_token_amount,
-                    )
-                    .expect("Failed to parse native token amount"),
-                    token_address,
-                    result_sender,
+            let tokens = payouts
+                .tokens
+                .iter()
+                .map(|token| TokenTransfer {
+                    address: token.address,
+                    amount: token
+                        .amount
+                        .unwrap_or(app_config.token_amount)
+                        .into(),
+                    mode: token.mode,
                 })
-                .expect("Failed to send transaction to processor");
+                .collect();
+
+            Transaction::Evm {
+                typed_chain_id: faucet_req.typed_chain_id,
+                wallets,
+                to: dest,
+                native_token_amount: payouts
+                    .native
+                    .then_some(native_token_amount),
+                tokens,
+                confirmations: network.confirmations,
+                inclusion_timeout: network.inclusion_timeout,
+                gas_limits: GasLimits::of(
+                    network,
+                    app_config.gas_limit_multiplier,
+                ),
+                fees: FeeSettings::of(network),
+                event_sender,
+                result_sender,
+            }
         }
         webb_proposals::TypedChainId::Substrate(chain_id) => {
+            let dest = faucet_req
+                .wallet_address
+                .substrate()
+                .ok_or(Error::Custom(format!(
+                    "{} is not a Substrate address",
+                    faucet_req.wallet_address
+                )))?
+                .clone();
             // 1. Create a provider for the chain id.
             let api = substrate_providers
                 .providers
@@ -102,47 +294,67 @@ pub async fn handle_token_transfer(
                     chain_id
                 )))?
                 .clone();
+            let signers = substrate_wallets
+                .wallets
+                .get(&chain_id.into())
+                .ok_or(Error::Custom(format!(
+                    "No hot wallet found for chain id {}",
+                    chain_id
+                )))?
+                .clone();
 
-            // 2. Build a balance transfer extrinsic.
-            let dest = faucet_req.wallet_address.substrate().unwrap().clone();
-            tx_sender
-                .send(Transaction::Substrate {
-                    api,
-                    to: dest,
-                    amount: app_config.token_amount.into(),
-                    native_token_amount: ethers::utils::parse_ether(
-                        app_config.native_token_amount,
-                    )
-                    .expect("Failed to parse native token amount")
-                    .as_u128(),
-                    asset_id: None,
-                    signer: signer_pair.inner().clone(),
-                    timeout: std::time::Duration::from_millis(
-                        app_config.tx_timeout,
-                    ),
-                    result_sender,
+            // 2. Build the transfers of the claim.
+            let assets = payouts
+                .assets
+                .iter()
+                .map(|asset| {
+                    Ok(AssetTransfer {
+                        pallet: asset.pallet,
+                        asset_id: asset.asset_id,
+                        amount: base_units(
+                            asset.amount.unwrap_or(app_config.token_amount),
+                            asset.decimals,
+                        )?,
+                    })
                 })
-                .expect("Failed to send transaction to processor");
+                .collect::<Result<_, Error>>()?;
+            let native_token_amount = payouts
+                .native
+                .then(|| u128::try_from(native_token_amount))
+                .transpose()
+                .map_err(|_| {
+                    Error::Custom(format!(
+                        "The native token amount {} is too large",
+                        app_config.native_token_amount
+                    ))
+                })?;
+            Transaction::Substrate {
+                typed_chain_id: faucet_req.typed_chain_id,
+                api,
+                to: dest,
+                native_token_amount,
+                assets,
+                signers,
+                decimals: network.decimals,
+                finality: network.finality,
+                timeouts: SubstrateTimeouts::of(
+                    network,
+                    std::time::Duration::from_millis(app_config.tx_timeout),
+                ),
+                event_sender,
+                result_sender,
+            }
         }
         _ => return Err(Error::Custom("Invalid chain id".to_string())),
     };
 
-    // await the result
-    let result = match result_receiver.await {
-        Ok(res) => match res {
-            Ok(tx_result) => tx_result, // if transaction execution was successful
-            Err(e) => return Err(e), // if transaction execution resulted in an error
+    Ok(PreparedTransfer {
+        transaction,
+        queued: QueuedTransfer {
+            event_receiver,
+            result_receiver,
         },
-        Err(e) => {
-            return Err(Error::Custom(format!(
-                "Transaction was not processed: {}",
-                e
-            )))
-        }
-    };
-
-    // proceed with your result
-    Ok(result)
+    })
 }
 
 pub async fn check_twitter(
@@ -286,12 +498,13 @@ pub async fn faucet(
     twitter_bearer_token: auth::TwitterBearerToken<'_>,
     payload: Json<Payload>,
     auth_db: &State<SledAuthDb>,
-    evm_providers: &State<EvmProviders<EthersClient>>,
+    claim_events: &State<ClaimEvents>,
+    balances: &State<Balances>,
+    evm_providers: &State<EvmProviders<EvmWallets>>,
     substrate_providers: &State<
         SubstrateProviders<OnlineClient<PolkadotConfig>>,
     >,
-    evm_wallet: &State<Wallet<SigningKey>>,
-    signer_pair: &State<subxt_signer::sr25519::Keypair>,
+    substrate_wallets: &State<SubstrateWallets>,
     tx_sender: &State<UnboundedSender<Transaction>>,
 ) -> Result<status::Custom<String>, Error> {
     let twitter_user = check_twitter(app_config, twitter_bearer_token).await?;
@@ -306,35 +519,121 @@ pub async fn faucet(
         "Requesting faucet for (address {}, chain: {:?}",
         wallet_address, typed_chain_id
     );
-    // Check if the user's last claim date is within the last 24 hours
-    let claim_data = auth_db
-        .get_last_claim_data(twitter_user.id.into(), typed_chain_id)
-        .await?;
-    let last_claim_date = claim_data.map(|c| c.last_claimed_date);
+    let network = app_config
+        .networks
+        .from_typed_chain_id(typed_chain_id)
+        .ok_or(Error::Custom(format!(
+            "Unsupported chain id {typed_chain_id:?}"
+        )))?;
+    let payouts = faucet_data.payouts(network)?;
+    // Refuse claims the faucet cannot pay out before reserving them, so they
+    // do not count against the user's cooldowns.
+    let requested = payouts
+        .assets(app_config)
+        .into_iter()
+        .map(|(asset, _)| asset)
+        .collect::<Vec<_>>();
+    balances.check(typed_chain_id, &requested)?;
+    // Build the transfer before reserving, so that a claim that cannot be
+    // paid out is refused without any reservation to undo.
+    let transfer = prepare_token_transfer(
+        &faucet_data,
+        &payouts,
+        app_config,
+        evm_providers,
+        substrate_providers,
+        substrate_wallets,
+    )?;
+    let cooldowns = payouts
+        .assets(app_config)
+        .into_iter()
+        .map(|(asset, cooldown)| {
+            let time_delay = Duration::from_std(cooldown).map_err(|e| {
+                Error::Custom(format!("Invalid cooldown of {asset}: {e}"))
+            })?;
+            Ok((asset, cooldown, time_delay))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    // Reserve the claim of every asset, unless the user's last claim date
+    // of the asset is within its cooldown period or another claim of theirs
+    // for the asset is still being processed.
     let now = Utc::now();
-    if let Some(last_claim_date) = last_claim_date {
-        let time_delay =
-            Duration::from_std(app_config.time_to_wait_between_claims)
-                .expect("valid duration");
-        if now < last_claim_date + time_delay {
-            println!(
-                "{:?} User {:?} tried to claim again before the time limit",
-                Utc::now().to_rfc3339(),
-                twitter_user.username
-            );
-            return Ok(status::Custom(
-                Status::UnprocessableEntity,
-                json!({
-                    "error": "Error claiming tokens",
-                    "reason": "You can't claim right now. Please try again later.",
-                    "wallet": wallet_address,
-                    "typed_chain_id": typed_chain_id,
-                    "last_claimed_date": last_claim_date,
-                    "time_to_wait_between_claims_ms": app_config.time_to_wait_between_claims.as_millis(),
-                    "user": twitter_user,
-                })
-                .to_string(),
-            ));
+    let claim: ClaimsData = ClaimsData {
+        identity: twitter_user.id.into(),
+        address: wallet_address.clone().into(),
+        last_claimed_date: now,
+    };
+    let mut assets = Vec::new();
+    for (asset, cooldown, time_delay) in cooldowns {
+        let reservation = match auth_db
+            .reserve_claim(
+                twitter_user.id.into(),
+                typed_chain_id,
+                asset,
+                claim.clone(),
+                time_delay,
+            )
+            .await
+        {
+            Ok(reservation) => reservation,
+            Err(e) => {
+                release_claims(
+                    auth_db,
+                    claim.identity,
+                    typed_chain_id,
+                    &assets,
+                )
+                .await?;
+                return Err(e.into());
+            }
+        };
+        if reservation != ClaimReservation::Reserved {
+            release_claims(auth_db, claim.identity, typed_chain_id, &assets)
+                .await?;
+        }
+        match reservation {
+            ClaimReservation::Reserved => assets.push(asset),
+            ClaimReservation::Pending => {
+                println!(
+                    "{:?} User {:?} tried to claim while another claim is pending",
+                    Utc::now().to_rfc3339(),
+                    twitter_user.username
+                );
+                return Ok(status::Custom(
+                    Status::UnprocessableEntity,
+                    json!({
+                        "error": "Error claiming tokens",
+                        "reason": "Your previous claim is still being processed. Please try again later.",
+                        "asset": asset,
+                        "wallet": wallet_address,
+                        "typed_chain_id": typed_chain_id,
+                        "user": twitter_user,
+                    })
+                    .to_string(),
+                ));
+            }
+            ClaimReservation::TooEarly(last_claim) => {
+                println!(
+                    "{:?} User {:?} tried to claim again before the time limit",
+                    Utc::now().to_rfc3339(),
+                    twitter_user.username
+                );
+                return Ok(status::Custom(
+                    Status::UnprocessableEntity,
+                    json!({
+                        "error": "Error claiming tokens",
+                        "reason": "You can't claim right now. Please try again later.",
+                        "asset": asset,
+                        "wallet": wallet_address,
+                        "typed_chain_id": typed_chain_id,
+                        "last_claimed_date": last_claim.last_claimed_date,
+                        "time_to_wait_between_claims_ms": cooldown.as_millis(),
+                        "user": twitter_user,
+                    })
+                    .to_string(),
+                ));
+            }
         }
     }
 
@@ -343,31 +642,49 @@ pub async fn faucet(
         twitter_user.username,
     );
 
-    match handle_token_transfer(
-        faucet_data,
-        app_config,
-        evm_providers,
-        substrate_providers,
-        evm_wallet,
-        signer_pair,
-        tx_sender,
-    )
-    .await
-    {
+    if faucet_data.asynchronous {
+        return enqueue_claim_ticket(
+            faucet_data,
+            transfer,
+            twitter_user,
+            claim,
+            assets,
+            auth_db,
+            claim_events,
+            tx_sender,
+        )
+        .await;
+    }
+
+    let res = match transfer.enqueue(tx_sender) {
+        Ok(QueuedTransfer {
+            mut event_receiver,
+            result_receiver,
+        }) => {
+            let res = follow_transfer(
+                auth_db,
+                &claim,
+                typed_chain_id,
+                &mut event_receiver,
+                result_receiver,
+                None,
+            )
+            .await;
+            rocket::tokio::spawn(track_late_inclusions(
+                auth_db.inner().clone(),
+                claim.clone(),
+                typed_chain_id,
+                assets.clone(),
+                still_tracked_assets(&assets, &res),
+                event_receiver,
+            ));
+            res
+        }
+        Err(e) => Err(e),
+    };
+    settle_claims(auth_db, &claim, typed_chain_id, &assets, &res).await?;
+    match res {
         Ok(tx_result) => {
-            let claim: ClaimsData = ClaimsData {
-                identity: twitter_user.id.into(),
-                address: wallet_address.clone().into(),
-                last_claimed_date: now,
-            };
-
-            auth_db
-                .put_last_claim_data(
-                    twitter_user.id.into(),
-                    typed_chain_id,
-                    claim,
-                )
-                .await?;
             println!(
                 "{:?} Paid {} on chain: {:?}",
                 Utc::now().to_rfc3339(),
@@ -388,18 +705,99 @@ pub async fn faucet(
         }
         Err(e) => {
             rocket::log::private::error!("Error transferring tokens: {e:?}");
-            Ok(status::Custom(
-                Status::InternalServerError,
-                json!({
-                    "error": "Error transferring tokens",
-                    "reason": format!("{e}"),
-                    "typed_chain_id": typed_chain_id,
-                    "wallet": wallet_address,
-                    "user": twitter_user,
-                    "last_claimed_date": now,
-                })
-                .to_string(),
-            ))
+            Err(e)
+        }
+    }
+}
+
+/// Queues the transfer of a reserved claim and returns a claim ticket
+/// that is updated in the background as the transfer progresses.
+#[allow(clippy::too_many_arguments)]
+async fn enqueue_claim_ticket(
+    faucet_data: FaucetRequest,
+    transfer: PreparedTransfer,
+    twitter_user: twitter_v2::User,
+    claim: ClaimsData,
+    assets: Vec<ClaimAsset>,
+    auth_db: &State<SledAuthDb>,
+    claim_events: &State<ClaimEvents>,
+    tx_sender: &State<UnboundedSender<Transaction>>,
+) -> Result<status::Custom<String>, Error> {
+    let FaucetRequest {
+        wallet_address,
+        typed_chain_id,
+        ..
+    } = faucet_data;
+    let mut ticket = ClaimTicket {
+        id: 0,
+        identity: claim.identity,
+        address: claim.address,
+        typed_chain_id,
+        status: ClaimStatus::Queued,
+        created_at: claim.last_claimed_date,
+        updated_at: claim.last_claimed_date,
+        assets: assets.clone(),
+        tx_hashes: Vec::new(),
+    };
+    let res = create_claim_ticket(auth_db, claim_events, &mut ticket).await;
+    if let Err(e) = res {
+        release_claims(auth_db, claim.identity, typed_chain_id, &assets)
+            .await?;
+        return Err(e.into());
+    }
+
+    let transfer = match transfer.enqueue(tx_sender) {
+        Ok(transfer) => transfer,
+        Err(e) => {
+            rocket::log::private::error!("Error transferring tokens: {e:?}");
+            release_claims(auth_db, claim.identity, typed_chain_id, &assets)
+                .await?;
+            let status = failed_status(&e);
+            update_ticket(auth_db, claim_events, &mut ticket, status).await;
+            return Err(e);
         }
+    };
+
+    let ticket_id = ticket.id;
+    rocket::tokio::spawn(track_claim_ticket(
+        auth_db.inner().clone(),
+        claim_events.inner().clone(),
+        ticket,
+        claim,
+        assets,
+        transfer,
+    ));
+
+    Ok(status::Custom(
+        Status::Accepted,
+        json!({
+            "ticket_id": ticket_id,
+            "wallet": wallet_address,
+            "typed_chain_id": typed_chain_id,
+            "user": twitter_user,
+        })
+        .to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_whole_amounts_to_base_units() {
+        assert_eq!(base_units(20, 0).unwrap(), 20);
+        assert_eq!(base_units(20, 12).unwrap(), 20_000_000_000_000);
+        assert_eq!(
+            base_units(u64::MAX, 18).unwrap(),
+            u128::from(u64::MAX) * 10u128.pow(18)
+        );
+    }
+
+    #[test]
+    fn rejects_amounts_overflowing_u128() {
+        assert!(base_units(1, 39).is_err());
+        assert!(base_units(u64::MAX, 20).is_err());
+        assert!(base_units(1, u8::MAX).is_err());
     }
 }
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::State;
+use serde::Serialize;
+use serde_json::json;
+use webb::evm::ethers::types::U256;
+use webb_proposals::TypedChainId;
+
+use crate::error::Error;
+use crate::txes::networks::Network;
+use crate::txes::processor::estimate_fees;
+use crate::txes::types::{EvmProviders, EvmWallets, FeeEstimate};
+use crate::AppConfig;
+
+/// The time after which `/gas` estimates the fees of a network again, if no
+/// claim did in the meantime.
+const GAS_STATE_MAX_AGE_SECS: i64 = 30;
+
+/// The fees of an EVM network, as last seen by the faucet.
+#[derive(Clone, Debug, Serialize)]
+pub struct GasState {
+    #[serde(flatten)]
+    pub fees: FeeEstimate,
+    /// The highest fee per gas the faucet pays on the network.
+    pub max_fee_per_gas: Option<U256>,
+    /// Whether claims are held or rejected because the fees are above
+    /// `max_fee_per_gas`.
+    pub over_max_fee: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl GasState {
+    pub fn new(fees: FeeEstimate, max_fee_per_gas: Option<U256>) -> Self {
+        Self {
+            fees,
+            max_fee_per_gas,
+            over_max_fee: max_fee_per_gas
+                .is_some_and(|max| fees.fee_per_gas > max),
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+/// The latest fees of every EVM network, updated whenever they are
+/// estimated.
+#[derive(Clone, Default)]
+pub struct GasStates {
+    states: Arc<Mutex<HashMap<TypedChainId, GasState>>>,
+}
+
+impl GasStates {
+    /// Records the latest fees of a network.
+    pub fn update(&self, typed_chain_id: TypedChainId, state: GasState) {
+        self.states
+            .lock()
+            .expect("gas states lock poisoned")
+            .insert(typed_chain_id, state);
+    }
+
+    /// Returns the latest fees of a network, if they were estimated.
+    pub fn get(&self, typed_chain_id: TypedChainId) -> Option<GasState> {
+        self.states
+            .lock()
+            .expect("gas states lock poisoned")
+            .get(&typed_chain_id)
+            .cloned()
+    }
+}
+
+/// Returns the latest fees of a network, estimating them again if they are
+/// stale.
+async fn current_gas_state(
+    network: &Network,
+    evm_providers: &EvmProviders<EvmWallets>,
+    gas_states: &GasStates,
+) -> Result<GasState, Error> {
+    let typed_chain_id = network.typed_chain_id;
+    if let Some(state) = gas_states.get(typed_chain_id) {
+        let age = Utc::now() - state.updated_at;
+        if age < Duration::seconds(GAS_STATE_MAX_AGE_SECS) {
+            return Ok(state);
+        }
+    }
+    let provider = network
+        .evm_chain_id()
+        .and_then(|chain_id| evm_providers.providers.get(&chain_id))
+        .map(|wallets| wallets.primary())
+        .ok_or(Error::Custom(format!(
+            "No provider found for chain id {typed_chain_id:?}"
+        )))?;
+    let fees = estimate_fees(provider, network.fee_strategy).await?;
+    let state = GasState::new(fees, network.max_fee_per_gas.map(Into::into));
+    gas_states.update(typed_chain_id, state.clone());
+    Ok(state)
+}
+
+/// Returns the current fees of every EVM network, and whether claims on it
+/// are held or rejected because they are above its `max_fee_per_gas`.
+#[get("/gas")]
+pub async fn gas(
+    app_config: &State<AppConfig>,
+    evm_providers: &State<EvmProviders<EvmWallets>>,
+    gas_states: &State<GasStates>,
+) -> Result<status::Custom<String>, Error> {
+    let mut networks = Vec::new();
+    for network in app_config.networks.evm_networks() {
+        let typed_chain_id = network.typed_chain_id;
+        let state = current_gas_state(network, evm_providers, gas_states).await;
+        networks.push(match state {
+            Ok(state) => json!({
+                "name": network.name,
+                "typed_chain_id": typed_chain_id,
+                "gas": state,
+            }),
+            Err(e) => json!({
+                "name": network.name,
+                "typed_chain_id": typed_chain_id,
+                "error": e.to_string(),
+            }),
+        });
+    }
+    Ok(status::Custom(
+        Status::Ok,
+        json!({ "networks": networks }).to_string(),
+    ))
+}
@@ -41,23 +41,30 @@ pub fn read_token_address_data() -> HashMap<u64, String> {
     read_json!("chainIdFaucetToken.json", HashMap<u64, String>)
 }
 
-pub fn get_evm_rpc_url(chain_id: u64) -> String {
+pub fn get_evm_rpc_urls(chain_id: u64) -> Vec<String> {
     let rpc_map = read_rpc_data();
-    let binding = vec!["http://localhost:8545".to_string()];
-    let rpc_data = rpc_map.get(&chain_id).unwrap_or(&binding);
-    rpc_data[0].clone()
+    rpc_map
+        .get(&chain_id)
+        .filter(|urls| !urls.is_empty())
+        .cloned()
+        .unwrap_or_else(|| vec!["http://localhost:8545".to_string()])
 }
 
-pub fn get_substrate_rpc_url(chain_id: u64) -> String {
+pub fn get_substrate_rpc_urls(chain_id: u64) -> Vec<String> {
     let rpc_map = read_rpc_data();
-    let binding = vec!["ws://localhost:9944".to_string()];
-    let rpc_data = rpc_map.get(&chain_id).unwrap_or(&binding);
-    rpc_data[0].clone()
+    rpc_map
+        .get(&chain_id)
+        .filter(|urls| !urls.is_empty())
+        .cloned()
+        .unwrap_or_else(|| vec!["ws://localhost:9944".to_string()])
 }
 
-pub fn get_evm_token_address(chain_id: u64) -> [u8; 20] {
+/// Returns the faucet token listed for the chain in
+/// `chainIdFaucetToken.json`, if any.
+pub fn get_evm_token_address(chain_id: u64) -> Option<[u8; 20]> {
     let token_map = read_token_address_data();
-    let binding = "0x00000000000000000000000000000000".to_string();
-    let token_data = token_map.get(&chain_id).unwrap_or(&binding);
-    convert_hex_to_bytes(token_data).unwrap_or([0u8; 20])
+    let token_data = token_map.get(&chain_id)?;
+    convert_hex_to_bytes(token_data)
+        .ok()
+        .filter(|address| *address != [0u8; 20])
 }
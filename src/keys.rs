@@ -0,0 +1,494 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Deserializer};
+use sp_core::{ed25519, Pair as _};
+use subxt_signer::{bip39::Mnemonic, ecdsa, sr25519, SecretUri};
+use webb::evm::ethers::core::k256::ecdsa::SigningKey;
+use webb::evm::ethers::signers::{coins_bip39, LocalWallet, Signer};
+use webb::substrate::subxt::tx::Signer as SubxtSigner;
+use webb::substrate::subxt::utils::{
+    AccountId32, MultiAddress, MultiSignature,
+};
+use webb::substrate::subxt::PolkadotConfig;
+use zeroize::Zeroizing;
+
+use crate::error::Error;
+use crate::txes::networks::{KeyConfig, KeyType, Network};
+
+/// The BIP-32 path the index of an EVM hot wallet is appended to when no
+/// `derivation_path` is set, as in `MnemonicBuilder::index`.
+const DEFAULT_EVM_DERIVATION_PATH: &str = "m/44'/60'/0'/0/";
+
+/// A secret of the configuration, such as a mnemonic, which is wiped from
+/// memory once the wallets are built. Copies of the configuration share
+/// their secrets, so wiping one wipes them all.
+#[derive(Clone)]
+pub struct Secret(Arc<Mutex<Option<Zeroizing<String>>>>);
+
+impl Secret {
+    pub fn new(secret: Zeroizing<String>) -> Self {
+        Self(Arc::new(Mutex::new(Some(secret))))
+    }
+
+    /// Returns a copy of the secret, which is wiped from memory when
+    /// dropped, or an error once the secret was wiped.
+    pub fn expose(&self) -> Result<Zeroizing<String>, Error> {
+        self.0.lock().expect("secret lock poisoned").clone().ok_or(
+            Error::Custom(
+                "The secret was wiped from memory once the wallets were built"
+                    .to_string(),
+            ),
+        )
+    }
+
+    /// Wipes the secret from memory.
+    pub fn wipe(&self) {
+        self.0.lock().expect("secret lock poisoned").take();
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        Zeroizing::<String>::deserialize(deserializer).map(Self::new)
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<hidden>")
+    }
+}
+
+/// A hot wallet on a Substrate network, of any supported key type.
+///
+/// `subxt_signer` only derives sr25519 and ecdsa keys, so ed25519 keys are
+/// derived with `sp_core`.
+#[derive(Clone)]
+pub enum SubstrateKeypair {
+    Sr25519(sr25519::Keypair),
+    Ecdsa(ecdsa::Keypair),
+    Ed25519(ed25519::Pair),
+}
+
+impl SubstrateKeypair {
+    /// Creates a keypair of `key_type` from a secret URI such as
+    /// `<mnemonic>//faucet//1`.
+    pub fn from_uri(uri: &str, key_type: KeyType) -> Result<Self, Error> {
+        if key_type == KeyType::Ed25519 {
+            return ed25519::Pair::from_string(uri, None)
+                .map(Self::Ed25519)
+                .map_err(|e| Error::Custom(format!("{e:?}")));
+        }
+        let uri = uri
+            .parse::<SecretUri>()
+            .map_err(|e| Error::Custom(e.to_string()))?;
+        match key_type {
+            KeyType::Sr25519 => sr25519::Keypair::from_uri(&uri)
+                .map(Self::Sr25519)
+                .map_err(|e| Error::Custom(e.to_string())),
+            KeyType::Ecdsa => ecdsa::Keypair::from_uri(&uri)
+                .map(Self::Ecdsa)
+                .map_err(|e| Error::Custom(e.to_string())),
+            KeyType::Ed25519 => unreachable!("ed25519 keys are derived above"),
+        }
+    }
+
+    /// Returns the account of the wallet.
+    pub fn account_id(&self) -> AccountId32 {
+        match self {
+            Self::Sr25519(keypair) => keypair.public_key().to_account_id(),
+            Self::Ecdsa(keypair) => keypair.public_key().to_account_id(),
+            Self::Ed25519(pair) => {
+                let public = pair.public();
+                let bytes: &[u8] = public.as_ref();
+                AccountId32(
+                    bytes.try_into().expect("ed25519 keys are 32 bytes"),
+                )
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for SubstrateKeypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sr25519(keypair) => {
+                f.debug_tuple("Sr25519").field(keypair).finish()
+            }
+            Self::Ecdsa(keypair) => {
+                f.debug_tuple("Ecdsa").field(keypair).finish()
+            }
+            Self::Ed25519(_) => f
+                .debug_tuple("Ed25519")
+                .field(&self.account_id().to_string())
+                .finish(),
+        }
+    }
+}
+
+impl SubxtSigner<PolkadotConfig> for SubstrateKeypair {
+    fn account_id(&self) -> AccountId32 {
+        SubstrateKeypair::account_id(self)
+    }
+
+    fn address(&self) -> MultiAddress<AccountId32, ()> {
+        MultiAddress::Id(SubstrateKeypair::account_id(self))
+    }
+
+    fn sign(&self, signer_payload: &[u8]) -> MultiSignature {
+        match self {
+            Self::Sr25519(keypair) => keypair.sign(signer_payload).into(),
+            Self::Ecdsa(keypair) => keypair.sign(signer_payload).into(),
+            Self::Ed25519(pair) => {
+                let signature = pair.sign(signer_payload);
+                let bytes: &[u8] = signature.as_ref();
+                MultiSignature::Ed25519(
+                    bytes.try_into().expect("ed25519 signatures are 64 bytes"),
+                )
+            }
+        }
+    }
+}
+
+/// Reads the mnemonic of `keys` from its source, or returns `None` if the
+/// secret is read from a `keystore` instead. `context` names the wallets
+/// the secret is for in errors.
+///
+/// The mnemonic is wiped from memory when dropped.
+fn load_mnemonic(
+    keys: &KeyConfig,
+    context: &str,
+) -> Result<Option<Zeroizing<String>>, Error> {
+    let sources = [
+        keys.mnemonic.is_some(),
+        keys.mnemonic_file.is_some(),
+        keys.mnemonic_env.is_some(),
+        keys.keystore.is_some(),
+    ];
+    match sources.into_iter().filter(|set| *set).count() {
+        0 => {
+            return Err(Error::Custom(format!(
+                "No mnemonic, mnemonic_file, mnemonic_env, keystore or signer configured for {context}"
+            )))
+        }
+        1 => {}
+        _ => {
+            return Err(Error::Custom(format!(
+                "Only one of mnemonic, mnemonic_file, mnemonic_env and keystore can be set for {context}"
+            )))
+        }
+    }
+    let (mnemonic, source) = if let Some(mnemonic) = &keys.mnemonic {
+        (mnemonic.expose()?, "mnemonic".to_string())
+    } else if let Some(path) = &keys.mnemonic_file {
+        let source = format!("mnemonic_file {}", path.display());
+        (read_secret_file(path, context)?, source)
+    } else if let Some(var) = &keys.mnemonic_env {
+        let mnemonic = std::env::var(var).map(Zeroizing::new).map_err(|e| {
+            Error::Custom(format!(
+                "Cannot read mnemonic_env {var} of {context}: {e}"
+            ))
+        })?;
+        (mnemonic, format!("mnemonic_env {var}"))
+    } else {
+        return Ok(None);
+    };
+    let mnemonic = Zeroizing::new(mnemonic.trim().to_string());
+    if mnemonic.is_empty() {
+        return Err(Error::Custom(format!(
+            "The {source} of {context} is empty"
+        )));
+    }
+    if let Err(e) = Mnemonic::parse(mnemonic.as_str()) {
+        return Err(Error::Custom(format!(
+            "The {source} of {context} is not a valid mnemonic: {e}"
+        )));
+    }
+    Ok(Some(mnemonic))
+}
+
+/// Reads a file holding a secret, without its trailing newline.
+fn read_secret_file(
+    path: &Path,
+    context: &str,
+) -> Result<Zeroizing<String>, Error> {
+    let contents =
+        Zeroizing::new(std::fs::read_to_string(path).map_err(|e| {
+            Error::Custom(format!(
+                "Cannot read {} of {context}: {e}",
+                path.display()
+            ))
+        })?);
+    let secret = Zeroizing::new(contents.trim_end().to_string());
+    if secret.is_empty() {
+        return Err(Error::Custom(format!(
+            "{} of {context} is empty",
+            path.display()
+        )));
+    }
+    Ok(secret)
+}
+
+/// Derives the EVM wallets at `indices` of the derivation path of `keys`,
+/// or decrypts the single wallet of its ethers keystore. Returns every
+/// wallet along with where it comes from.
+pub fn evm_wallets(
+    keys: &KeyConfig,
+    indices: &[u32],
+    context: &str,
+) -> Result<Vec<(LocalWallet, String)>, Error> {
+    if let Some(mnemonic) = load_mnemonic(keys, context)? {
+        let prefix = keys
+            .derivation_path
+            .as_deref()
+            .unwrap_or(DEFAULT_EVM_DERIVATION_PATH);
+        if !prefix.ends_with('/') {
+            return Err(Error::Custom(format!(
+                "The derivation_path {prefix} of {context} must end with `/`, the wallet index is appended to it"
+            )));
+        }
+        return indices
+            .iter()
+            .map(|index| evm_wallet(&mnemonic, prefix, *index))
+            .collect();
+    }
+    let Some(keystore) = &keys.keystore else {
+        unreachable!("load_mnemonic checks that a secret is set");
+    };
+    if indices != [0] {
+        return Err(Error::Custom(format!(
+            "The keystore of {context} holds a single wallet, so its wallet_indices must be [0]"
+        )));
+    }
+    let Some(password_file) = &keys.password_file else {
+        return Err(Error::Custom(format!(
+            "The keystore of {context} needs a password_file"
+        )));
+    };
+    let password = read_secret_file(password_file, context)?;
+    let wallet = LocalWallet::decrypt_keystore(keystore, password.as_str())
+        .map_err(|e| {
+            Error::Custom(format!(
+                "Cannot decrypt the keystore {} of {context}: {e}",
+                keystore.display()
+            ))
+        })?;
+    Ok(vec![(wallet, format!("keystore {}", keystore.display()))])
+}
+
+/// Returns the mnemonic the Substrate wallets of `keys` are derived from.
+///
+/// Only EVM keystores are supported, so a `keystore` is rejected.
+fn substrate_secret(
+    keys: &KeyConfig,
+    context: &str,
+) -> Result<Zeroizing<String>, Error> {
+    load_mnemonic(keys, context)?.ok_or(Error::Custom(format!(
+        "Keystores are only supported on EVM networks, set the mnemonic, mnemonic_file or mnemonic_env of {context} instead"
+    )))
+}
+
+/// Derives the EVM wallet at `index` of the derivation path `prefix`.
+/// Returns the wallet along with its full derivation path.
+///
+/// The key is derived from the mnemonic directly, as `MnemonicBuilder`
+/// keeps copies of the mnemonic that are never wiped.
+fn evm_wallet(
+    mnemonic: &str,
+    prefix: &str,
+    index: u32,
+) -> Result<(LocalWallet, String), Error> {
+    let path = format!("{prefix}{index}");
+    let invalid =
+        |e: String| Error::Custom(format!("Invalid EVM key at {path}: {e}"));
+    let key = coins_bip39::Mnemonic::<coins_bip39::English>::new_from_phrase(
+        mnemonic,
+    )
+    .and_then(|mnemonic| mnemonic.derive_key(path.as_str(), None))
+    .map_err(|e| invalid(e.to_string()))?;
+    let signing_key: &SigningKey = key.as_ref();
+    Ok((LocalWallet::from(signing_key.clone()), path))
+}
+
+/// Derives the hot wallets of an EVM network from its `wallet_keys`, or
+/// from the secret of `keys` at each of its `wallet_indices`, logging
+/// their addresses.
+pub fn evm_hot_wallets(
+    network: &Network,
+    keys: &KeyConfig,
+) -> Result<Vec<LocalWallet>, Error> {
+    let wallets = if network.wallet_keys.is_empty() {
+        let context = format!("network {}", network.name);
+        evm_wallets(keys, &network.wallet_indices, &context)?
+    } else {
+        network
+            .wallet_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let wallet = key
+                    .secret()?
+                    .parse::<LocalWallet>()
+                    .map_err(|e| Error::Custom(e.to_string()))?;
+                Ok((wallet, format!("wallet_keys[{i}]")))
+            })
+            .collect::<Result<Vec<_>, Error>>()?
+    };
+    Ok(wallets
+        .into_iter()
+        .map(|(wallet, path)| {
+            rocket::log::private::info!(
+                "Using Account {:?} ({path}) on network {}",
+                wallet.address(),
+                network.name
+            );
+            wallet
+        })
+        .collect())
+}
+
+/// Derives the hot wallets of a Substrate network from its `wallet_keys`,
+/// or from the secret and derivation of `keys` at each of its
+/// `wallet_indices`, logging their addresses. Index 0 is the derivation
+/// itself, and index `i` its `//i` hard derivation.
+pub fn substrate_hot_wallets(
+    network: &Network,
+    keys: &KeyConfig,
+) -> Result<Vec<SubstrateKeypair>, Error> {
+    let key_type = keys.key_type.unwrap_or_default();
+    let uris = if network.wallet_keys.is_empty() {
+        let context = format!("network {}", network.name);
+        let secret = substrate_secret(keys, &context)?;
+        let derivation = keys.derivation.as_deref().unwrap_or_default();
+        network
+            .wallet_indices
+            .iter()
+            .map(|index| {
+                let path = match index {
+                    0 => derivation.to_string(),
+                    index => format!("{derivation}//{index}"),
+                };
+                let uri = Zeroizing::new(format!("{}{path}", *secret));
+                if path.is_empty() {
+                    (uri, "root".to_string())
+                } else {
+                    (uri, path)
+                }
+            })
+            .collect::<Vec<_>>()
+    } else {
+        network
+            .wallet_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| Ok((key.secret()?, format!("wallet_keys[{i}]"))))
+            .collect::<Result<Vec<_>, Error>>()?
+    };
+    uris.into_iter()
+        .map(|(uri, path)| {
+            let keypair =
+                SubstrateKeypair::from_uri(&uri, key_type).map_err(|e| {
+                    Error::Custom(format!(
+                        "Invalid {key_type:?} key at {path}: {e}"
+                    ))
+                })?;
+            rocket::log::private::info!(
+                "Using {key_type:?} Account {} ({path}) on network {}",
+                keypair.account_id(),
+                network.name
+            );
+            Ok(keypair)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_ed25519_development_keys() {
+        let alice =
+            SubstrateKeypair::from_uri("//Alice", KeyType::Ed25519).unwrap();
+        assert_eq!(
+            hex::encode(alice.account_id().0),
+            "88dc3417d5058ec4b4503e0c12ea1a0a89be200fe98922423d4334014fa6b0ee"
+        );
+        let MultiSignature::Ed25519(signature) =
+            SubxtSigner::sign(&alice, b"payload")
+        else {
+            panic!("an ed25519 key signs with an ed25519 signature");
+        };
+        assert!(ed25519::Pair::verify(
+            &ed25519::Signature::from_raw(signature),
+            b"payload",
+            &ed25519::Public::from_raw(alice.account_id().0),
+        ));
+    }
+
+    #[test]
+    fn derives_evm_wallets_like_mnemonic_builder() {
+        let keys = KeyConfig {
+            mnemonic: Some(Secret::new(Zeroizing::new(
+                "test test test test test test test test test test test junk"
+                    .to_string(),
+            ))),
+            ..Default::default()
+        };
+        let wallets = evm_wallets(&keys, &[0, 1], "test").unwrap();
+        let addresses = wallets
+            .iter()
+            .map(|(wallet, _)| format!("{:?}", wallet.address()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            addresses,
+            [
+                "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+                "0x70997970c51812dc3a010c7d01b50e20d17dc79c",
+            ]
+        );
+        assert_eq!(wallets[1].1, "m/44'/60'/0'/0/1");
+    }
+
+    #[test]
+    fn rejects_derivation_paths_without_trailing_slash() {
+        let keys = KeyConfig {
+            mnemonic: Some(Secret::new(Zeroizing::new(
+                "test test test test test test test test test test test junk"
+                    .to_string(),
+            ))),
+            derivation_path: Some("m/44'/60'/0'/0/0".to_string()),
+            ..Default::default()
+        };
+        assert!(evm_wallets(&keys, &[0], "test").is_err());
+    }
+
+    #[test]
+    fn wipes_secrets_of_every_copy() {
+        let secret = Secret::new(Zeroizing::new("//Alice".to_string()));
+        let copy = secret.clone();
+        assert_eq!(copy.expose().unwrap().as_str(), "//Alice");
+        secret.wipe();
+        assert!(copy.expose().is_err());
+        assert_eq!(format!("{copy:?}"), "<hidden>");
+    }
+
+    #[test]
+    fn rejects_substrate_keystores() {
+        let keys = KeyConfig {
+            keystore: Some("/run/secrets/substrate-keystore".into()),
+            ..Default::default()
+        };
+        let e = substrate_secret(&keys, "test").unwrap_err();
+        assert!(e.to_string().contains("only supported on EVM networks"));
+    }
+
+    #[test]
+    fn rejects_soft_ed25519_derivations() {
+        assert!(SubstrateKeypair::from_uri("//Alice/soft", KeyType::Ed25519)
+            .is_err());
+    }
+}
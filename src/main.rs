@@ -5,10 +5,8 @@ use std::sync::Arc;
 use std::{collections::HashMap, path::PathBuf};
 
 use error::Error;
-use ethers::{
-    prelude::MiddlewareBuilder, signers::Signer, types::PathOrString,
-};
-use helpers::files::{get_evm_rpc_url, get_substrate_rpc_url};
+use ethers::{prelude::MiddlewareBuilder, signers::Signer};
+use keys::Secret;
 use rocket::tokio::sync::mpsc;
 use rocket::{
     fairing::{AdHoc, Fairing},
@@ -19,33 +17,36 @@ use rocket::{launch, log, routes};
 use rocket_cors::{AllowedOrigins, CorsOptions};
 use rocket_oauth2::OAuth2;
 use serde::Deserialize;
+use signers::EthersSigner;
 use txes::{
-    networks::Network,
-    processor::TransactionProcessingSystem,
-    types::{EvmProviders, SubstrateProviders},
+    failover::FailoverClient,
+    networks::{DispenseMode, KeyConfig, Network, NetworkRegistry},
+    processor::{verify_minter_role, TransactionProcessingSystem},
+    types::{
+        EvmProviders, EvmWallets, SubstrateProviders, SubstrateWallets,
+        Transaction, WalletPool,
+    },
 };
 use webb::evm::ethers;
 use webb::substrate::subxt;
 use webb::{
-    evm::ethers::{
-        prelude::{
-            gas_escalator::{
-                Frequency, GasEscalatorMiddleware, GeometricGasPrice,
-            },
-            gas_oracle::GasNow,
-        },
-        providers::{Http, Provider},
-        signers::{coins_bip39::English, MnemonicBuilder},
-    },
+    evm::ethers::providers::Provider,
     substrate::subxt::{OnlineClient, PolkadotConfig},
 };
 
 use webb_auth_sled::SledAuthDb;
 
 pub mod auth;
+pub mod balances;
+pub mod claims;
 pub mod error;
 pub mod faucet;
+pub mod gas;
 pub mod helpers;
+pub mod keys;
+pub mod recovery;
+pub mod signers;
+pub mod treasury;
 pub mod txes;
 
 fn provider_fairing<P: auth::providers::Provider + 'static>() -> impl Fairing {
@@ -78,12 +79,36 @@ const fn default_tx_timeout_ms() -> u64 {
     18_000 // 3 blocks
 }
 
+const fn default_gas_limit_multiplier() -> f64 {
+    1.2
+}
+
+const fn default_balance_check_interval() -> std::time::Duration {
+    std::time::Duration::from_secs(60)
+}
+
 #[derive(Deserialize)]
 pub struct AppConfig {
     /// The database to use for the auth and claims
     db: PathBuf,
-    /// The mnemonic to use for the faucet
-    mnemonic: String,
+    /// The mnemonic to use for the faucet, unless the keys of a chain family
+    /// or network set their own
+    #[serde(default)]
+    mnemonic: Option<Secret>,
+    /// A file holding the mnemonic, used instead of `mnemonic`
+    #[serde(default)]
+    mnemonic_file: Option<PathBuf>,
+    /// An environment variable holding the mnemonic, used instead of
+    /// `mnemonic`
+    #[serde(default)]
+    mnemonic_env: Option<String>,
+    /// The secret and derivation of the hot wallets on EVM networks
+    #[serde(default)]
+    evm_keys: KeyConfig,
+    /// The secret, derivation and key type of the hot wallets on Substrate
+    /// networks
+    #[serde(default)]
+    substrate_keys: KeyConfig,
     /// The amount of time to wait between claims
     /// This is to prevent users from spamming the faucet
     #[serde(default = "default_time_to_wait_between_claims")]
@@ -97,10 +122,54 @@ pub struct AppConfig {
     /// Whether to verify that the user is following the webb twitter account
     #[serde(default = "default_verify_following_webb")]
     pub verify_following_webb: bool,
-    /// The timeout for transactions in milliseconds
+    /// The timeout for submitting transactions in milliseconds, unless a
+    /// network sets its own `submission_timeout`.
     /// This is to prevent the faucet from hanging
     #[serde(default = "default_tx_timeout_ms")]
     pub tx_timeout: u64,
+    /// The factor the estimated gas of EVM transactions is multiplied by,
+    /// unless a network sets its own `gas_limit_multiplier`.
+    #[serde(default = "default_gas_limit_multiplier")]
+    pub gas_limit_multiplier: f64,
+    /// The time between two checks of the balances of the faucet accounts.
+    #[serde(default = "default_balance_check_interval")]
+    pub balance_check_interval: std::time::Duration,
+    /// The networks the faucet pays out on, at least one of them enabled
+    pub networks: NetworkRegistry,
+}
+
+impl AppConfig {
+    /// Returns the keys of the hot wallets of a network, falling back to
+    /// the keys of its chain family and then to the top-level mnemonic.
+    pub fn keys(&self, network: &Network) -> KeyConfig {
+        let family = match network.evm_chain_id() {
+            Some(_) => &self.evm_keys,
+            None => &self.substrate_keys,
+        };
+        network.keys.or(family).or(&self.root_keys())
+    }
+
+    /// Wipes every secret of the configuration from memory, once the
+    /// wallets are built.
+    ///
+    /// Secrets written inline in the configuration are also held by the
+    /// figment of Rocket, which cannot be wiped, so only the secrets read
+    /// from files, the environment and keystores are gone for good.
+    fn wipe_secrets(&self) {
+        self.root_keys().wipe_secret();
+        self.evm_keys.wipe_secret();
+        self.substrate_keys.wipe_secret();
+        self.networks.wipe_secrets();
+    }
+
+    fn root_keys(&self) -> KeyConfig {
+        KeyConfig {
+            mnemonic: self.mnemonic.clone(),
+            mnemonic_file: self.mnemonic_file.clone(),
+            mnemonic_env: self.mnemonic_env.clone(),
+            ..Default::default()
+        }
+    }
 }
 
 fn auth_db_firing() -> impl Fairing {
@@ -116,108 +185,141 @@ fn auth_db_firing() -> impl Fairing {
     })
 }
 
-fn ethers_wallet_firing() -> impl Fairing {
-    AdHoc::try_on_ignite("Open ethers-rs wallet", |rocket| async {
-        let maybe_wallet = match rocket.state::<AppConfig>() {
-            Some(config) => {
-                let mnemonic: String =
-                    config.mnemonic.parse().expect("Mnemonic is not valid");
-                MnemonicBuilder::<English>::default()
-                    .phrase(PathOrString::String(mnemonic))
-                    .build()
-            }
-            None => return Err(rocket),
+fn substrate_wallet_firing() -> impl Fairing {
+    AdHoc::try_on_ignite("Open substrate wallets", |rocket| async {
+        let Some(config) = rocket.state::<AppConfig>() else {
+            return Err(rocket);
         };
-
-        match maybe_wallet {
-            Ok(wallet) => Ok(rocket.manage(wallet)),
-            Err(_) => Err(rocket),
+        let mut wallet_map = HashMap::new();
+        for network in config.networks.substrate_networks() {
+            let Some(chain_id) = network.substrate_chain_id() else {
+                continue;
+            };
+            let wallets =
+                signers::substrate_signers(network, &config.keys(network))
+                    .and_then(WalletPool::new);
+            match wallets {
+                Ok(wallets) => {
+                    wallet_map.insert(chain_id, Arc::new(wallets));
+                }
+                Err(e) => {
+                    log::private::error!(
+                        "Cannot open the hot wallets of network {}: {e}",
+                        network.name
+                    );
+                    return Err(rocket);
+                }
+            }
         }
+        Ok(rocket.manage(SubstrateWallets {
+            wallets: wallet_map,
+        }))
     })
 }
 
-fn substrate_wallet_firing() -> impl Fairing {
-    use subxt_signer::{bip39::Mnemonic, sr25519::Keypair};
-    AdHoc::try_on_ignite("Open substrate wallet", |rocket| async {
-        let maybe_wallet = match rocket.state::<AppConfig>() {
-            Some(config) => {
-                let mnemonic: Mnemonic =
-                    config.mnemonic.parse().expect("Mnemonic is not valid");
-                Keypair::from_phrase(&mnemonic, None)
-            }
-            None => return Err(rocket),
+fn ethers_providers_firing() -> impl Fairing {
+    AdHoc::try_on_ignite("Open ethers provider", |rocket| async {
+        let Some(config) = rocket.state::<AppConfig>() else {
+            return Err(rocket);
         };
-
-        match maybe_wallet {
-            Ok(wallet) => Ok(rocket.manage(wallet)),
-            Err(_) => Err(rocket),
+        let mut provider_map: HashMap<u64, EvmWallets> = HashMap::new();
+        for net in config.networks.evm_networks() {
+            let Some(chain_id) = net.evm_chain_id() else {
+                continue;
+            };
+            let wallets = match signers::evm_signers(net, &config.keys(net)) {
+                Ok(wallets) => wallets,
+                Err(e) => {
+                    log::private::error!(
+                        "Cannot open the hot wallets of network {}: {e}",
+                        net.name
+                    );
+                    return Err(rocket);
+                }
+            };
+            // Every hot wallet gets its own client, so a slow wallet does
+            // not hold up the requests of the others.
+            let providers = wallets
+                .into_iter()
+                .map(|signer| {
+                    let client = FailoverClient::new_http(&net.rpc_urls())?;
+                    // Fees and nonces are set by the transaction processor,
+                    // following the fee strategy of the chain.
+                    let signer =
+                        EthersSigner::new(signer).with_chain_id(chain_id);
+                    let provider = Provider::new(client).with_signer(signer);
+                    Ok(Arc::new(provider))
+                })
+                .collect::<Result<Vec<_>, Error>>()
+                .and_then(WalletPool::new);
+            match providers {
+                Ok(providers) => {
+                    provider_map.insert(chain_id, Arc::new(providers));
+                }
+                Err(e) => {
+                    log::private::error!(
+                        "Cannot open the providers of network {}: {e}",
+                        net.name
+                    );
+                    return Err(rocket);
+                }
+            }
         }
+        Ok(rocket.manage(EvmProviders {
+            providers: provider_map,
+        }))
     })
 }
 
-fn ethers_providers_firing() -> impl Fairing {
-    AdHoc::try_on_ignite("Open ethers provider", |rocket| async {
-        let result: Result<HashMap<u64, _>, Error> = match rocket
-            .state::<AppConfig>()
-        {
-            Some(config) => {
-                // Supported networks
-                let networks = vec![
-                    Network::Athena,
-                    Network::Hermes,
-                    Network::Demeter,
-                    Network::TangleEVMTestnet,
-                ];
-
-                let mnemonic: String = config.mnemonic.parse().unwrap();
-                let wallet = match MnemonicBuilder::<English>::default()
-                    .phrase(PathOrString::String(mnemonic))
-                    .build()
-                {
-                    Ok(wallet) => wallet,
-                    Err(_) => return Err(rocket),
-                };
-
-                let address = wallet.address();
-                log::private::info!("Using Account {address:?}");
-                let providers: Vec<(_, _)> = networks
-                    .iter()
-                    .map(|net| net.to_evm_chain_id().unwrap())
-                    .map(|chain_id| (chain_id, get_evm_rpc_url(chain_id)))
-                    .map(|(chain_id, url)| {
-                        let escalator =
-                            GeometricGasPrice::new(1.125, 60_u64, None::<u64>);
-                        let gas_oracle = GasNow::new();
-                        let provider = Provider::<Http>::try_from(url)
-                            .unwrap()
-                            .wrap_into(|p| {
-                                GasEscalatorMiddleware::new(
-                                    p,
-                                    escalator,
-                                    Frequency::PerBlock,
-                                )
-                            })
-                            .gas_oracle(gas_oracle)
-                            .with_signer(wallet.clone().with_chain_id(chain_id))
-                            .nonce_manager(address);
-                        (chain_id, provider)
-                    })
-                    .collect();
-
-                let mut provider_map: HashMap<u64, _> = HashMap::new();
-                for (chain_id, provider) in providers {
-                    provider_map.insert(chain_id, Arc::new(provider));
+fn token_minter_roles_firing() -> impl Fairing {
+    AdHoc::try_on_ignite("Verify token minter roles", |rocket| async {
+        let verified = match (
+            rocket.state::<AppConfig>(),
+            rocket.state::<EvmProviders<EvmWallets>>(),
+        ) {
+            (Some(config), Some(evm_providers)) => {
+                let mut verified = true;
+                for network in config.networks.evm_networks() {
+                    let wallets = match network
+                        .evm_chain_id()
+                        .and_then(|id| evm_providers.providers.get(&id))
+                    {
+                        Some(wallets) => wallets,
+                        None => continue,
+                    };
+                    let minted_tokens = network
+                        .tokens()
+                        .into_iter()
+                        .filter(|token| token.mode == DispenseMode::Mint);
+                    for token in minted_tokens {
+                        // Any hot wallet may mint a claim.
+                        for provider in wallets.iter() {
+                            let res = verify_minter_role(
+                                provider.clone(),
+                                token.address,
+                            )
+                            .await;
+                            if let Err(e) = res {
+                                log::private::error!(
+                                    "Cannot mint {} on network {} from {:?}: {e}",
+                                    token.symbol,
+                                    network.name,
+                                    provider.address()
+                                );
+                                verified = false;
+                            }
+                        }
+                    }
                 }
-                Ok(provider_map)
+                verified
             }
-            None => return Err(rocket),
+            _ => false,
         };
 
-        match result {
-            Ok(provider_map) => Ok(rocket.manage(EvmProviders {
-                providers: provider_map,
-            })),
-            Err(_) => Err(rocket),
+        if verified {
+            Ok(rocket)
+        } else {
+            Err(rocket)
         }
     })
 }
@@ -226,29 +328,33 @@ fn substrate_providers_firing() -> impl Fairing {
     AdHoc::try_on_ignite("Open subxt providers", |rocket| async {
         let result: Result<HashMap<u64, OnlineClient<PolkadotConfig>>, Error> =
             match rocket.state::<AppConfig>() {
-                Some(_config) => {
-                    let networks = vec![
-                        Network::Tangle,
-                        #[cfg(debug_assertions)]
-                        Network::TangleLocal,
-                    ];
-
+                Some(config) => {
                     let mut futures_unordered = FuturesUnordered::new();
 
-                    for network in &networks {
-                        let chain_id = network.to_substrate_chain_id().unwrap();
-                        let url = get_substrate_rpc_url(chain_id);
+                    for network in config.networks.substrate_networks() {
+                        let chain_id = network.substrate_chain_id().unwrap();
+                        let urls = network.rpc_urls();
                         futures_unordered.push(async move {
-                        let res = OnlineClient::<PolkadotConfig>::from_url(url).await;
-                        let api = match res {
-                            Ok(api) => Some(api),
-                            Err(e) => {
-                                eprintln!("Error connecting to substrate node: {e}");
-                                None
-                            }
-                        };
-                        Result::<_, subxt::Error>::Ok((chain_id, api))
-                    });
+                            let res = match FailoverClient::connect(&urls).await
+                            {
+                                Ok(client) => {
+                                    OnlineClient::<PolkadotConfig>::from_rpc_client(
+                                        Arc::new(client),
+                                    )
+                                    .await
+                                    .map_err(|e| e.to_string())
+                                }
+                                Err(e) => Err(e.to_string()),
+                            };
+                            let api = match res {
+                                Ok(api) => Some(api),
+                                Err(e) => {
+                                    eprintln!("Error connecting to substrate node: {e}");
+                                    None
+                                }
+                            };
+                            Result::<_, subxt::Error>::Ok((chain_id, api))
+                        });
                     }
 
                     let mut provider_map: HashMap<
@@ -281,6 +387,176 @@ fn substrate_providers_firing() -> impl Fairing {
     })
 }
 
+fn transaction_processor_firing(
+    rx_receiver: mpsc::UnboundedReceiver<Transaction>,
+) -> impl Fairing {
+    AdHoc::try_on_ignite(
+        "Start transaction processing system",
+        |rocket| async move {
+            match (
+                rocket.state::<AppConfig>(),
+                rocket.state::<gas::GasStates>(),
+                rocket.state::<balances::Balances>(),
+            ) {
+                (Some(config), Some(gas_states), Some(balances)) => {
+                    // Pass the receiver to your transaction processing system
+                    let concurrency = config
+                        .networks
+                        .enabled()
+                        .map(|net| (net.typed_chain_id, net.concurrency))
+                        .collect();
+                    TransactionProcessingSystem::new(
+                        rx_receiver,
+                        concurrency,
+                        gas_states.clone(),
+                        balances.clone(),
+                    )
+                    .run();
+                    Ok(rocket)
+                }
+                _ => Err(rocket),
+            }
+        },
+    )
+}
+
+fn claim_recovery_firing() -> impl Fairing {
+    AdHoc::try_on_ignite("Recover pending claims", |rocket| async {
+        let (
+            Some(auth_db),
+            Some(claim_events),
+            Some(evm_providers),
+            Some(substrate_providers),
+        ) = (
+            rocket.state::<SledAuthDb>(),
+            rocket.state::<claims::ClaimEvents>(),
+            rocket.state::<EvmProviders<EvmWallets>>(),
+            rocket.state::<SubstrateProviders<OnlineClient<PolkadotConfig>>>(),
+        )
+        else {
+            return Err(rocket);
+        };
+        let chains = recovery::Chains {
+            evm: evm_providers.providers.clone(),
+            substrate: substrate_providers.providers.clone(),
+        };
+        let res = recovery::recover_claims(auth_db, claim_events, chains).await;
+        match res {
+            Ok(()) => Ok(rocket),
+            Err(e) => {
+                log::private::error!("Cannot recover pending claims: {e}");
+                Err(rocket)
+            }
+        }
+    })
+}
+
+fn balance_monitor_firing() -> impl Fairing {
+    AdHoc::try_on_ignite("Start balance monitor", |rocket| async {
+        match (
+            rocket.state::<AppConfig>(),
+            rocket.state::<EvmProviders<EvmWallets>>(),
+            rocket.state::<SubstrateProviders<OnlineClient<PolkadotConfig>>>(),
+            rocket.state::<SubstrateWallets>(),
+            rocket.state::<balances::Balances>(),
+        ) {
+            (
+                Some(config),
+                Some(evm_providers),
+                Some(substrate_providers),
+                Some(substrate_wallets),
+                Some(balances),
+            ) => {
+                rocket::tokio::spawn(balances::monitor_balances(
+                    config.networks.clone(),
+                    config.native_token_amount,
+                    config.token_amount,
+                    evm_providers.providers.clone(),
+                    substrate_providers.providers.clone(),
+                    substrate_wallets.wallets.clone(),
+                    balances.clone(),
+                    config.balance_check_interval,
+                ));
+                Ok(rocket)
+            }
+            _ => Err(rocket),
+        }
+    })
+}
+
+fn treasury_firing() -> impl Fairing {
+    AdHoc::try_on_ignite("Start treasury", |rocket| async {
+        let (
+            Some(config),
+            Some(gas_states),
+            Some(evm_providers),
+            Some(substrate_providers),
+            Some(substrate_wallets),
+            Some(balances),
+        ) = (
+            rocket.state::<AppConfig>(),
+            rocket.state::<gas::GasStates>(),
+            rocket.state::<EvmProviders<EvmWallets>>(),
+            rocket.state::<SubstrateProviders<OnlineClient<PolkadotConfig>>>(),
+            rocket.state::<SubstrateWallets>(),
+            rocket.state::<balances::Balances>(),
+        )
+        else {
+            return Err(rocket);
+        };
+        let mut treasuries = Vec::new();
+        for network in config.networks.enabled() {
+            let Some(treasury_config) = &network.treasury else {
+                continue;
+            };
+            match treasury::Treasury::connect(
+                network,
+                treasury_config,
+                config,
+                gas_states,
+                evm_providers,
+                substrate_providers,
+                substrate_wallets,
+            ) {
+                Ok(treasury) => {
+                    log::private::info!(
+                        "Treasury {} refills network {}",
+                        treasury.address(),
+                        network.name
+                    );
+                    treasuries.push(treasury);
+                }
+                Err(e) => {
+                    log::private::error!(
+                        "Cannot open the treasury of network {}: {e}",
+                        network.name
+                    );
+                    return Err(rocket);
+                }
+            }
+        }
+        for treasury in treasuries {
+            rocket::tokio::spawn(treasury::run_treasury(
+                treasury,
+                balances.clone(),
+                config.balance_check_interval,
+            ));
+        }
+        Ok(rocket)
+    })
+}
+
+/// Wipes the secrets of the configuration from memory once every wallet
+/// was built from them, so they do not stay in the managed state.
+fn wipe_secrets_firing() -> impl Fairing {
+    AdHoc::on_ignite("Wipe secrets", |rocket| async {
+        if let Some(config) = rocket.state::<AppConfig>() {
+            config.wipe_secrets();
+        }
+        rocket
+    })
+}
+
 #[launch]
 async fn rocket() -> _ {
     let cors = CorsOptions::default()
@@ -296,20 +572,35 @@ async fn rocket() -> _ {
     // Create the channel
     let (tx_sender, rx_receiver) = mpsc::unbounded_channel();
 
-    // Pass the receiver to your transaction processing system
-    TransactionProcessingSystem::new(rx_receiver).run();
-
     rocket::build()
         .attach(AdHoc::config::<AppConfig>())
         .attach(auth_db_firing())
         .attach(provider_fairing::<auth::providers::Twitter>())
         .attach(ethers_providers_firing())
+        .attach(token_minter_roles_firing())
         .attach(substrate_providers_firing())
-        .attach(ethers_wallet_firing())
         .attach(substrate_wallet_firing())
+        .attach(transaction_processor_firing(rx_receiver))
+        .attach(claim_recovery_firing())
+        .attach(balance_monitor_firing())
+        .attach(treasury_firing())
+        .attach(wipe_secrets_firing())
         .attach(cors.to_cors().unwrap())
         .manage(cors.to_cors().unwrap())
         .manage(tx_sender)
+        .manage(claims::ClaimEvents::default())
+        .manage(gas::GasStates::default())
+        .manage(balances::Balances::default())
         .mount("/", rocket_cors::catch_all_options_routes())
-        .mount("/", routes![auth::login::twitter, faucet::faucet])
+        .mount(
+            "/",
+            routes![
+                auth::login::twitter,
+                faucet::faucet,
+                claims::claim_status,
+                claims::claim_events,
+                gas::gas,
+                balances::balances
+            ],
+        )
 }
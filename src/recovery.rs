@@ -0,0 +1,344 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use rocket::futures::future::join_all;
+use rocket::tokio::time::{sleep, Instant};
+use serde_json::json;
+use webb::evm::ethers::providers::Middleware;
+use webb::evm::ethers::types::{Address, BlockNumber, H256, U256};
+use webb::substrate::subxt::utils::AccountId32;
+use webb::substrate::subxt::{OnlineClient, PolkadotConfig};
+use webb::substrate::tangle_runtime::api as RuntimeApi;
+use webb_auth::model::{ClaimStatus, ClaimTicket, PendingClaim, SentTx};
+use webb_auth::AuthDb;
+use webb_auth_sled::SledAuthDb;
+use webb_proposals::TypedChainId;
+
+use crate::claims::{update_ticket, ClaimEvents};
+use crate::error::Error;
+use crate::txes::processor::LATE_INCLUSION_TRACKING;
+use crate::txes::types::{EthersClient, EvmWallets};
+
+/// How often the transactions of a claim left reserved by a restart are
+/// checked.
+const POLL_INTERVAL: Duration = Duration::from_secs(12);
+
+/// The chains the claims left reserved by a restart are settled on.
+#[derive(Clone)]
+pub struct Chains {
+    pub evm: HashMap<u64, EvmWallets>,
+    pub substrate: HashMap<u64, OnlineClient<PolkadotConfig>>,
+}
+
+/// How a claim left reserved by a restart was settled.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Settlement {
+    /// A transaction of the claim was included, and the claim committed.
+    Committed,
+    /// No transaction of the claim made it, and the claim was released.
+    Released,
+    /// The transactions of the claim could not be checked, and the claim
+    /// stays reserved until the next restart.
+    Unknown,
+}
+
+/// What the chain says about the transactions of a claim.
+#[derive(Debug, PartialEq)]
+enum Inclusion {
+    /// A transaction was included, and succeeded if `true`.
+    Included(bool),
+    /// No transaction was included yet.
+    Pending,
+    /// The nonces of the transactions were used by other transactions.
+    Dropped,
+}
+
+/// Resumes the claims that were being paid out when the faucet stopped.
+///
+/// Their claim tickets are tracked again, and the ones that were sent are
+/// marked as timed out. Every reserved claim is then settled in the
+/// background from the transactions recorded for it: committed if one of
+/// them is included, released otherwise. Tickets that were never sent
+/// fail right away.
+pub async fn recover_claims(
+    auth_db: &SledAuthDb,
+    claim_events: &ClaimEvents,
+    chains: Chains,
+) -> Result<(), webb_auth_sled::Error> {
+    let mut pending_claims = auth_db.pending_claims()?;
+    for mut ticket in auth_db.unfinished_claim_tickets()? {
+        let (claims, others) = pending_claims
+            .into_iter()
+            .partition(|pending| is_claim_of(&ticket, pending));
+        pending_claims = others;
+        claim_events.register(ticket.id);
+        if let ClaimStatus::Broadcast { .. } | ClaimStatus::InBlock { .. } =
+            ticket.status
+        {
+            let status = ClaimStatus::TimedOut {
+                code: None,
+                reason:
+                    "The faucet restarted while the claim was being paid out"
+                        .to_string(),
+            };
+            update_ticket(auth_db, claim_events, &mut ticket, status).await;
+        }
+        rocket::tokio::spawn(recover_ticket(
+            auth_db.clone(),
+            claim_events.clone(),
+            chains.clone(),
+            ticket,
+            claims,
+        ));
+    }
+    for pending in pending_claims {
+        let auth_db = auth_db.clone();
+        let chains = chains.clone();
+        rocket::tokio::spawn(async move {
+            settle_pending_claim(&auth_db, &chains, &pending).await;
+        });
+    }
+    Ok(())
+}
+
+/// Returns whether a reserved claim was made by a claim ticket.
+fn is_claim_of(ticket: &ClaimTicket, pending: &PendingClaim) -> bool {
+    pending.claim.identity == ticket.identity
+        && pending.typed_chain_id == ticket.typed_chain_id
+        && pending.claim.last_claimed_date == ticket.created_at
+}
+
+/// Settles the reserved claims of a claim ticket and gives it its final
+/// status.
+async fn recover_ticket(
+    auth_db: SledAuthDb,
+    claim_events: ClaimEvents,
+    chains: Chains,
+    mut ticket: ClaimTicket,
+    claims: Vec<PendingClaim>,
+) {
+    let settlements = join_all(
+        claims
+            .iter()
+            .map(|pending| settle_pending_claim(&auth_db, &chains, pending)),
+    )
+    .await;
+    if settlements.contains(&Settlement::Unknown) {
+        return;
+    }
+    let paid = settlements.contains(&Settlement::Committed)
+        || was_committed(&auth_db, &ticket).await;
+    let sent = !ticket.tx_hashes.is_empty()
+        || claims.iter().any(|pending| !pending.sent.is_empty());
+    let status = if paid {
+        ClaimStatus::Included {
+            tx_result: json!({
+                "recovered": true,
+                "tx_hashes": ticket.tx_hashes,
+            }),
+        }
+    } else if sent {
+        ClaimStatus::Failed {
+            code: None,
+            reason: "The transfer was not included".to_string(),
+        }
+    } else {
+        ClaimStatus::Failed {
+            code: None,
+            reason: "The faucet restarted before the claim was sent"
+                .to_string(),
+        }
+    };
+    update_ticket(&auth_db, &claim_events, &mut ticket, status).await;
+}
+
+/// Returns whether a claim of the ticket was committed before the faucet
+/// stopped.
+async fn was_committed(auth_db: &SledAuthDb, ticket: &ClaimTicket) -> bool {
+    for asset in &ticket.assets {
+        let res = auth_db
+            .get_last_claim_data(ticket.identity, ticket.typed_chain_id, *asset)
+            .await;
+        match res {
+            Ok(Some(claim)) if claim.last_claimed_date == ticket.created_at => {
+                return true
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Error reading claim of ticket {}: {e}", ticket.id);
+            }
+        }
+    }
+    false
+}
+
+/// Follows the transactions recorded for a reserved claim until one of them
+/// is included or they stop being tracked, and commits or releases the
+/// claim accordingly.
+async fn settle_pending_claim(
+    auth_db: &SledAuthDb,
+    chains: &Chains,
+    pending: &PendingClaim,
+) -> Settlement {
+    let PendingClaim {
+        claim,
+        typed_chain_id,
+        asset,
+        sent,
+    } = pending;
+    let deadline = Instant::now() + LATE_INCLUSION_TRACKING;
+    let settlement = loop {
+        if sent.is_empty() {
+            // The claim was never sent.
+            break Settlement::Released;
+        }
+        match check_inclusion(chains, *typed_chain_id, sent).await {
+            Ok(Inclusion::Included(true)) => break Settlement::Committed,
+            Ok(Inclusion::Included(false) | Inclusion::Dropped) => {
+                break Settlement::Released
+            }
+            Ok(Inclusion::Pending) if Instant::now() >= deadline => {
+                break Settlement::Released
+            }
+            Ok(Inclusion::Pending) => {}
+            Err(e) if Instant::now() >= deadline => {
+                eprintln!(
+                    "Cannot settle claim of {asset} by {} on chain {typed_chain_id:?}, leaving it reserved: {e}",
+                    claim.identity
+                );
+                return Settlement::Unknown;
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error checking claim of {asset} by {} on chain {typed_chain_id:?}: {e}",
+                    claim.identity
+                );
+            }
+        }
+        sleep(POLL_INTERVAL).await;
+    };
+    let res = match settlement {
+        Settlement::Committed => auth_db
+            .commit_claim(
+                claim.identity,
+                *typed_chain_id,
+                *asset,
+                claim.clone(),
+            )
+            .await
+            .map(|_| ()),
+        _ => {
+            auth_db
+                .release_claim(claim.identity, *typed_chain_id, *asset)
+                .await
+        }
+    };
+    match res {
+        Ok(()) => settlement,
+        Err(e) => {
+            eprintln!("Error settling recovered claim of {asset}: {e}");
+            Settlement::Unknown
+        }
+    }
+}
+
+/// Checks whether one of the transactions of a claim was included.
+async fn check_inclusion(
+    chains: &Chains,
+    typed_chain_id: TypedChainId,
+    sent: &[SentTx],
+) -> Result<Inclusion, Error> {
+    let not_running =
+        || Error::Custom(format!("Chain {typed_chain_id:?} is not connected"));
+    match typed_chain_id {
+        TypedChainId::Evm(chain_id) => {
+            let wallets = chains
+                .evm
+                .get(&u64::from(chain_id))
+                .ok_or_else(not_running)?;
+            check_evm_inclusion(wallets.primary(), sent).await
+        }
+        TypedChainId::Substrate(chain_id) => {
+            let api = chains
+                .substrate
+                .get(&u64::from(chain_id))
+                .ok_or_else(not_running)?;
+            check_substrate_inclusion(api, sent).await
+        }
+        _ => Err(not_running()),
+    }
+}
+
+/// Checks the receipts of the transactions of a claim on an EVM chain.
+///
+/// As replacements share a nonce, the transactions are dropped once the
+/// nonces of their wallets are used without any of them being included.
+async fn check_evm_inclusion(
+    provider: &EthersClient,
+    sent: &[SentTx],
+) -> Result<Inclusion, Error> {
+    // Read the nonces before the receipts, so that a transaction mined in
+    // between is seen by its receipt rather than taken for a dropped one.
+    let mut nonces = HashMap::new();
+    let wallets: HashSet<_> = sent.iter().map(|tx| &tx.from).collect();
+    for wallet in wallets {
+        let from = wallet.parse::<Address>().map_err(|e| {
+            Error::Custom(format!("Invalid wallet {wallet}: {e}"))
+        })?;
+        let nonce = provider
+            .get_transaction_count(from, Some(BlockNumber::Latest.into()))
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?;
+        nonces.insert(wallet, nonce);
+    }
+    for tx in sent {
+        let tx_hash = tx.tx_hash.parse::<H256>().map_err(|e| {
+            Error::Custom(format!("Invalid transaction {}: {e}", tx.tx_hash))
+        })?;
+        let receipt = provider
+            .get_transaction_receipt(tx_hash)
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?;
+        if let Some(receipt) = receipt {
+            return Ok(Inclusion::Included(receipt.status == Some(1.into())));
+        }
+    }
+    let dropped = sent.iter().all(|tx| {
+        nonces
+            .get(&tx.from)
+            .is_some_and(|nonce| *nonce > U256::from(tx.nonce))
+    });
+    Ok(if dropped {
+        Inclusion::Dropped
+    } else {
+        Inclusion::Pending
+    })
+}
+
+/// Checks the nonces of the extrinsics of a claim on a Substrate chain.
+///
+/// Extrinsics are never replaced, so one whose nonce was used is taken to
+/// be included, rather than risk paying the claim out twice.
+async fn check_substrate_inclusion(
+    api: &OnlineClient<PolkadotConfig>,
+    sent: &[SentTx],
+) -> Result<Inclusion, Error> {
+    let storage = api
+        .storage()
+        .at_latest()
+        .await
+        .map_err(|e| Error::Custom(e.to_string()))?;
+    for tx in sent {
+        let account = tx.from.parse::<AccountId32>().map_err(|e| {
+            Error::Custom(format!("Invalid account {}: {e}", tx.from))
+        })?;
+        let account_info = storage
+            .fetch_or_default(&RuntimeApi::storage().system().account(&account))
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?;
+        if u64::from(account_info.nonce) > tx.nonce {
+            return Ok(Inclusion::Included(true));
+        }
+    }
+    Ok(Inclusion::Pending)
+}
@@ -0,0 +1,191 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use webb::evm::ethers::signers::{LocalWallet, Signer};
+use webb::evm::ethers::types::transaction::eip2718::TypedTransaction;
+use webb::evm::ethers::types::transaction::eip712::Eip712;
+use webb::evm::ethers::types::{Address, Signature};
+use webb::substrate::subxt::tx::Signer as SubxtSigner;
+use webb::substrate::subxt::utils::{AccountId32, MultiSignature};
+
+use crate::error::Error;
+use crate::keys::{self, SubstrateKeypair};
+use crate::txes::networks::{KeyConfig, Network, SignerConfig};
+
+pub mod remote;
+
+/// Signs the transactions of an EVM hot wallet, wherever its key is kept.
+#[rocket::async_trait]
+pub trait EvmSigner: Debug + Send + Sync {
+    /// Returns the address of the hot wallet.
+    fn address(&self) -> Address;
+
+    /// Signs a transaction filled with its chain id, nonce, gas and fees.
+    async fn sign_transaction(
+        &self,
+        tx: &TypedTransaction,
+    ) -> Result<Signature, Error>;
+}
+
+/// Signs the extrinsics of a Substrate hot wallet, wherever its key is
+/// kept.
+#[rocket::async_trait]
+pub trait SubstrateSigner: Debug + Send + Sync {
+    /// Returns the account of the hot wallet.
+    fn account_id(&self) -> AccountId32;
+
+    /// Signs the payload of an extrinsic.
+    async fn sign(&self, payload: &[u8]) -> Result<MultiSignature, Error>;
+}
+
+#[rocket::async_trait]
+impl EvmSigner for LocalWallet {
+    fn address(&self) -> Address {
+        Signer::address(self)
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &TypedTransaction,
+    ) -> Result<Signature, Error> {
+        Signer::sign_transaction(self, tx)
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))
+    }
+}
+
+#[rocket::async_trait]
+impl SubstrateSigner for SubstrateKeypair {
+    fn account_id(&self) -> AccountId32 {
+        SubstrateKeypair::account_id(self)
+    }
+
+    async fn sign(&self, payload: &[u8]) -> Result<MultiSignature, Error> {
+        Ok(SubxtSigner::sign(self, payload))
+    }
+}
+
+/// The error of an [`EthersSigner`].
+#[derive(thiserror::Error, Debug)]
+#[error("{0}")]
+pub struct SignerError(String);
+
+/// Adapts an [`EvmSigner`] to the ethers `Signer` trait, so that the
+/// `SignerMiddleware` of a chain signs with it.
+#[derive(Clone, Debug)]
+pub struct EthersSigner {
+    signer: Arc<dyn EvmSigner>,
+    chain_id: u64,
+}
+
+impl EthersSigner {
+    pub fn new(signer: Arc<dyn EvmSigner>) -> Self {
+        Self {
+            signer,
+            chain_id: 1,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Signer for EthersSigner {
+    type Error = SignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        _message: S,
+    ) -> Result<Signature, Self::Error> {
+        Err(SignerError(
+            "Hot wallets only sign transactions".to_string(),
+        ))
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &TypedTransaction,
+    ) -> Result<Signature, Self::Error> {
+        self.signer
+            .sign_transaction(tx)
+            .await
+            .map_err(|e| SignerError(e.to_string()))
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        _payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        Err(SignerError(
+            "Hot wallets only sign transactions".to_string(),
+        ))
+    }
+
+    fn address(&self) -> Address {
+        self.signer.address()
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    fn with_chain_id<T: Into<u64>>(mut self, chain_id: T) -> Self {
+        self.chain_id = chain_id.into();
+        self
+    }
+}
+
+/// Returns the signer set in `keys`, or `None` if the hot wallets are
+/// derived from a secret or set with `wallet_keys`.
+fn signer_config<'a>(
+    network: &Network,
+    keys: &'a KeyConfig,
+) -> Result<Option<&'a SignerConfig>, Error> {
+    let Some(signer) = &keys.signer else {
+        return Ok(None);
+    };
+    let has_secret = keys.mnemonic.is_some()
+        || keys.mnemonic_file.is_some()
+        || keys.mnemonic_env.is_some()
+        || keys.keystore.is_some();
+    if has_secret || !network.wallet_keys.is_empty() {
+        return Err(Error::Custom(format!(
+            "The signer of network {} cannot be combined with a secret or wallet_keys",
+            network.name
+        )));
+    }
+    Ok(Some(signer))
+}
+
+/// Creates the signers of the hot wallets of an EVM network: the signer
+/// set in `keys`, or the local wallets of its secret or `wallet_keys`.
+pub fn evm_signers(
+    network: &Network,
+    keys: &KeyConfig,
+) -> Result<Vec<Arc<dyn EvmSigner>>, Error> {
+    match signer_config(network, keys)? {
+        Some(SignerConfig::Remote(config)) => {
+            remote::evm_signers(network, config)
+        }
+        None => Ok(keys::evm_hot_wallets(network, keys)?
+            .into_iter()
+            .map(|wallet| Arc::new(wallet) as Arc<dyn EvmSigner>)
+            .collect()),
+    }
+}
+
+/// Creates the signers of the hot wallets of a Substrate network: the
+/// signer set in `keys`, or the local keypairs of its secret or
+/// `wallet_keys`.
+pub fn substrate_signers(
+    network: &Network,
+    keys: &KeyConfig,
+) -> Result<Vec<Arc<dyn SubstrateSigner>>, Error> {
+    match signer_config(network, keys)? {
+        Some(SignerConfig::Remote(config)) => {
+            remote::substrate_signers(network, keys, config)
+        }
+        None => Ok(keys::substrate_hot_wallets(network, keys)?
+            .into_iter()
+            .map(|keypair| Arc::new(keypair) as Arc<dyn SubstrateSigner>)
+            .collect()),
+    }
+}
@@ -0,0 +1,513 @@
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rocket::tokio::io::{AsyncReadExt, AsyncWriteExt};
+use rocket::tokio::net::UnixStream;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use webb::evm::ethers::providers::{Http, JsonRpcClient};
+use webb::evm::ethers::types::transaction::eip2718::TypedTransaction;
+use webb::evm::ethers::types::{Address, Bytes, Signature};
+use webb::evm::ethers::utils::rlp::Rlp;
+use webb::substrate::subxt::utils::{AccountId32, MultiSignature};
+
+use crate::error::Error;
+use crate::txes::networks::{KeyConfig, KeyType, Network, RemoteSignerConfig};
+
+use super::{EvmSigner, SubstrateSigner};
+
+/// How a signing daemon is reached.
+#[derive(Debug)]
+enum Transport {
+    /// JSON-RPC over HTTP.
+    Http(Http),
+    /// JSON-RPC over a Unix socket, one request per connection.
+    Unix(PathBuf),
+}
+
+/// A JSON-RPC client of a signing daemon on the same host, holding the keys
+/// of the hot wallets so that they never enter the faucet.
+#[derive(Debug)]
+pub struct SigningDaemon {
+    url: String,
+    transport: Transport,
+    timeout: Duration,
+    substrate_method: String,
+    next_id: AtomicU64,
+}
+
+#[derive(Serialize)]
+struct RpcRequest<'a, T> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: T,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse<R> {
+    result: Option<R>,
+    error: Option<RpcErrorObject>,
+}
+
+#[derive(Deserialize)]
+struct RpcErrorObject {
+    code: i64,
+    message: String,
+}
+
+/// The answer of `eth_signTransaction`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SignedTransaction {
+    /// The `{ raw, tx }` object returned by geth and clef.
+    Object { raw: Bytes },
+    /// The raw transaction returned by other signers.
+    Raw(Bytes),
+}
+
+impl SignedTransaction {
+    fn raw(&self) -> &Bytes {
+        match self {
+            Self::Object { raw } | Self::Raw(raw) => raw,
+        }
+    }
+}
+
+impl SigningDaemon {
+    pub fn new(config: &RemoteSignerConfig) -> Result<Self, Error> {
+        let transport = match config.url.strip_prefix("unix://") {
+            Some(path) => Transport::Unix(PathBuf::from(path)),
+            None => {
+                Transport::Http(config.url.parse::<Http>().map_err(|e| {
+                    Error::Custom(format!(
+                        "Invalid remote signer url {}: {e}",
+                        config.url
+                    ))
+                })?)
+            }
+        };
+        Ok(Self {
+            url: config.url.clone(),
+            transport,
+            timeout: config.timeout,
+            substrate_method: config.substrate_method.clone(),
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    /// Sends a JSON-RPC request to the daemon, failing if it does not
+    /// answer within its timeout.
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let request = async {
+            match &self.transport {
+                Transport::Http(client) => client
+                    .request(method, params)
+                    .await
+                    .map_err(|e| e.to_string()),
+                Transport::Unix(path) => {
+                    self.unix_request(path, method, params).await
+                }
+            }
+        };
+        match rocket::tokio::time::timeout(self.timeout, request).await {
+            Ok(Ok(res)) => Ok(res),
+            Ok(Err(e)) => Err(Error::Custom(format!(
+                "Remote signer {} failed on {method}: {e}",
+                self.url
+            ))),
+            Err(_) => Err(Error::Custom(format!(
+                "Remote signer {} timed out on {method}",
+                self.url
+            ))),
+        }
+    }
+
+    /// Sends a JSON-RPC request over a new connection to the socket of the
+    /// daemon, and reads until a whole response is received.
+    async fn unix_request<T, R>(
+        &self,
+        path: &Path,
+        method: &str,
+        params: T,
+    ) -> Result<R, String>
+    where
+        T: Serialize,
+        R: DeserializeOwned,
+    {
+        let request = RpcRequest {
+            jsonrpc: "2.0",
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            method,
+            params,
+        };
+        let mut bytes =
+            serde_json::to_vec(&request).map_err(|e| e.to_string())?;
+        bytes.push(b'\n');
+        let mut stream =
+            UnixStream::connect(path).await.map_err(|e| e.to_string())?;
+        stream.write_all(&bytes).await.map_err(|e| e.to_string())?;
+        let mut response = Vec::new();
+        let mut chunk = [0u8; 4096];
+        let res = loop {
+            let read =
+                stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+            if read == 0 {
+                return Err("The connection closed before a response".into());
+            }
+            response.extend_from_slice(&chunk[..read]);
+            match serde_json::from_slice::<RpcResponse<R>>(&response) {
+                Ok(res) => break res,
+                // The response is not complete yet.
+                Err(e) if e.is_eof() => continue,
+                Err(e) => return Err(e.to_string()),
+            }
+        };
+        match (res.result, res.error) {
+            (_, Some(error)) => {
+                Err(format!("({}) {}", error.code, error.message))
+            }
+            (Some(result), None) => Ok(result),
+            (None, None) => Err("The response has no result".into()),
+        }
+    }
+}
+
+/// An EVM hot wallet whose key is held by a signing daemon.
+#[derive(Debug)]
+pub struct RemoteEvmSigner {
+    daemon: Arc<SigningDaemon>,
+    address: Address,
+}
+
+#[rocket::async_trait]
+impl EvmSigner for RemoteEvmSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Signs a transaction with `eth_signTransaction`.
+    ///
+    /// The signature is checked against the transaction and the address
+    /// of the wallet, as a transaction signed by another account would
+    /// still be valid on chain.
+    async fn sign_transaction(
+        &self,
+        tx: &TypedTransaction,
+    ) -> Result<Signature, Error> {
+        let mut tx = tx.clone();
+        tx.set_from(self.address);
+        let signed: SignedTransaction =
+            self.daemon.request("eth_signTransaction", [&tx]).await?;
+        let (_, signature) = TypedTransaction::decode_signed(&Rlp::new(
+            signed.raw(),
+        ))
+        .map_err(|e| {
+            Error::Custom(format!(
+                "Remote signer returned an invalid transaction: {e}"
+            ))
+        })?;
+        signature.verify(tx.sighash(), self.address).map_err(|e| {
+            Error::Custom(format!(
+                "Remote signer did not sign the transaction as {:?}: {e}",
+                self.address
+            ))
+        })?;
+        Ok(signature)
+    }
+}
+
+/// A Substrate hot wallet whose key is held by a signing daemon.
+#[derive(Debug)]
+pub struct RemoteSubstrateSigner {
+    daemon: Arc<SigningDaemon>,
+    account_id: AccountId32,
+    key_type: KeyType,
+}
+
+#[rocket::async_trait]
+impl SubstrateSigner for RemoteSubstrateSigner {
+    fn account_id(&self) -> AccountId32 {
+        self.account_id.clone()
+    }
+
+    /// Signs the payload of an extrinsic with the `substrate_method` of the
+    /// daemon. The signature is not checked here, as the chain rejects an
+    /// extrinsic not signed by the account it is sent from.
+    async fn sign(&self, payload: &[u8]) -> Result<MultiSignature, Error> {
+        let params =
+            (self.account_id.to_string(), Bytes::from(payload.to_vec()));
+        let signature: Bytes = self
+            .daemon
+            .request(&self.daemon.substrate_method, params)
+            .await?;
+        let invalid = || {
+            Error::Custom(format!(
+                "Remote signer returned a {} byte signature for a {:?} key",
+                signature.len(),
+                self.key_type
+            ))
+        };
+        match self.key_type {
+            KeyType::Sr25519 => signature[..]
+                .try_into()
+                .map(MultiSignature::Sr25519)
+                .map_err(|_| invalid()),
+            KeyType::Ecdsa => signature[..]
+                .try_into()
+                .map(MultiSignature::Ecdsa)
+                .map_err(|_| invalid()),
+            KeyType::Ed25519 => signature[..]
+                .try_into()
+                .map(MultiSignature::Ed25519)
+                .map_err(|_| invalid()),
+        }
+    }
+}
+
+/// Returns the accounts of the hot wallets held by the daemon, failing if
+/// none are configured.
+fn accounts<'a>(
+    network: &Network,
+    config: &'a RemoteSignerConfig,
+) -> Result<&'a [String], Error> {
+    if config.accounts.is_empty() {
+        return Err(Error::Custom(format!(
+            "The remote signer of network {} has no accounts",
+            network.name
+        )));
+    }
+    Ok(&config.accounts)
+}
+
+/// Creates the signers of the hot wallets of an EVM network held by a
+/// signing daemon, logging their addresses.
+pub fn evm_signers(
+    network: &Network,
+    config: &RemoteSignerConfig,
+) -> Result<Vec<Arc<dyn EvmSigner>>, Error> {
+    let daemon = Arc::new(SigningDaemon::new(config)?);
+    accounts(network, config)?
+        .iter()
+        .map(|account| {
+            let address = account.parse::<Address>().map_err(|e| {
+                Error::Custom(format!(
+                    "Invalid remote signer account {account}: {e}"
+                ))
+            })?;
+            rocket::log::private::info!(
+                "Using Account {address:?} (remote signer {}) on network {}",
+                config.url,
+                network.name
+            );
+            let signer = RemoteEvmSigner {
+                daemon: daemon.clone(),
+                address,
+            };
+            Ok(Arc::new(signer) as Arc<dyn EvmSigner>)
+        })
+        .collect()
+}
+
+/// Creates the signers of the hot wallets of a Substrate network held by a
+/// signing daemon, logging their addresses.
+pub fn substrate_signers(
+    network: &Network,
+    keys: &KeyConfig,
+    config: &RemoteSignerConfig,
+) -> Result<Vec<Arc<dyn SubstrateSigner>>, Error> {
+    let key_type = keys.key_type.unwrap_or_default();
+    let daemon = Arc::new(SigningDaemon::new(config)?);
+    accounts(network, config)?
+        .iter()
+        .map(|account| {
+            let account_id = account.parse::<AccountId32>().map_err(|e| {
+                Error::Custom(format!(
+                    "Invalid remote signer account {account}: {e}"
+                ))
+            })?;
+            rocket::log::private::info!(
+                "Using {key_type:?} Account {account_id} (remote signer {}) on network {}",
+                config.url,
+                network.name
+            );
+            let signer = RemoteSubstrateSigner {
+                daemon: daemon.clone(),
+                account_id,
+                key_type,
+            };
+            Ok(Arc::new(signer) as Arc<dyn SubstrateSigner>)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::tokio::io::{AsyncBufReadExt, BufReader};
+    use rocket::tokio::net::UnixListener;
+    use serde_json::{json, Value};
+    use sp_core::{sr25519, Pair as _};
+    use webb::evm::ethers::signers::{LocalWallet, Signer};
+    use webb::evm::ethers::types::TransactionRequest;
+    use webb::substrate::subxt::tx::Signer as SubxtSigner;
+
+    use super::*;
+    use crate::keys::SubstrateKeypair;
+
+    const ANVIL_KEYS: [&str; 2] = [
+        "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+        "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d",
+    ];
+
+    /// The key a mock signing daemon signs every request with.
+    enum MockKey {
+        Evm(LocalWallet),
+        Substrate(SubstrateKeypair),
+    }
+
+    impl MockKey {
+        async fn sign(&self, params: &Value) -> Value {
+            match self {
+                Self::Evm(wallet) => {
+                    let tx: TypedTransaction =
+                        serde_json::from_value(params[0].clone()).unwrap();
+                    let signature =
+                        Signer::sign_transaction(wallet, &tx).await.unwrap();
+                    json!({ "raw": tx.rlp_signed(&signature) })
+                }
+                Self::Substrate(keypair) => {
+                    let payload: Bytes =
+                        serde_json::from_value(params[1].clone()).unwrap();
+                    let signature = match SubxtSigner::sign(keypair, &payload) {
+                        MultiSignature::Sr25519(signature)
+                        | MultiSignature::Ed25519(signature) => {
+                            signature.to_vec()
+                        }
+                        MultiSignature::Ecdsa(signature) => signature.to_vec(),
+                    };
+                    json!(Bytes::from(signature))
+                }
+            }
+        }
+    }
+
+    /// Serves a mock signing daemon on a new socket, reading one
+    /// newline-terminated request per connection, and returns its config.
+    fn mock_daemon(key: MockKey) -> RemoteSignerConfig {
+        static NEXT_SOCKET: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "faucet-signer-{}-{}.sock",
+            std::process::id(),
+            NEXT_SOCKET.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let key = Arc::new(key);
+        rocket::tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let key = key.clone();
+                rocket::tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    let mut line = String::new();
+                    stream.read_line(&mut line).await.unwrap();
+                    let request: Value = serde_json::from_str(&line).unwrap();
+                    let response = json!({
+                        "jsonrpc": "2.0",
+                        "id": request["id"],
+                        "result": key.sign(&request["params"]).await,
+                    });
+                    let bytes = serde_json::to_vec(&response).unwrap();
+                    stream.get_mut().write_all(&bytes).await.unwrap();
+                });
+            }
+        });
+        RemoteSignerConfig {
+            url: format!("unix://{}", path.display()),
+            accounts: Vec::new(),
+            timeout: Duration::from_secs(5),
+            substrate_method: "substrate_sign".to_string(),
+        }
+    }
+
+    fn evm_signer(config: &RemoteSignerConfig, key: &str) -> RemoteEvmSigner {
+        RemoteEvmSigner {
+            daemon: Arc::new(SigningDaemon::new(config).unwrap()),
+            address: key.parse::<LocalWallet>().unwrap().address(),
+        }
+    }
+
+    fn transfer() -> TypedTransaction {
+        TransactionRequest::new()
+            .to(Address::repeat_byte(1))
+            .value(1_000)
+            .nonce(7)
+            .gas(21_000)
+            .gas_price(1_000_000_000)
+            .chain_id(31337)
+            .into()
+    }
+
+    #[rocket::async_test]
+    async fn signs_evm_transactions_with_the_daemon() {
+        let wallet = ANVIL_KEYS[0].parse::<LocalWallet>().unwrap();
+        let config = mock_daemon(MockKey::Evm(wallet));
+        let signer = evm_signer(&config, ANVIL_KEYS[0]);
+        let mut tx = transfer();
+        let signature = signer.sign_transaction(&tx).await.unwrap();
+        tx.set_from(signer.address);
+        signature.verify(tx.sighash(), signer.address).unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn rejects_evm_transactions_signed_by_another_account() {
+        let wallet = ANVIL_KEYS[1].parse::<LocalWallet>().unwrap();
+        let config = mock_daemon(MockKey::Evm(wallet));
+        let signer = evm_signer(&config, ANVIL_KEYS[0]);
+        let err = signer.sign_transaction(&transfer()).await.unwrap_err();
+        assert!(err.to_string().contains("did not sign the transaction"));
+    }
+
+    #[rocket::async_test]
+    async fn signs_substrate_payloads_with_the_daemon() {
+        let keypair =
+            SubstrateKeypair::from_uri("//Alice", KeyType::Sr25519).unwrap();
+        let account_id = keypair.account_id();
+        let config = mock_daemon(MockKey::Substrate(keypair));
+        let signer = RemoteSubstrateSigner {
+            daemon: Arc::new(SigningDaemon::new(&config).unwrap()),
+            account_id: account_id.clone(),
+            key_type: KeyType::Sr25519,
+        };
+        let MultiSignature::Sr25519(signature) =
+            signer.sign(b"payload").await.unwrap()
+        else {
+            panic!("an sr25519 key signs with an sr25519 signature");
+        };
+        assert!(sr25519::Pair::verify(
+            &sr25519::Signature::from_raw(signature),
+            b"payload",
+            &sr25519::Public::from_raw(account_id.0),
+        ));
+    }
+
+    #[rocket::async_test]
+    async fn rejects_substrate_signatures_of_another_key_type() {
+        let keypair =
+            SubstrateKeypair::from_uri("//Alice", KeyType::Sr25519).unwrap();
+        let account_id = keypair.account_id();
+        let config = mock_daemon(MockKey::Substrate(keypair));
+        let signer = RemoteSubstrateSigner {
+            daemon: Arc::new(SigningDaemon::new(&config).unwrap()),
+            account_id,
+            key_type: KeyType::Ecdsa,
+        };
+        let err = signer.sign(b"payload").await.unwrap_err();
+        assert!(err.to_string().contains("64 byte signature"));
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rocket::tokio::sync::mpsc;
+use rocket::tokio::time::timeout;
+use webb::evm::contract::protocol_solidity::erc20_preset_minter_pauser::ERC20PresetMinterPauserContract;
+use webb::evm::ethers::prelude::MiddlewareBuilder;
+use webb::evm::ethers::providers::Provider;
+use webb::evm::ethers::signers::Signer;
+use webb::evm::ethers::types::{Address, TransactionReceipt, U256};
+use webb::evm::ethers::utils::parse_units;
+use webb::substrate::subxt::utils::AccountId32;
+use webb::substrate::subxt::{OnlineClient, PolkadotConfig};
+use webb_auth::model::ClaimAsset;
+use webb_proposals::TypedChainId;
+
+use crate::balances::{AssetBalance, Balances};
+use crate::error::Error;
+use crate::gas::GasStates;
+use crate::signers::{self, EthersSigner, SubstrateSigner};
+use crate::txes::failover::FailoverClient;
+use crate::txes::networks::{Network, TreasuryConfig};
+use crate::txes::processor::{
+    handle_evm_native_tx, handle_substrate_native_tx, EvmNonces, EvmSender,
+    SubstrateNonces, SubstrateSubmitter,
+};
+use crate::txes::types::{
+    EthersClient, EvmProviders, EvmWallets, FeeSettings, GasLimits,
+    SubstrateProviders, SubstrateTimeouts, SubstrateWallets,
+};
+use crate::AppConfig;
+
+/// The treasury account of a network, and the hot wallets it refills.
+///
+/// Top-ups are sent like claims, following the fee strategy, fee cap and
+/// timeouts of the network, with nonces of their own.
+enum TreasuryAccount {
+    Evm {
+        sender: EvmSender<EthersClient>,
+        hot_wallets: Vec<Address>,
+    },
+    Substrate {
+        submitter: SubstrateSubmitter,
+        hot_wallets: Vec<AccountId32>,
+    },
+}
+
+/// A treasury refilling the hot wallets of a network.
+pub struct Treasury {
+    pub network: Network,
+    pub config: TreasuryConfig,
+    account: TreasuryAccount,
+    /// The longest time a top-up is waited for.
+    timeout: Duration,
+}
+
+impl Treasury {
+    /// Opens the treasury account of a network from its `keys`, and looks
+    /// up the hot wallets it refills.
+    pub fn connect(
+        network: &Network,
+        config: &TreasuryConfig,
+        app_config: &AppConfig,
+        gas_states: &GasStates,
+        evm_providers: &EvmProviders<EvmWallets>,
+        substrate_providers: &SubstrateProviders<OnlineClient<PolkadotConfig>>,
+        substrate_wallets: &SubstrateWallets,
+    ) -> Result<Self, Error> {
+        if !config.keys.has_secret() {
+            return Err(Error::Custom(format!(
+                "The treasury of network {} has no keys",
+                network.name
+            )));
+        }
+        // The treasury account is the first account of its keys.
+        let treasury_network = Network {
+            name: format!("{} treasury", network.name),
+            wallet_indices: vec![0],
+            wallet_keys: Vec::new(),
+            ..network.clone()
+        };
+        let timeouts = SubstrateTimeouts::of(
+            network,
+            Duration::from_millis(app_config.tx_timeout),
+        );
+        // Top-ups are not tracked by claims, so their events are dropped.
+        let (event_sender, _) = mpsc::unbounded_channel();
+        let account = match network.typed_chain_id {
+            TypedChainId::Evm(chain_id) => {
+                let hot_wallets = evm_providers
+                    .providers
+                    .get(&chain_id.into())
+                    .ok_or(Error::Custom(format!(
+                        "No provider found for chain id {chain_id}"
+                    )))?
+                    .iter()
+                    .map(|provider| provider.address())
+                    .collect();
+                let signer =
+                    signers::evm_signers(&treasury_network, &config.keys)?
+                        .into_iter()
+                        .next()
+                        .ok_or(Error::Custom(format!(
+                            "The treasury of network {} has no account",
+                            network.name
+                        )))?;
+                let client = FailoverClient::new_http(&network.rpc_urls())
+                    .map_err(|e| Error::Custom(e.to_string()))?;
+                let signer = EthersSigner::new(signer)
+                    .with_chain_id(u64::from(chain_id));
+                let provider = Provider::new(client).with_signer(signer);
+                TreasuryAccount::Evm {
+                    sender: EvmSender {
+                        typed_chain_id: network.typed_chain_id,
+                        provider: Arc::new(provider),
+                        confirmations: network.confirmations,
+                        inclusion_timeout: network.inclusion_timeout,
+                        gas_limits: GasLimits::of(
+                            network,
+                            app_config.gas_limit_multiplier,
+                        ),
+                        fees: FeeSettings::of(network),
+                        nonces: EvmNonces::default(),
+                        gas_states: gas_states.clone(),
+                        event_sender,
+                    },
+                    hot_wallets,
+                }
+            }
+            TypedChainId::Substrate(chain_id) => {
+                let api = substrate_providers
+                    .providers
+                    .get(&chain_id.into())
+                    .ok_or(Error::Custom(format!(
+                        "No provider found for chain id {chain_id}"
+                    )))?
+                    .clone();
+                let hot_wallets = substrate_wallets
+                    .wallets
+                    .get(&chain_id.into())
+                    .ok_or(Error::Custom(format!(
+                        "No hot wallet found for chain id {chain_id}"
+                    )))?
+                    .iter()
+                    .map(|signer| signer.account_id())
+                    .collect();
+                let signer = signers::substrate_signers(
+                    &treasury_network,
+                    &config.keys,
+                )?
+                .into_iter()
+                .next()
+                .ok_or(Error::Custom(format!(
+                    "The treasury of network {} has no account",
+                    network.name
+                )))?;
+                TreasuryAccount::Substrate {
+                    submitter: SubstrateSubmitter {
+                        api,
+                        signer,
+                        nonces: SubstrateNonces::default(),
+                        finality: network.finality,
+                        timeouts,
+                        decimals: network.decimals,
+                        event_sender,
+                    },
+                    hot_wallets,
+                }
+            }
+            _ => {
+                return Err(Error::Custom(format!(
+                    "Unsupported chain id {:?}",
+                    network.typed_chain_id
+                )))
+            }
+        };
+        Ok(Self {
+            network: network.clone(),
+            config: config.clone(),
+            account,
+            timeout: timeouts.submission
+                + timeouts.inclusion
+                + timeouts.finalization,
+        })
+    }
+
+    /// Returns the address of the treasury account.
+    pub fn address(&self) -> String {
+        match &self.account {
+            TreasuryAccount::Evm { sender, .. } => {
+                format!("{:?}", sender.provider.address())
+            }
+            TreasuryAccount::Substrate { submitter, .. } => {
+                submitter.signer.account_id().to_string()
+            }
+        }
+    }
+
+    /// Returns the low and high water marks of an asset, in the smallest
+    /// unit of the asset, or `None` if the treasury does not refill it.
+    async fn water_marks(
+        &self,
+        asset: ClaimAsset,
+    ) -> Result<Option<(U256, U256)>, Error> {
+        match (asset, &self.account) {
+            (ClaimAsset::Native, _) => {
+                let decimals = u32::from(self.network.decimals);
+                let low = parse_units(self.config.low_water, decimals)
+                    .map_err(|e| Error::Custom(e.to_string()))?;
+                let high = parse_units(self.config.high_water, decimals)
+                    .map_err(|e| Error::Custom(e.to_string()))?;
+                Ok(Some((low.into(), high.into())))
+            }
+            (
+                ClaimAsset::Token(address),
+                TreasuryAccount::Evm { sender, .. },
+            ) => {
+                let marks = self
+                    .network
+                    .tokens()
+                    .into_iter()
+                    .find(|token| token.address == address)
+                    .and_then(|token| {
+                        self.config
+                            .tokens
+                            .iter()
+                            .find(|marks| marks.symbol == token.symbol)
+                    });
+                let Some(marks) = marks else {
+                    return Ok(None);
+                };
+                let contract = ERC20PresetMinterPauserContract::new(
+                    address,
+                    sender.provider.clone(),
+                );
+                let decimals =
+                    contract.decimals().call().await.map_err(|e| {
+                        Error::Custom(format!("Failed to fetch decimals: {e}"))
+                    })?;
+                let unit = U256::exp10(usize::from(decimals));
+                Ok(Some((
+                    U256::from(marks.low_water) * unit,
+                    U256::from(marks.high_water) * unit,
+                )))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Refills a hot wallet up to the high water mark of an asset if its
+    /// balance is below the low water mark. Returns the amount sent and the
+    /// hash of the transfer, if any.
+    ///
+    /// A top-up that is not done within the timeouts of the network is
+    /// abandoned, and the nonces of the treasury are fetched again.
+    async fn top_up(
+        &self,
+        balance: &AssetBalance,
+    ) -> Result<Option<(U256, String)>, Error> {
+        let Some((low, high)) = self.water_marks(balance.asset).await? else {
+            return Ok(None);
+        };
+        if balance.balance >= low {
+            return Ok(None);
+        }
+        let amount = high.saturating_sub(balance.balance);
+        let tx_hash = match &self.account {
+            TreasuryAccount::Evm {
+                sender,
+                hot_wallets,
+            } => {
+                let Some(hot_wallet) = hot_wallets
+                    .iter()
+                    .find(|wallet| format!("{wallet:?}") == balance.wallet)
+                else {
+                    return Ok(None);
+                };
+                let top_up =
+                    evm_top_up(sender, *hot_wallet, balance.asset, amount);
+                let receipt = match timeout(self.timeout, top_up).await {
+                    Ok(res) => res?,
+                    Err(_) => {
+                        sender.nonces.reset(&sender.provider.address()).await;
+                        return Err(self.timed_out());
+                    }
+                };
+                format!("{:?}", receipt.transaction_hash)
+            }
+            TreasuryAccount::Substrate {
+                submitter,
+                hot_wallets,
+            } => {
+                let Some(hot_wallet) = hot_wallets
+                    .iter()
+                    .find(|wallet| wallet.to_string() == balance.wallet)
+                else {
+                    return Ok(None);
+                };
+                let amount = u128::try_from(amount).map_err(|_| {
+                    Error::Custom(format!("The top-up {amount} is too large"))
+                })?;
+                let top_up =
+                    handle_substrate_native_tx(submitter, hot_wallet, amount);
+                let transfer = match timeout(self.timeout, top_up).await {
+                    Ok(res) => res?,
+                    Err(_) => {
+                        let account = submitter.signer.account_id();
+                        submitter.nonces.reset(&account).await;
+                        return Err(self.timed_out());
+                    }
+                };
+                format!("{:?}", transfer.tx_hash)
+            }
+        };
+        Ok(Some((amount, tx_hash)))
+    }
+
+    /// Returns the error of a top-up that was abandoned.
+    fn timed_out(&self) -> Error {
+        Error::Custom(format!(
+            "The top-up was not done within {:?}",
+            self.timeout
+        ))
+    }
+}
+
+/// Sends `amount` of the native token or of an ERC20 token from the
+/// treasury to a hot wallet, and waits for it to be confirmed.
+async fn evm_top_up(
+    sender: &EvmSender<EthersClient>,
+    hot_wallet: Address,
+    asset: ClaimAsset,
+    amount: U256,
+) -> Result<TransactionReceipt, Error> {
+    match asset {
+        ClaimAsset::Native => {
+            handle_evm_native_tx(sender, hot_wallet, amount).await
+        }
+        ClaimAsset::Token(address) => {
+            let contract = ERC20PresetMinterPauserContract::new(
+                address,
+                sender.provider.clone(),
+            );
+            let tx = contract.transfer(hot_wallet, amount).tx;
+            sender.send(tx, asset).await
+        }
+        ClaimAsset::Asset(asset_id) => Err(Error::Custom(format!(
+            "Asset {asset_id} cannot be sent on an EVM network"
+        ))),
+    }
+}
+
+/// Refills the hot wallets of a network from its treasury whenever the
+/// balance monitor reports them below their low water marks, logging every
+/// top-up.
+///
+/// Every treasury runs in its own task, so a slow network never holds up
+/// the top-ups of the others.
+pub async fn run_treasury(
+    treasury: Treasury,
+    balances: Balances,
+    interval: Duration,
+) {
+    println!("Treasury started for network {}", treasury.network.name);
+    let typed_chain_id = treasury.network.typed_chain_id;
+    // An asset of a wallet is not refilled again until its balance was
+    // fetched after its last top-up, nor from an expired balance.
+    let mut topped_up_at: HashMap<(String, ClaimAsset), DateTime<Utc>> =
+        HashMap::new();
+    loop {
+        for balance in balances.of_chain(typed_chain_id) {
+            let key = (balance.wallet.clone(), balance.asset);
+            if balance.is_expired()
+                || topped_up_at
+                    .get(&key)
+                    .is_some_and(|at| *at >= balance.updated_at)
+            {
+                continue;
+            }
+            match treasury.top_up(&balance).await {
+                Ok(Some((amount, tx_hash))) => {
+                    rocket::log::private::info!(
+                        "Topped up {amount} of {} to {} on network {} from treasury {} in tx {tx_hash}, the balance was {}",
+                        balance.asset,
+                        balance.wallet,
+                        treasury.network.name,
+                        treasury.address(),
+                        balance.balance
+                    );
+                    topped_up_at.insert(key, Utc::now());
+                }
+                Ok(None) => {}
+                Err(e) => rocket::log::private::error!(
+                    "Error topping up {} to {} on network {} from treasury {}: {e}",
+                    balance.asset,
+                    balance.wallet,
+                    treasury.network.name,
+                    treasury.address()
+                ),
+            }
+        }
+        rocket::tokio::time::sleep(interval).await;
+    }
+}
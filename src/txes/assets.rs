@@ -0,0 +1,159 @@
+use webb::substrate::subxt::blocks::ExtrinsicEvents;
+use webb::substrate::subxt::dynamic::Value;
+use webb::substrate::subxt::events::StaticEvent;
+use webb::substrate::subxt::ext::scale_decode::DecodeAsType;
+use webb::substrate::subxt::ext::scale_value::At;
+use webb::substrate::subxt::storage::Storage;
+use webb::substrate::subxt::tx::DynamicPayload;
+use webb::substrate::subxt::utils::AccountId32;
+use webb::substrate::subxt::{OnlineClient, PolkadotConfig};
+
+use crate::error::Error;
+
+use super::networks::{AssetPallet, SubstrateAsset};
+use super::types::AssetTransfer;
+
+/// The `Transferred` event of `pallet-assets`.
+#[derive(Debug, DecodeAsType)]
+#[decode_as_type(crate_path = "webb::substrate::subxt::ext::scale_decode")]
+struct AssetsTransferred {
+    asset_id: u128,
+    from: AccountId32,
+    to: AccountId32,
+    amount: u128,
+}
+
+impl StaticEvent for AssetsTransferred {
+    const PALLET: &'static str = "Assets";
+    const EVENT: &'static str = "Transferred";
+}
+
+/// The `Transfer` event of ORML `tokens`.
+#[derive(Debug, DecodeAsType)]
+#[decode_as_type(crate_path = "webb::substrate::subxt::ext::scale_decode")]
+struct TokensTransfer {
+    currency_id: u128,
+    from: AccountId32,
+    to: AccountId32,
+    amount: u128,
+}
+
+impl StaticEvent for TokensTransfer {
+    const PALLET: &'static str = "Tokens";
+    const EVENT: &'static str = "Transfer";
+}
+
+/// Builds the extrinsic transferring `asset` to `to`.
+///
+/// The call is built dynamically, as the asset pallets are not part of the
+/// static runtime API of every chain.
+pub fn transfer_call(
+    asset: &AssetTransfer,
+    to: &AccountId32,
+) -> DynamicPayload {
+    let dest = Value::unnamed_variant("Id", [Value::from_bytes(to.0)]);
+    let asset_id = Value::u128(u128::from(asset.asset_id));
+    let amount = Value::u128(asset.amount);
+    match asset.pallet {
+        AssetPallet::Assets => webb::substrate::subxt::dynamic::tx(
+            "Assets",
+            "transfer",
+            vec![asset_id, dest, amount],
+        ),
+        AssetPallet::Tokens => webb::substrate::subxt::dynamic::tx(
+            "Tokens",
+            "transfer",
+            vec![dest, asset_id, amount],
+        ),
+    }
+}
+
+/// Checks that the events of an extrinsic contain the transfer of `asset`
+/// from `from` to `to`.
+pub fn verify_transfer(
+    events: &ExtrinsicEvents<PolkadotConfig>,
+    asset: &AssetTransfer,
+    from: &AccountId32,
+    to: &AccountId32,
+) -> Result<(), Error> {
+    let transfers = match asset.pallet {
+        AssetPallet::Assets => events
+            .find::<AssetsTransferred>()
+            .map(|ev| ev.map(|ev| (ev.asset_id, ev.from, ev.to, ev.amount)))
+            .collect::<Result<Vec<_>, _>>(),
+        AssetPallet::Tokens => events
+            .find::<TokensTransfer>()
+            .map(|ev| ev.map(|ev| (ev.currency_id, ev.from, ev.to, ev.amount)))
+            .collect::<Result<Vec<_>, _>>(),
+    }
+    .map_err(|e| Error::Custom(e.to_string()))?;
+
+    let transferred = transfers.iter().any(|(asset_id, src, dest, amount)| {
+        *asset_id == u128::from(asset.asset_id)
+            && src == from
+            && dest == to
+            && *amount == asset.amount
+    });
+    if transferred {
+        println!(
+            "Transfered {} of asset {} -> {to}",
+            asset.amount, asset.asset_id
+        );
+        Ok(())
+    } else {
+        Err(Error::Custom(format!(
+            "No transfer of {} of asset {} from {from} to {to} found in the extrinsic events",
+            asset.amount, asset.asset_id
+        )))
+    }
+}
+
+/// Fetches the balance of `asset` held by `account`, in the smallest unit of
+/// the asset.
+///
+/// Like the transfers, the storage is read dynamically: the `balance` of
+/// `Assets.Account` or the `free` balance of ORML `Tokens.Accounts`.
+pub async fn balance(
+    storage: &Storage<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+    asset: &SubstrateAsset,
+    account: &AccountId32,
+) -> Result<u128, Error> {
+    let asset_id = Value::u128(u128::from(asset.asset_id));
+    let account_id = Value::from_bytes(account.0);
+    let (address, field) = match asset.pallet {
+        AssetPallet::Assets => (
+            webb::substrate::subxt::dynamic::storage(
+                "Assets",
+                "Account",
+                vec![asset_id, account_id],
+            ),
+            "balance",
+        ),
+        AssetPallet::Tokens => (
+            webb::substrate::subxt::dynamic::storage(
+                "Tokens",
+                "Accounts",
+                vec![account_id, asset_id],
+            ),
+            "free",
+        ),
+    };
+    let account_data = storage
+        .fetch(&address)
+        .await
+        .map_err(|e| Error::Custom(e.to_string()))?;
+    // Accounts that never held the asset have no entry.
+    let Some(account_data) = account_data else {
+        return Ok(0);
+    };
+    let value = account_data
+        .to_value()
+        .map_err(|e| Error::Custom(e.to_string()))?;
+    value
+        .at(field)
+        .and_then(|balance| balance.as_u128())
+        .ok_or(Error::Custom(format!(
+            "No {field} found in the account of asset {}",
+            asset.asset_id
+        )))
+}
@@ -0,0 +1,916 @@
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rocket::futures::{stream, StreamExt};
+use rocket::tokio::sync::Mutex as AsyncMutex;
+use serde::{de::DeserializeOwned, Serialize};
+use webb::evm::ethers::providers::{
+    Http, HttpClientError, JsonRpcClient, JsonRpcError, RpcError,
+};
+use webb::evm::ethers::types::{Bytes, H256};
+use webb::evm::ethers::utils::keccak256;
+use webb::substrate::subxt::error::RpcError as SubxtRpcError;
+use webb::substrate::subxt::rpc::{
+    RawValue, RpcClientT, RpcFuture, RpcSubscription, RpcSubscriptionStream,
+};
+
+use crate::error::Error;
+
+/// The score of an endpoint that has not failed recently.
+const MAX_SCORE: i64 = 100;
+/// The lowest score an endpoint can reach.
+const MIN_SCORE: i64 = -100;
+/// How much a successful request raises the score of an endpoint.
+const SUCCESS_REWARD: i64 = 1;
+/// How much a failed request lowers the score of an endpoint.
+const FAILURE_PENALTY: i64 = 25;
+/// How long after its last failure an endpoint is considered healthy again.
+const RECOVERY_PERIOD: Duration = Duration::from_secs(60);
+/// The time to wait before reconnecting to a Substrate endpoint after a
+/// failed attempt, doubled on every further failure.
+const MIN_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+/// The longest time to wait before reconnecting to a Substrate endpoint.
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(60);
+/// The time to wait for a Substrate endpoint to connect.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// The time to wait for a Substrate endpoint to answer a health check.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tracks how healthy an RPC endpoint has been recently.
+#[derive(Debug)]
+struct EndpointHealth {
+    score: AtomicI64,
+    last_failure: Mutex<Option<Instant>>,
+}
+
+impl EndpointHealth {
+    fn new() -> Self {
+        Self {
+            score: AtomicI64::new(MAX_SCORE),
+            last_failure: Mutex::new(None),
+        }
+    }
+
+    fn score(&self) -> i64 {
+        let mut last_failure =
+            self.last_failure.lock().expect("health lock poisoned");
+        if last_failure.is_some_and(|at| at.elapsed() >= RECOVERY_PERIOD) {
+            *last_failure = None;
+            self.score.store(MAX_SCORE, Ordering::SeqCst);
+        }
+        self.score.load(Ordering::SeqCst)
+    }
+
+    fn record_success(&self) {
+        let _ = self.score.fetch_update(
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+            |score| Some((score + SUCCESS_REWARD).min(MAX_SCORE)),
+        );
+    }
+
+    fn record_failure(&self) {
+        *self.last_failure.lock().expect("health lock poisoned") =
+            Some(Instant::now());
+        let _ = self.score.fetch_update(
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+            |score| Some((score - FAILURE_PENALTY).max(MIN_SCORE)),
+        );
+    }
+}
+
+#[derive(Debug)]
+struct Endpoint<C> {
+    url: String,
+    client: C,
+    health: EndpointHealth,
+}
+
+/// An RPC client that sends every request to the healthiest of the
+/// endpoints configured for a network, and switches over to the next one
+/// whenever an endpoint fails.
+#[derive(Debug)]
+pub struct FailoverClient<C> {
+    endpoints: Arc<Vec<Endpoint<C>>>,
+}
+
+impl<C> Clone for FailoverClient<C> {
+    fn clone(&self) -> Self {
+        Self {
+            endpoints: self.endpoints.clone(),
+        }
+    }
+}
+
+impl<C> FailoverClient<C> {
+    fn from_clients(clients: Vec<(String, C)>) -> Result<Self, Error> {
+        if clients.is_empty() {
+            return Err(Error::Custom(
+                "No reachable RPC endpoints configured".to_string(),
+            ));
+        }
+        let endpoints = clients
+            .into_iter()
+            .map(|(url, client)| Endpoint {
+                url,
+                client,
+                health: EndpointHealth::new(),
+            })
+            .collect();
+        Ok(Self {
+            endpoints: Arc::new(endpoints),
+        })
+    }
+
+    /// Returns the endpoints ordered from the healthiest to the least
+    /// healthy one. Endpoints with the same score keep their configured
+    /// order, so the first configured endpoint is preferred.
+    fn ranked(&self) -> Vec<&Endpoint<C>> {
+        self.ranked_indices()
+            .into_iter()
+            .map(|index| &self.endpoints[index])
+            .collect()
+    }
+
+    /// Returns the indices of the endpoints in the order of [`Self::ranked`].
+    fn ranked_indices(&self) -> Vec<usize> {
+        let mut indices: Vec<_> = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .map(|(index, endpoint)| (endpoint.health.score(), index))
+            .collect();
+        indices.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        indices.into_iter().map(|(_, index)| index).collect()
+    }
+}
+
+impl FailoverClient<Http> {
+    /// Creates an EVM client over all of the given HTTP endpoints.
+    pub fn new_http(urls: &[String]) -> Result<Self, Error> {
+        let clients = urls
+            .iter()
+            .map(|url| {
+                url.parse::<Http>()
+                    .map(|client| (url.clone(), client))
+                    .map_err(|e| {
+                        Error::Custom(format!("Invalid RPC url {url}: {e}"))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_clients(clients)
+    }
+}
+
+#[rocket::async_trait]
+impl JsonRpcClient for FailoverClient<Http> {
+    type Error = HttpClientError;
+
+    async fn request<T, R>(
+        &self,
+        method: &str,
+        params: T,
+    ) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(params).map_err(|err| {
+            HttpClientError::SerdeJson {
+                err,
+                text: String::new(),
+            }
+        })?;
+        let mut last_error = None;
+        for endpoint in self.ranked() {
+            match endpoint.client.request(method, params.clone()).await {
+                Ok(res) => {
+                    endpoint.health.record_success();
+                    return Ok(res);
+                }
+                // The node answered the request, so the endpoint is healthy
+                // and another one would answer the same way.
+                Err(e) if e.is_error_response() => {
+                    endpoint.health.record_success();
+                    let resent = match (&last_error, e.as_error_response()) {
+                        (Some(_), Some(error)) => {
+                            already_sent(method, &params, error)
+                        }
+                        _ => None,
+                    };
+                    return match resent {
+                        Some(tx_hash) => serde_json::to_value(tx_hash)
+                            .and_then(serde_json::from_value)
+                            .map_err(|err| HttpClientError::SerdeJson {
+                                err,
+                                text: String::new(),
+                            }),
+                        None => Err(e),
+                    };
+                }
+                Err(e) => {
+                    eprintln!(
+                        "RPC endpoint {} failed on {method}: {e}",
+                        endpoint.url
+                    );
+                    endpoint.health.record_failure();
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.expect("failover client has at least one endpoint"))
+    }
+}
+
+/// Returns the hash of a raw transaction that an endpoint rejected as
+/// already known, or as reusing a nonce that is taken, after an earlier
+/// endpoint failed while it was being sent.
+///
+/// The earlier endpoint may have accepted the transaction before failing,
+/// in which case it is pending or even mined, so it is taken as sent
+/// rather than reported as failed. A transaction whose nonce was taken by
+/// another one is then never mined, and times out.
+fn already_sent(
+    method: &str,
+    params: &serde_json::Value,
+    error: &JsonRpcError,
+) -> Option<H256> {
+    const ALREADY_SENT: [&str; 4] = [
+        "already known",
+        "known transaction",
+        "already imported",
+        "nonce too low",
+    ];
+    if method != "eth_sendRawTransaction" {
+        return None;
+    }
+    let message = error.message.to_lowercase();
+    if !ALREADY_SENT.iter().any(|known| message.contains(known)) {
+        return None;
+    }
+    let raw: Bytes = serde_json::from_value(params.get(0)?.clone()).ok()?;
+    Some(keccak256(&raw).into())
+}
+
+/// A connection being opened to a Substrate endpoint.
+type ConnectFuture =
+    Pin<Box<dyn Future<Output = Result<Arc<dyn RpcClientT>, String>> + Send>>;
+
+/// Opens a connection to a Substrate endpoint.
+type Connect = Arc<dyn Fn(String) -> ConnectFuture + Send + Sync>;
+
+/// The connection of a [`WsEndpoint`], and when it may be reopened after it
+/// failed to connect.
+struct Connection {
+    client: Option<Arc<dyn RpcClientT>>,
+    retry_at: Instant,
+    backoff: Duration,
+}
+
+/// A Substrate endpoint, whose connection is reopened on demand once it
+/// closes. Failed attempts to reconnect are backed off exponentially, so an
+/// endpoint that is down is not hammered with connections.
+pub struct WsEndpoint {
+    url: String,
+    connect: Connect,
+    connection: AsyncMutex<Connection>,
+}
+
+/// The outcome of a call to a [`WsEndpoint`].
+enum Call<T> {
+    /// The node answered the call.
+    Answered(Result<T, SubxtRpcError>),
+    /// The endpoint could not be reached.
+    Failed(SubxtRpcError),
+}
+
+impl Debug for WsEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WsEndpoint")
+            .field("url", &self.url)
+            .finish()
+    }
+}
+
+impl WsEndpoint {
+    fn new(url: String, connect: Connect) -> Self {
+        Self {
+            url,
+            connect,
+            connection: AsyncMutex::new(Connection {
+                client: None,
+                retry_at: Instant::now(),
+                backoff: MIN_RECONNECT_BACKOFF,
+            }),
+        }
+    }
+
+    /// Returns the open connection, or opens a new one unless the last
+    /// attempt failed too recently. The flag is `true` for a new
+    /// connection.
+    async fn client(
+        &self,
+    ) -> Result<(Arc<dyn RpcClientT>, bool), SubxtRpcError> {
+        let mut connection = self.connection.lock().await;
+        if let Some(client) = &connection.client {
+            return Ok((client.clone(), false));
+        }
+        if Instant::now() < connection.retry_at {
+            return Err(SubxtRpcError::ClientError(
+                format!("Endpoint {} is waiting to reconnect", self.url).into(),
+            ));
+        }
+        let connect = (self.connect)(self.url.clone());
+        let res = match rocket::tokio::time::timeout(CONNECT_TIMEOUT, connect)
+            .await
+        {
+            Ok(res) => res,
+            Err(_) => Err("timed out".to_string()),
+        };
+        match res {
+            Ok(client) => {
+                connection.client = Some(client.clone());
+                connection.backoff = MIN_RECONNECT_BACKOFF;
+                Ok((client, true))
+            }
+            Err(e) => {
+                connection.retry_at = Instant::now() + connection.backoff;
+                connection.backoff =
+                    (connection.backoff * 2).min(MAX_RECONNECT_BACKOFF);
+                Err(SubxtRpcError::ClientError(
+                    format!("Cannot connect to {}: {e}", self.url).into(),
+                ))
+            }
+        }
+    }
+
+    /// Checks whether the node still answers on a connection that returned
+    /// an error, and closes the connection if not. Returns `true` if the
+    /// connection is alive.
+    async fn check(&self, client: &Arc<dyn RpcClientT>) -> bool {
+        let probe = client.request_raw("system_health", None);
+        if matches!(
+            rocket::tokio::time::timeout(PROBE_TIMEOUT, probe).await,
+            Ok(Ok(_))
+        ) {
+            return true;
+        }
+        let mut connection = self.connection.lock().await;
+        if connection
+            .client
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, client))
+        {
+            eprintln!("Connection to {} closed, reconnecting", self.url);
+            connection.client = None;
+            connection.retry_at = Instant::now();
+        }
+        false
+    }
+
+    /// Calls the endpoint, reconnecting and calling it once more if its
+    /// connection turns out to be closed.
+    async fn call<T, F, Fut>(&self, f: F) -> Call<(T, Arc<dyn RpcClientT>)>
+    where
+        F: Fn(Arc<dyn RpcClientT>) -> Fut,
+        Fut: Future<Output = Result<T, SubxtRpcError>>,
+    {
+        loop {
+            let (client, fresh) = match self.client().await {
+                Ok(client) => client,
+                Err(e) => return Call::Failed(e),
+            };
+            match f(client.clone()).await {
+                Ok(res) => return Call::Answered(Ok((res, client))),
+                Err(e) if self.check(&client).await => {
+                    return Call::Answered(Err(e))
+                }
+                Err(e) if fresh => return Call::Failed(e),
+                Err(_) => continue,
+            }
+        }
+    }
+}
+
+impl FailoverClient<WsEndpoint> {
+    /// Connects a Substrate client to all of the given endpoints. Endpoints
+    /// that cannot be reached yet are reconnected to later, but at least one
+    /// of them has to be reachable.
+    pub async fn connect(urls: &[String]) -> Result<Self, Error> {
+        use webb::substrate::subxt::client::default_rpc_client;
+
+        Self::with_connector(
+            urls,
+            Arc::new(|url: String| -> ConnectFuture {
+                Box::pin(async move {
+                    default_rpc_client(&url)
+                        .await
+                        .map(|client| Arc::new(client) as Arc<dyn RpcClientT>)
+                        .map_err(|e| e.to_string())
+                })
+            }),
+        )
+        .await
+    }
+
+    async fn with_connector(
+        urls: &[String],
+        connect: Connect,
+    ) -> Result<Self, Error> {
+        let endpoints = urls
+            .iter()
+            .map(|url| {
+                (url.clone(), WsEndpoint::new(url.clone(), connect.clone()))
+            })
+            .collect();
+        let client = Self::from_clients(endpoints)?;
+        let mut reachable = false;
+        for endpoint in client.endpoints.iter() {
+            match endpoint.client.client().await {
+                Ok(_) => reachable = true,
+                Err(e) => {
+                    eprintln!("Error connecting to substrate node: {e}");
+                    endpoint.health.record_failure();
+                }
+            }
+        }
+        if !reachable {
+            return Err(Error::Custom(
+                "No reachable RPC endpoints configured".to_string(),
+            ));
+        }
+        Ok(client)
+    }
+
+    /// Subscribes on the healthiest endpoint that accepts the subscription,
+    /// returning the index of the endpoint and the connection used.
+    async fn subscribe(
+        &self,
+        sub: &str,
+        params: Option<Box<RawValue>>,
+        unsub: &str,
+    ) -> Result<(usize, Arc<dyn RpcClientT>, RpcSubscription), SubxtRpcError>
+    {
+        let mut last_error = None;
+        for index in self.ranked_indices() {
+            let endpoint = &self.endpoints[index];
+            let call = endpoint
+                .client
+                .call(|client| {
+                    let params = params.clone();
+                    async move {
+                        client.subscribe_raw(sub, params, unsub).await
+                    }
+                })
+                .await;
+            match call {
+                Call::Answered(Ok((subscription, client))) => {
+                    endpoint.health.record_success();
+                    return Ok((index, client, subscription));
+                }
+                Call::Answered(Err(e)) => {
+                    endpoint.health.record_success();
+                    return Err(e);
+                }
+                Call::Failed(e) => {
+                    eprintln!(
+                        "RPC endpoint {} failed on {sub}: {e}",
+                        endpoint.url
+                    );
+                    endpoint.health.record_failure();
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(no_endpoints))
+    }
+
+    /// Follows a subscription, moving it to the healthiest endpoint whenever
+    /// the endpoint it is on drops it.
+    fn resubscribing(
+        &self,
+        sub: &str,
+        params: Option<Box<RawValue>>,
+        unsub: &str,
+        endpoint: usize,
+        client: Arc<dyn RpcClientT>,
+        stream: RpcSubscriptionStream,
+    ) -> RpcSubscriptionStream {
+        let state = Resubscription {
+            failover: self.clone(),
+            sub: sub.to_string(),
+            params,
+            unsub: unsub.to_string(),
+            endpoint,
+            client,
+            stream: Some(stream),
+        };
+        Box::pin(stream::unfold(state, |mut state| async move {
+            loop {
+                let item = state.stream.as_mut()?.next().await;
+                let endpoint = &state.failover.endpoints[state.endpoint];
+                match item {
+                    Some(Ok(item)) => return Some((Ok(item), state)),
+                    Some(Err(e)) => eprintln!(
+                        "Subscription {} on {} failed: {e}",
+                        state.sub, endpoint.url
+                    ),
+                    None => eprintln!(
+                        "Subscription {} on {} ended",
+                        state.sub, endpoint.url
+                    ),
+                }
+                if !endpoint.client.check(&state.client).await {
+                    endpoint.health.record_failure();
+                }
+                match state
+                    .failover
+                    .subscribe(&state.sub, state.params.clone(), &state.unsub)
+                    .await
+                {
+                    Ok((endpoint, client, subscription)) => {
+                        state.endpoint = endpoint;
+                        state.client = client;
+                        state.stream = Some(subscription.stream);
+                    }
+                    Err(e) => {
+                        state.stream = None;
+                        return Some((Err(e), state));
+                    }
+                }
+            }
+        }))
+    }
+}
+
+/// A subscription followed across endpoints.
+struct Resubscription {
+    failover: FailoverClient<WsEndpoint>,
+    sub: String,
+    params: Option<Box<RawValue>>,
+    unsub: String,
+    endpoint: usize,
+    client: Arc<dyn RpcClientT>,
+    stream: Option<RpcSubscriptionStream>,
+}
+
+/// Returns `true` if a subscription can be resumed on another endpoint.
+/// Watching an extrinsic would submit it again, so its subscription ends
+/// with the endpoint it was submitted to.
+fn is_resumable(sub: &str) -> bool {
+    !sub.starts_with("author_")
+}
+
+impl RpcClientT for FailoverClient<WsEndpoint> {
+    fn request_raw<'a>(
+        &'a self,
+        method: &'a str,
+        params: Option<Box<RawValue>>,
+    ) -> RpcFuture<'a, Box<RawValue>> {
+        Box::pin(async move {
+            let mut last_error = None;
+            for endpoint in self.ranked() {
+                let call = endpoint
+                    .client
+                    .call(|client| {
+                        let params = params.clone();
+                        async move { client.request_raw(method, params).await }
+                    })
+                    .await;
+                match call {
+                    Call::Answered(res) => {
+                        endpoint.health.record_success();
+                        return res.map(|(res, _)| res);
+                    }
+                    Call::Failed(e) => {
+                        eprintln!(
+                            "RPC endpoint {} failed on {method}: {e}",
+                            endpoint.url
+                        );
+                        endpoint.health.record_failure();
+                        last_error = Some(e);
+                    }
+                }
+            }
+            Err(last_error.unwrap_or_else(no_endpoints))
+        })
+    }
+
+    fn subscribe_raw<'a>(
+        &'a self,
+        sub: &'a str,
+        params: Option<Box<RawValue>>,
+        unsub: &'a str,
+    ) -> RpcFuture<'a, RpcSubscription> {
+        Box::pin(async move {
+            let (endpoint, client, subscription) =
+                self.subscribe(sub, params.clone(), unsub).await?;
+            if !is_resumable(sub) {
+                return Ok(subscription);
+            }
+            Ok(RpcSubscription {
+                stream: self.resubscribing(
+                    sub,
+                    params,
+                    unsub,
+                    endpoint,
+                    client,
+                    subscription.stream,
+                ),
+                id: subscription.id,
+            })
+        })
+    }
+}
+
+fn no_endpoints() -> SubxtRpcError {
+    SubxtRpcError::ClientError("No RPC endpoints available".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize};
+
+    use super::*;
+
+    fn rpc_error(message: &str) -> JsonRpcError {
+        JsonRpcError {
+            code: -32000,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+
+    #[test]
+    fn takes_known_transactions_as_sent_after_a_failover() {
+        let raw = Bytes::from(vec![0x02, 0xf8, 0x6c]);
+        let params = serde_json::json!([raw]);
+        let tx_hash = H256::from(keccak256(&raw));
+        for message in ["already known", "nonce too low: next nonce 5"] {
+            assert_eq!(
+                already_sent(
+                    "eth_sendRawTransaction",
+                    &params,
+                    &rpc_error(message)
+                ),
+                Some(tx_hash)
+            );
+        }
+        assert_eq!(
+            already_sent(
+                "eth_sendRawTransaction",
+                &params,
+                &rpc_error("insufficient funds for gas * price + value")
+            ),
+            None
+        );
+        assert_eq!(
+            already_sent("eth_call", &params, &rpc_error("already known")),
+            None
+        );
+    }
+
+    /// A node that can go down, and whose connections can be closed.
+    #[derive(Default)]
+    struct MockNode {
+        name: &'static str,
+        down: AtomicBool,
+        connects: AtomicUsize,
+        connections: Mutex<Vec<Arc<AtomicBool>>>,
+    }
+
+    impl MockNode {
+        fn new(name: &'static str) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                ..Default::default()
+            })
+        }
+
+        fn close_connections(&self) {
+            for closed in self.connections.lock().unwrap().iter() {
+                closed.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    struct MockClient {
+        node: Arc<MockNode>,
+        closed: Arc<AtomicBool>,
+    }
+
+    impl MockClient {
+        fn check(&self) -> Result<(), SubxtRpcError> {
+            if self.closed.load(Ordering::SeqCst)
+                || self.node.down.load(Ordering::SeqCst)
+            {
+                return Err(SubxtRpcError::ClientError("closed".into()));
+            }
+            Ok(())
+        }
+    }
+
+    fn raw(value: &str) -> Box<RawValue> {
+        RawValue::from_string(format!("\"{value}\"")).unwrap()
+    }
+
+    impl RpcClientT for MockClient {
+        fn request_raw<'a>(
+            &'a self,
+            method: &'a str,
+            _params: Option<Box<RawValue>>,
+        ) -> RpcFuture<'a, Box<RawValue>> {
+            Box::pin(async move {
+                self.check()?;
+                match method {
+                    "invalid" => {
+                        Err(SubxtRpcError::ClientError("invalid method".into()))
+                    }
+                    _ => Ok(raw(self.node.name)),
+                }
+            })
+        }
+
+        fn subscribe_raw<'a>(
+            &'a self,
+            _sub: &'a str,
+            _params: Option<Box<RawValue>>,
+            _unsub: &'a str,
+        ) -> RpcFuture<'a, RpcSubscription> {
+            Box::pin(async move {
+                self.check()?;
+                // Every subscription yields one item and is then dropped.
+                let item = Ok(raw(self.node.name));
+                Ok(RpcSubscription {
+                    stream: Box::pin(stream::iter([item])),
+                    id: None,
+                })
+            })
+        }
+    }
+
+    async fn connect(
+        nodes: &[Arc<MockNode>],
+    ) -> Result<FailoverClient<WsEndpoint>, Error> {
+        let urls: Vec<_> = nodes.iter().map(|n| n.name.to_string()).collect();
+        let nodes = nodes.to_vec();
+        FailoverClient::with_connector(
+            &urls,
+            Arc::new(move |url: String| -> ConnectFuture {
+                let node =
+                    nodes.iter().find(|node| node.name == url).unwrap().clone();
+                Box::pin(async move {
+                    node.connects.fetch_add(1, Ordering::SeqCst);
+                    if node.down.load(Ordering::SeqCst) {
+                        return Err("connection refused".to_string());
+                    }
+                    let closed = Arc::new(AtomicBool::new(false));
+                    node.connections.lock().unwrap().push(closed.clone());
+                    Ok(Arc::new(MockClient { node, closed })
+                        as Arc<dyn RpcClientT>)
+                })
+            }),
+        )
+        .await
+    }
+
+    async fn request(client: &FailoverClient<WsEndpoint>) -> String {
+        let res = client.request_raw("system_name", None).await.unwrap();
+        serde_json::from_str(res.get()).unwrap()
+    }
+
+    async fn next(subscription: &mut RpcSubscriptionStream) -> String {
+        let item = subscription.next().await.unwrap().unwrap();
+        serde_json::from_str(item.get()).unwrap()
+    }
+
+    #[test]
+    fn scores_failures_and_recovers() {
+        let health = EndpointHealth::new();
+        assert_eq!(health.score(), MAX_SCORE);
+        health.record_failure();
+        assert_eq!(health.score(), MAX_SCORE - FAILURE_PENALTY);
+        health.record_success();
+        assert_eq!(health.score(), MAX_SCORE - FAILURE_PENALTY + 1);
+        for _ in 0..10 {
+            health.record_failure();
+        }
+        assert_eq!(health.score(), MIN_SCORE);
+        *health.last_failure.lock().unwrap() =
+            Instant::now().checked_sub(RECOVERY_PERIOD);
+        assert_eq!(health.score(), MAX_SCORE);
+    }
+
+    #[test]
+    fn ranks_by_score_then_configured_order() {
+        let client = FailoverClient::from_clients(vec![
+            ("a".to_string(), ()),
+            ("b".to_string(), ()),
+            ("c".to_string(), ()),
+        ])
+        .unwrap();
+        let urls = |client: &FailoverClient<()>| -> Vec<String> {
+            client.ranked().iter().map(|e| e.url.clone()).collect()
+        };
+        assert_eq!(urls(&client), ["a", "b", "c"]);
+        client.endpoints[0].health.record_failure();
+        assert_eq!(urls(&client), ["b", "c", "a"]);
+        client.endpoints[2].health.record_failure();
+        client.endpoints[2].health.record_failure();
+        assert_eq!(urls(&client), ["b", "a", "c"]);
+    }
+
+    #[rocket::async_test]
+    async fn fails_over_to_the_next_endpoint() {
+        let (a, b) = (MockNode::new("a"), MockNode::new("b"));
+        let client = connect(&[a.clone(), b.clone()]).await.unwrap();
+        assert_eq!(request(&client).await, "a");
+        a.down.store(true, Ordering::SeqCst);
+        assert_eq!(request(&client).await, "b");
+        assert!(
+            client.endpoints[0].health.score()
+                < client.endpoints[1].health.score()
+        );
+    }
+
+    #[rocket::async_test]
+    async fn answered_errors_do_not_fail_over() {
+        let (a, b) = (MockNode::new("a"), MockNode::new("b"));
+        let client = connect(&[a.clone(), b.clone()]).await.unwrap();
+        assert!(client.request_raw("invalid", None).await.is_err());
+        assert_eq!(client.endpoints[0].health.score(), MAX_SCORE);
+        assert_eq!(a.connects.load(Ordering::SeqCst), 1);
+    }
+
+    #[rocket::async_test]
+    async fn reconnects_closed_connections() {
+        let a = MockNode::new("a");
+        let client = connect(&[a.clone()]).await.unwrap();
+        a.close_connections();
+        assert_eq!(request(&client).await, "a");
+        assert_eq!(a.connects.load(Ordering::SeqCst), 2);
+    }
+
+    #[rocket::async_test]
+    async fn keeps_unreachable_endpoints_and_backs_off() {
+        let (a, b) = (MockNode::new("a"), MockNode::new("b"));
+        a.down.store(true, Ordering::SeqCst);
+        let client = connect(&[a.clone(), b.clone()]).await.unwrap();
+        assert_eq!(a.connects.load(Ordering::SeqCst), 1);
+        let endpoint = &client.endpoints[0].client;
+        a.down.store(false, Ordering::SeqCst);
+        // The failed attempt is backed off.
+        assert!(endpoint.client().await.is_err());
+        assert_eq!(a.connects.load(Ordering::SeqCst), 1);
+        {
+            let mut connection = endpoint.connection.lock().await;
+            assert_eq!(connection.backoff, MIN_RECONNECT_BACKOFF * 2);
+            connection.retry_at = Instant::now();
+        }
+        assert!(endpoint.client().await.is_ok());
+        assert_eq!(a.connects.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            endpoint.connection.lock().await.backoff,
+            MIN_RECONNECT_BACKOFF
+        );
+    }
+
+    #[rocket::async_test]
+    async fn fails_without_reachable_endpoints() {
+        let a = MockNode::new("a");
+        a.down.store(true, Ordering::SeqCst);
+        assert!(connect(&[a]).await.is_err());
+    }
+
+    #[rocket::async_test]
+    async fn moves_dropped_subscriptions() {
+        let (a, b) = (MockNode::new("a"), MockNode::new("b"));
+        let client = connect(&[a.clone(), b.clone()]).await.unwrap();
+        let mut subscription = client
+            .subscribe_raw("chain_subscribeNewHeads", None, "unsub")
+            .await
+            .unwrap()
+            .stream;
+        assert_eq!(next(&mut subscription).await, "a");
+        a.down.store(true, Ordering::SeqCst);
+        assert_eq!(next(&mut subscription).await, "b");
+    }
+
+    #[rocket::async_test]
+    async fn does_not_resume_extrinsic_subscriptions() {
+        let a = MockNode::new("a");
+        let client = connect(&[a.clone()]).await.unwrap();
+        let mut subscription = client
+            .subscribe_raw("author_submitAndWatchExtrinsic", None, "unsub")
+            .await
+            .unwrap()
+            .stream;
+        assert!(subscription.next().await.is_some());
+        assert!(subscription.next().await.is_none());
+    }
+}
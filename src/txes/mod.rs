@@ -1,3 +1,5 @@
+pub mod assets;
+pub mod failover;
 pub mod networks;
 pub mod processor;
 pub mod types;
@@ -1,125 +1,779 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Deserialize;
+use webb::evm::ethers::types::Address;
 use webb_proposals::TypedChainId;
+use zeroize::Zeroizing;
+
+use crate::error::Error;
+use crate::helpers::files::{
+    get_evm_rpc_urls, get_evm_token_address, get_substrate_rpc_urls,
+};
+use crate::keys::Secret;
+
+const fn default_decimals() -> u8 {
+    18
+}
+
+const fn default_enabled() -> bool {
+    true
+}
+
+const fn default_concurrency() -> usize {
+    1
+}
+
+const fn default_confirmations() -> u64 {
+    1
+}
+
+const fn default_escalator_coefficient() -> f64 {
+    1.125
+}
+
+const fn default_escalator_every() -> Duration {
+    Duration::from_secs(60)
+}
+
+const fn default_escalator_stuck_after() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
+const fn default_max_fee_hold() -> Duration {
+    Duration::from_secs(10 * 60)
+}
+
+fn default_wallet_indices() -> Vec<u32> {
+    vec![0]
+}
+
+const fn default_remote_signer_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_substrate_sign_method() -> String {
+    "substrate_signPayload".to_string()
+}
+
+const fn default_inclusion_timeout() -> Duration {
+    Duration::from_secs(60)
+}
+
+const fn default_finalization_timeout() -> Duration {
+    Duration::from_secs(120)
+}
+
+/// The symbol of the token listed in `chainIdFaucetToken.json`, which is
+/// used when a network configures no tokens.
+pub const DEFAULT_TOKEN_SYMBOL: &str = "default";
+
+/// How the faucet dispenses an ERC20 token.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DispenseMode {
+    /// Transfer tokens held by the faucet account.
+    #[default]
+    Transfer,
+    /// Mint new tokens, which requires the faucet account to hold the
+    /// `MINTER_ROLE` of the token.
+    Mint,
+}
+
+/// An ERC20 token the faucet drips on an EVM network.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FaucetToken {
+    /// The symbol users select the token by, e.g. `webbWETH`.
+    pub symbol: String,
+    /// The address of the token contract.
+    pub address: Address,
+    /// The amount of tokens sent per claim.
+    /// If not set, `token_amount` is used.
+    #[serde(default)]
+    pub amount: Option<u64>,
+    /// The time to wait between claims of this token.
+    /// If not set, `time_to_wait_between_claims` is used.
+    #[serde(default)]
+    pub cooldown: Option<Duration>,
+    /// How the token is dispensed.
+    #[serde(default)]
+    pub mode: DispenseMode,
+    /// The balance, in whole tokens, below which claims of the token are
+    /// refused. If not set, the amount of a claim is used.
+    #[serde(default)]
+    pub min_balance: Option<u64>,
+}
+
+/// The pallet a Substrate asset is transferred with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetPallet {
+    /// `pallet-assets`, transferred with `assets.transfer`.
+    #[default]
+    Assets,
+    /// ORML `tokens`, transferred with `tokens.transfer`.
+    Tokens,
+}
+
+/// How the faucet prices EVM transactions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FeeStrategy {
+    /// EIP-1559 transactions, with fees estimated from `eth_feeHistory`.
+    Eip1559,
+    /// Legacy transactions, priced with `eth_gasPrice`.
+    #[default]
+    Legacy,
+    /// Legacy transactions with a fixed gas price.
+    Fixed {
+        /// The gas price in wei.
+        gas_price: u64,
+    },
+}
+
+/// How the fees of EVM transactions that are not mined in time are bumped.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct EscalatorConfig {
+    /// The factor the fees are multiplied by on every bump. Most nodes only
+    /// accept a replacement that raises the fees by at least 10%.
+    #[serde(default = "default_escalator_coefficient")]
+    pub coefficient: f64,
+    /// The time to wait for a transaction to be mined before bumping its
+    /// fees.
+    #[serde(default = "default_escalator_every")]
+    pub every: Duration,
+    /// The time after which a transaction that is still not mined is
+    /// considered stuck, and the nonce gaps holding it up are filled.
+    #[serde(default = "default_escalator_stuck_after")]
+    pub stuck_after: Duration,
+}
+
+impl Default for EscalatorConfig {
+    fn default() -> Self {
+        Self {
+            coefficient: default_escalator_coefficient(),
+            every: default_escalator_every(),
+            stuck_after: default_escalator_stuck_after(),
+        }
+    }
+}
+
+/// What happens to EVM claims while the fees of their network are above its
+/// `max_fee_per_gas`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverMaxFee {
+    /// Hold the claims in the queue until the fees drop.
+    Hold,
+    /// Reject the claims.
+    #[default]
+    Reject,
+}
+
+/// The treasury account refilling the faucet account of a network, holding
+/// its own signer.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TreasuryConfig {
+    /// The secret or signer of the treasury account, read like the keys of
+    /// the hot wallets. The treasury account is the first account of its
+    /// keys, and never falls back to the keys of the hot wallets.
+    pub keys: KeyConfig,
+    /// The native balance, in whole tokens, below which the faucet account
+    /// is refilled.
+    pub low_water: f64,
+    /// The native balance, in whole tokens, the faucet account is refilled
+    /// to.
+    pub high_water: f64,
+    /// The ERC20 tokens refilled on EVM networks.
+    #[serde(default)]
+    pub tokens: Vec<TreasuryToken>,
+}
+
+/// The secret key of a hot wallet, which is never printed.
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct WalletKey(Secret);
+
+impl WalletKey {
+    /// Returns the key: a hex private key on EVM networks, or a secret URI
+    /// such as `//Alice` or `<mnemonic>//faucet` on Substrate networks.
+    pub fn secret(&self) -> Result<Zeroizing<String>, Error> {
+        self.0.expose()
+    }
+}
+
+impl std::fmt::Debug for WalletKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<hidden>")
+    }
+}
+
+/// The signature scheme of the hot wallets on a Substrate network.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyType {
+    #[default]
+    Sr25519,
+    Ecdsa,
+    Ed25519,
+}
+
+/// Where the keys of the hot wallets are kept when the faucet does not
+/// derive them from a secret itself.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignerConfig {
+    /// A signing daemon on the same host, holding the keys of the hot
+    /// wallets.
+    Remote(RemoteSignerConfig),
+}
+
+/// A signing daemon signing EVM transactions with `eth_signTransaction`,
+/// and the payloads of Substrate extrinsics with `substrate_method`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RemoteSignerConfig {
+    /// The `http://` URL of the JSON-RPC endpoint of the daemon, or the
+    /// `unix://` path of its socket.
+    pub url: String,
+    /// The hot wallets held by the daemon: EVM addresses, or SS58
+    /// addresses on Substrate networks.
+    pub accounts: Vec<String>,
+    /// The time to wait for the daemon to sign a transaction.
+    #[serde(default = "default_remote_signer_timeout")]
+    pub timeout: Duration,
+    /// The JSON-RPC method signing the payload of a Substrate extrinsic,
+    /// called with the SS58 address of the account in the generic format
+    /// and the hex payload, and returning the hex signature.
+    #[serde(default = "default_substrate_sign_method")]
+    pub substrate_method: String,
+}
+
+/// The secret and derivation of the hot wallets, set for a chain family
+/// with `evm_keys` and `substrate_keys`, or for a single network with
+/// `keys`. Unset fields fall back to the family, and the secret to the
+/// top-level `mnemonic`, `mnemonic_file` or `mnemonic_env`.
+///
+/// The secret is read from exactly one of `mnemonic`, `mnemonic_file`,
+/// `mnemonic_env` and `keystore`, unless a `signer` holds the keys.
+#[derive(Clone, Default, Deserialize)]
+pub struct KeyConfig {
+    /// The mnemonic the hot wallets are derived from.
+    #[serde(default)]
+    pub mnemonic: Option<Secret>,
+    /// A file holding the mnemonic.
+    #[serde(default)]
+    pub mnemonic_file: Option<PathBuf>,
+    /// The environment variable holding the mnemonic.
+    #[serde(default)]
+    pub mnemonic_env: Option<String>,
+    /// An ethers JSON keystore holding the key of the EVM hot wallet. Not
+    /// supported on Substrate networks.
+    #[serde(default)]
+    pub keystore: Option<PathBuf>,
+    /// A file holding the password of `keystore`.
+    #[serde(default)]
+    pub password_file: Option<PathBuf>,
+    /// The BIP-32 path the index of an EVM hot wallet is appended to, so it
+    /// must end with `/`. `m/44'/60'/0'/0/` if not set.
+    #[serde(default)]
+    pub derivation_path: Option<String>,
+    /// The junctions Substrate hot wallets are derived with before their
+    /// index, such as `//faucet` or `//faucet//1`.
+    #[serde(default)]
+    pub derivation: Option<String>,
+    /// The key type of Substrate hot wallets, `sr25519` if not set.
+    #[serde(default)]
+    pub key_type: Option<KeyType>,
+    /// The signer holding the keys of the hot wallets, instead of a
+    /// secret.
+    #[serde(default)]
+    pub signer: Option<SignerConfig>,
+}
+
+impl KeyConfig {
+    /// Fills the fields that are not set from `fallback`. The secret is
+    /// taken from `fallback` as a whole, so a network reading its own
+    /// secret from a file does not also inherit the mnemonic of its family.
+    pub fn or(&self, fallback: &KeyConfig) -> KeyConfig {
+        let secret = if self.has_secret() { self } else { fallback };
+        KeyConfig {
+            mnemonic: secret.mnemonic.clone(),
+            mnemonic_file: secret.mnemonic_file.clone(),
+            mnemonic_env: secret.mnemonic_env.clone(),
+            keystore: secret.keystore.clone(),
+            password_file: secret.password_file.clone(),
+            signer: secret.signer.clone(),
+            derivation_path: self
+                .derivation_path
+                .clone()
+                .or(fallback.derivation_path.clone()),
+            derivation: self.derivation.clone().or(fallback.derivation.clone()),
+            key_type: self.key_type.or(fallback.key_type),
+        }
+    }
+
+    /// Wipes the secret from memory, once the wallets are built, and
+    /// removes its `mnemonic_env` from the environment of the process.
+    pub fn wipe_secret(&self) {
+        if let Some(mnemonic) = &self.mnemonic {
+            mnemonic.wipe();
+        }
+        if let Some(var) = &self.mnemonic_env {
+            std::env::remove_var(var);
+        }
+    }
+
+    /// Returns `true` if any source of the secret, or a signer, is set.
+    pub fn has_secret(&self) -> bool {
+        self.mnemonic.is_some()
+            || self.mnemonic_file.is_some()
+            || self.mnemonic_env.is_some()
+            || self.keystore.is_some()
+            || self.signer.is_some()
+    }
+}
+
+impl std::fmt::Debug for KeyConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyConfig")
+            .field("mnemonic", &self.mnemonic)
+            .field("mnemonic_file", &self.mnemonic_file)
+            .field("mnemonic_env", &self.mnemonic_env)
+            .field("keystore", &self.keystore)
+            .field("password_file", &self.password_file)
+            .field("derivation_path", &self.derivation_path)
+            .field("derivation", &self.derivation)
+            .field("key_type", &self.key_type)
+            .field("signer", &self.signer)
+            .finish()
+    }
+}
+
+/// An ERC20 token the treasury refills the faucet account with.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TreasuryToken {
+    /// The symbol of the token, as configured in the `tokens` of the
+    /// network.
+    pub symbol: String,
+    /// The balance, in whole tokens, below which the faucet account is
+    /// refilled.
+    pub low_water: u64,
+    /// The balance, in whole tokens, the faucet account is refilled to.
+    pub high_water: u64,
+}
+
+/// How far a Substrate extrinsic has to get before its claim is paid.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubstrateFinality {
+    /// The extrinsic is included in a block.
+    #[default]
+    InBlock,
+    /// The block including the extrinsic is finalized.
+    Finalized,
+}
+
+/// A non-native asset the faucet drips on a Substrate network.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SubstrateAsset {
+    /// The symbol users select the asset by.
+    pub symbol: String,
+    /// The id of the asset, or the currency id for ORML `tokens`.
+    pub asset_id: u32,
+    /// The pallet the asset is transferred with.
+    #[serde(default)]
+    pub pallet: AssetPallet,
+    /// The decimals of the asset.
+    #[serde(default = "default_decimals")]
+    pub decimals: u8,
+    /// The amount of the asset sent per claim.
+    /// If not set, `token_amount` is used.
+    #[serde(default)]
+    pub amount: Option<u64>,
+    /// The time to wait between claims of this asset.
+    /// If not set, `time_to_wait_between_claims` is used.
+    #[serde(default)]
+    pub cooldown: Option<Duration>,
+    /// The balance, in whole units, below which claims of the asset are
+    /// refused. If not set, the amount of a claim is used.
+    #[serde(default)]
+    pub min_balance: Option<u64>,
+}
 
-pub enum Network {
-    Sepolia,
-    Goerli,
-    OptimismGoerli,
-    ArbitrumGoerli,
-    PolygonMumbai,
-    ScrollAlpha,
-    MoonbaseAlpha,
-    AvalancheFuji,
-    Hermes,
-    Athena,
-    Demeter,
-    TangleEVMTestnet,
-
-    // Substrate
-    Tangle,
-    TangleLocal,
+/// A network the faucet can pay out on, as configured in `Rocket.toml`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Network {
+    /// The name of the network, e.g. `athena` or `tangle`.
+    pub name: String,
+    /// The typed chain id of the network.
+    pub typed_chain_id: TypedChainId,
+    /// The RPC endpoints of the network.
+    /// If empty, the endpoints listed in `chainIdRpcs.json` are used.
+    #[serde(default)]
+    pub rpc_urls: Vec<String>,
+    /// The ERC20 token dispensed on EVM networks when no `tokens` are
+    /// configured. If not set, the address listed in
+    /// `chainIdFaucetToken.json` is used.
+    #[serde(default)]
+    pub token_address: Option<Address>,
+    /// The ERC20 tokens users can choose from on EVM networks.
+    /// The first one is sent when a claim selects no token.
+    #[serde(default)]
+    pub tokens: Vec<FaucetToken>,
+    /// The non-native assets users can choose from on Substrate networks.
+    #[serde(default)]
+    pub assets: Vec<SubstrateAsset>,
+    /// The decimals of the native token of the network.
+    #[serde(default = "default_decimals")]
+    pub decimals: u8,
+    /// The native balance, in whole tokens, below which claims of the native
+    /// token are refused. If not set, `native_token_amount` is used.
+    #[serde(default)]
+    pub min_native_balance: Option<f64>,
+    /// The native balance, in whole tokens, below which claims without the
+    /// native token are refused, as their fees could not be paid. If not
+    /// set, EVM hot wallets keep the fees of a token transfer at the current
+    /// gas price, and Substrate hot wallets keep none.
+    #[serde(default)]
+    pub min_fee_balance: Option<f64>,
+    /// The treasury refilling the faucet account, if any.
+    #[serde(default)]
+    pub treasury: Option<TreasuryConfig>,
+    /// The secret and derivation of the hot wallets of this network, over
+    /// those of its chain family.
+    #[serde(default)]
+    pub keys: KeyConfig,
+    /// The indices of the hot wallets derived from `mnemonic`, which claims
+    /// are spread across. Ignored if `wallet_keys` or a remote signer is
+    /// set.
+    #[serde(default = "default_wallet_indices")]
+    pub wallet_indices: Vec<u32>,
+    /// The keys of the hot wallets claims are spread across, instead of
+    /// wallets derived from `mnemonic`.
+    #[serde(default)]
+    pub wallet_keys: Vec<WalletKey>,
+    /// Whether the faucet serves this network.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// The number of transactions processed at once on this network.
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// The number of blocks an EVM transaction has to be buried under,
+    /// counting its own block, before its claim is paid.
+    #[serde(default = "default_confirmations")]
+    pub confirmations: u64,
+    /// The factor the estimated gas of EVM transactions is multiplied by.
+    /// If not set, `gas_limit_multiplier` is used.
+    #[serde(default)]
+    pub gas_limit_multiplier: Option<f64>,
+    /// The lowest gas limit EVM transactions are sent with.
+    #[serde(default)]
+    pub min_gas_limit: Option<u64>,
+    /// The highest gas limit EVM transactions are sent with.
+    #[serde(default)]
+    pub max_gas_limit: Option<u64>,
+    /// How EVM transactions are priced.
+    #[serde(default)]
+    pub fee_strategy: FeeStrategy,
+    /// How the fees of EVM transactions that are not mined are bumped.
+    #[serde(default)]
+    pub escalator: EscalatorConfig,
+    /// The highest gas price, or max fee per gas, EVM transactions are sent
+    /// with, in wei. Bumped fees are capped to it, and no transaction is
+    /// sent while the estimated fees are above it.
+    #[serde(default)]
+    pub max_fee_per_gas: Option<u64>,
+    /// What happens to claims while the fees are above `max_fee_per_gas`.
+    #[serde(default)]
+    pub over_max_fee: OverMaxFee,
+    /// The longest time a claim is held for when `over_max_fee` is `hold`,
+    /// before it is rejected.
+    #[serde(default = "default_max_fee_hold")]
+    pub max_fee_hold: Duration,
+    /// How far extrinsics have to get on Substrate networks.
+    #[serde(default)]
+    pub finality: SubstrateFinality,
+    /// The time to wait for the node to accept an extrinsic.
+    /// If not set, `tx_timeout` is used.
+    #[serde(default)]
+    pub submission_timeout: Option<Duration>,
+    /// The time to wait for an extrinsic to be included in a block, or for
+    /// an EVM transaction to be mined, and then to get its `confirmations`.
+    #[serde(default = "default_inclusion_timeout")]
+    pub inclusion_timeout: Duration,
+    /// The time to wait for the block of an extrinsic to be finalized.
+    #[serde(default = "default_finalization_timeout")]
+    pub finalization_timeout: Duration,
 }
 
 impl Network {
-    pub fn from_string(network: &str) -> Option<Self> {
-        match network {
-            "sepolia" => Some(Self::Sepolia),
-            "goerli" => Some(Self::Goerli),
-            "optimism-goerli" => Some(Self::OptimismGoerli),
-            "arbitrum-goerli" => Some(Self::ArbitrumGoerli),
-            "polygon-mumbai" => Some(Self::PolygonMumbai),
-            "scroll-alpha" => Some(Self::ScrollAlpha),
-            "moonbase-alpha" => Some(Self::MoonbaseAlpha),
-            "avalanche-fuji" => Some(Self::AvalancheFuji),
-            "hermes" => Some(Self::Hermes),
-            "athena" => Some(Self::Athena),
-            "demeter" => Some(Self::Demeter),
-            "tangle-evm-testnet" => Some(Self::TangleEVMTestnet),
-            "tangle" => Some(Self::Tangle),
-            "tangle-local" => Some(Self::TangleLocal),
+    /// Returns the EVM chain id if this is an EVM network.
+    pub fn evm_chain_id(&self) -> Option<u64> {
+        match self.typed_chain_id {
+            TypedChainId::Evm(chain_id) => Some(chain_id.into()),
             _ => None,
         }
     }
 
-    pub fn from_evm_chain_id(chain_id: u64) -> Option<Self> {
-        match chain_id {
-            11155111 => Some(Self::Sepolia),
-            5 => Some(Self::Goerli),
-            420 => Some(Self::OptimismGoerli),
-            421613 => Some(Self::ArbitrumGoerli),
-            80001 => Some(Self::PolygonMumbai),
-            534353 => Some(Self::ScrollAlpha),
-            1287 => Some(Self::MoonbaseAlpha),
-            43113 => Some(Self::AvalancheFuji),
-            3884533461 => Some(Self::Athena),
-            3884533462 => Some(Self::Hermes),
-            3884533463 => Some(Self::Demeter),
-            3799 => Some(Self::TangleEVMTestnet),
+    /// Returns the Substrate chain id if this is a Substrate network.
+    pub fn substrate_chain_id(&self) -> Option<u64> {
+        match self.typed_chain_id {
+            TypedChainId::Substrate(chain_id) => Some(chain_id.into()),
             _ => None,
         }
     }
 
-    pub fn to_evm_chain_id(&self) -> Option<u64> {
-        match self {
-            Self::Sepolia => Some(11155111),
-            Self::Goerli => Some(5),
-            Self::OptimismGoerli => Some(420),
-            Self::ArbitrumGoerli => Some(421613),
-            Self::PolygonMumbai => Some(80001),
-            Self::ScrollAlpha => Some(534353),
-            Self::MoonbaseAlpha => Some(1287),
-            Self::AvalancheFuji => Some(43113),
-            Self::Athena => Some(3884533461),
-            Self::Hermes => Some(3884533462),
-            Self::Demeter => Some(3884533463),
-            Self::TangleEVMTestnet => Some(3799),
-            _ => None,
+    /// Wipes the secrets of the hot wallets and treasury of the network
+    /// from memory, once they are built.
+    pub fn wipe_secrets(&self) {
+        self.keys.wipe_secret();
+        for key in &self.wallet_keys {
+            key.0.wipe();
+        }
+        if let Some(treasury) = &self.treasury {
+            treasury.keys.wipe_secret();
         }
     }
 
-    pub fn from_substrate_chain_id(chain_id: u64) -> Option<Self> {
-        match chain_id {
-            3799 => Some(Self::Tangle),
-            3800 => Some(Self::TangleLocal),
-            _ => None,
+    /// Returns the RPC endpoints of the network, falling back to
+    /// `chainIdRpcs.json` when none are configured.
+    pub fn rpc_urls(&self) -> Vec<String> {
+        if !self.rpc_urls.is_empty() {
+            return self.rpc_urls.clone();
+        }
+        match self.typed_chain_id {
+            TypedChainId::Substrate(chain_id) => {
+                get_substrate_rpc_urls(chain_id.into())
+            }
+            typed_chain_id => get_evm_rpc_urls(typed_chain_id.chain_id()),
         }
     }
 
-    pub fn to_substrate_chain_id(&self) -> Option<u64> {
-        match self {
-            Self::Tangle => Some(3799),
-            Self::TangleLocal => Some(3800),
-            _ => None,
+    /// Returns the ERC20 tokens of the network, falling back to the
+    /// `token_address` or `chainIdFaucetToken.json` token when none are
+    /// configured.
+    pub fn tokens(&self) -> Vec<FaucetToken> {
+        if !self.tokens.is_empty() {
+            return self.tokens.clone();
         }
+        self.token_address
+            .or_else(|| {
+                get_evm_token_address(self.typed_chain_id.chain_id())
+                    .map(Into::into)
+            })
+            .map(|address| FaucetToken {
+                symbol: DEFAULT_TOKEN_SYMBOL.to_string(),
+                address,
+                amount: None,
+                cooldown: None,
+                mode: DispenseMode::default(),
+                min_balance: None,
+            })
+            .into_iter()
+            .collect()
     }
 
-    pub fn from_typed_chain_id(typed_chain_id: TypedChainId) -> Option<Self> {
-        match typed_chain_id {
-            TypedChainId::Evm(chain_id) => {
-                Self::from_evm_chain_id(chain_id.into())
+    /// Looks up the tokens with the given symbols, or the first token of
+    /// the network if no symbols are given.
+    pub fn select_tokens(
+        &self,
+        symbols: &[String],
+    ) -> Result<Vec<FaucetToken>, Error> {
+        let tokens = self.tokens();
+        if symbols.is_empty() {
+            return tokens.into_iter().next().map(|token| vec![token]).ok_or(
+                Error::Custom(format!(
+                    "No faucet token configured on network {}",
+                    self.name
+                )),
+            );
+        }
+        let mut selected: Vec<FaucetToken> = Vec::with_capacity(symbols.len());
+        for symbol in symbols {
+            let token = tokens
+                .iter()
+                .find(|token| token.symbol == *symbol)
+                .ok_or(Error::Custom(format!(
+                    "Unknown token {symbol} on network {}",
+                    self.name
+                )))?;
+            if !selected.iter().any(|t| t.address == token.address) {
+                selected.push(token.clone());
             }
-            TypedChainId::Substrate(chain_id) => {
-                Self::from_substrate_chain_id(chain_id.into())
+        }
+        Ok(selected)
+    }
+
+    /// Looks up the Substrate assets with the given symbols.
+    pub fn select_assets(
+        &self,
+        symbols: &[String],
+    ) -> Result<Vec<SubstrateAsset>, Error> {
+        let mut selected: Vec<SubstrateAsset> =
+            Vec::with_capacity(symbols.len());
+        for symbol in symbols {
+            let asset = self
+                .assets
+                .iter()
+                .find(|asset| asset.symbol == *symbol)
+                .ok_or(Error::Custom(format!(
+                    "Unknown asset {symbol} on network {}",
+                    self.name
+                )))?;
+            if !selected.iter().any(|a| a.asset_id == asset.asset_id) {
+                selected.push(asset.clone());
+            }
+        }
+        Ok(selected)
+    }
+}
+
+/// The registry of all networks known to the faucet. It holds at least one
+/// enabled network, as a faucet without networks cannot pay out anything.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "Vec<Network>")]
+pub struct NetworkRegistry {
+    networks: Vec<Network>,
+}
+
+impl TryFrom<Vec<Network>> for NetworkRegistry {
+    type Error = String;
+
+    fn try_from(networks: Vec<Network>) -> Result<Self, Self::Error> {
+        if networks.is_empty() {
+            return Err("No networks are configured, add at least one \
+                        `[[default.networks]]` entry"
+                .to_string());
+        }
+        if !networks.iter().any(|network| network.enabled) {
+            return Err("All configured networks are disabled, enable at \
+                        least one of them"
+                .to_string());
+        }
+        // Networks are looked up by name and by chain id among the enabled
+        // ones, so two of them cannot share either.
+        let enabled: Vec<_> =
+            networks.iter().filter(|network| network.enabled).collect();
+        for (i, network) in enabled.iter().enumerate() {
+            for other in &enabled[..i] {
+                if other.name == network.name {
+                    return Err(format!(
+                        "Two enabled networks are named {}",
+                        network.name
+                    ));
+                }
+                if other.typed_chain_id == network.typed_chain_id {
+                    return Err(format!(
+                        "The enabled networks {} and {} have the same typed_chain_id {:?}",
+                        other.name, network.name, network.typed_chain_id
+                    ));
+                }
             }
-            _ => None,
         }
+        Ok(Self { networks })
     }
+}
 
-    pub fn to_typed_chain_id(&self) -> Option<TypedChainId> {
-        match self {
-            Self::Tangle => Some(TypedChainId::Substrate(3799)),
-            Self::TangleLocal => Some(TypedChainId::Substrate(3800)),
-            Self::ArbitrumGoerli => Some(TypedChainId::Evm(421613)),
-            Self::Athena => Some(TypedChainId::Evm(3884533461)),
-            Self::Demeter => Some(TypedChainId::Evm(3884533463)),
-            Self::TangleEVMTestnet => Some(TypedChainId::Evm(3799)),
-            Self::Goerli => Some(TypedChainId::Evm(5)),
-            Self::Hermes => Some(TypedChainId::Evm(3884533462)),
-            Self::MoonbaseAlpha => Some(TypedChainId::Evm(1287)),
-            Self::OptimismGoerli => Some(TypedChainId::Evm(420)),
-            Self::PolygonMumbai => Some(TypedChainId::Evm(80001)),
-            Self::ScrollAlpha => Some(TypedChainId::Evm(534353)),
-            Self::Sepolia => Some(TypedChainId::Evm(11155111)),
-            Self::AvalancheFuji => Some(TypedChainId::Evm(43113)),
+impl NetworkRegistry {
+    /// Wipes the secrets of every network from memory, once the wallets
+    /// are built.
+    pub fn wipe_secrets(&self) {
+        for network in &self.networks {
+            network.wipe_secrets();
         }
     }
+
+    /// Returns all enabled networks.
+    pub fn enabled(&self) -> impl Iterator<Item = &Network> {
+        self.networks.iter().filter(|network| network.enabled)
+    }
+
+    /// Returns all enabled EVM networks.
+    pub fn evm_networks(&self) -> impl Iterator<Item = &Network> {
+        self.enabled()
+            .filter(|network| network.evm_chain_id().is_some())
+    }
+
+    /// Returns all enabled Substrate networks.
+    pub fn substrate_networks(&self) -> impl Iterator<Item = &Network> {
+        self.enabled()
+            .filter(|network| network.substrate_chain_id().is_some())
+    }
+
+    /// Looks up an enabled network by its name.
+    pub fn from_string(&self, network: &str) -> Option<&Network> {
+        self.enabled().find(|n| n.name == network)
+    }
+
+    /// Looks up an enabled network by its typed chain id.
+    pub fn from_typed_chain_id(
+        &self,
+        typed_chain_id: TypedChainId,
+    ) -> Option<&Network> {
+        self.enabled().find(|n| n.typed_chain_id == typed_chain_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn network(name: &str, enabled: bool) -> serde_json::Value {
+        serde_json::json!({
+            "name": name,
+            "typed_chain_id": { "type": "Evm", "id": 3884533461u32 },
+            "enabled": enabled,
+        })
+    }
+
+    #[test]
+    fn rejects_no_networks() {
+        let res =
+            serde_json::from_value::<NetworkRegistry>(serde_json::json!([]));
+        assert!(res.unwrap_err().to_string().contains("No networks"));
+    }
+
+    #[test]
+    fn rejects_only_disabled_networks() {
+        let res =
+            serde_json::from_value::<NetworkRegistry>(serde_json::json!([
+                network("athena", false)
+            ]));
+        assert!(res.unwrap_err().to_string().contains("disabled"));
+    }
+
+    #[test]
+    fn rejects_duplicate_networks() {
+        let res =
+            serde_json::from_value::<NetworkRegistry>(serde_json::json!([
+                network("athena", true),
+                network("hermes", true)
+            ]));
+        let e = res.unwrap_err().to_string();
+        assert!(e.contains("athena and hermes"), "{e}");
+
+        let mut hermes = network("athena", true);
+        hermes["typed_chain_id"]["id"] = 5001.into();
+        let res =
+            serde_json::from_value::<NetworkRegistry>(serde_json::json!([
+                network("athena", true),
+                hermes
+            ]));
+        let e = res.unwrap_err().to_string();
+        assert!(e.contains("Two enabled networks are named athena"), "{e}");
+    }
+
+    #[test]
+    fn keeps_enabled_networks() {
+        let registry =
+            serde_json::from_value::<NetworkRegistry>(serde_json::json!([
+                network("athena", true),
+                network("hermes", false)
+            ]))
+            .unwrap();
+        let enabled: Vec<_> =
+            registry.enabled().map(|n| n.name.as_str()).collect();
+        assert_eq!(enabled, ["athena"]);
+        assert!(registry.from_string("hermes").is_none());
+    }
 }
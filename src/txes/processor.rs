@@ -1,84 +1,118 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use ethers::prelude::ContractCall;
-use ethers::providers::Middleware;
-use ethers::types::{Address, TransactionReceipt, TransactionRequest};
-use rocket::futures::TryFutureExt;
+use ethers::contract::EthError;
+use ethers::providers::{
+    JsonRpcError, Middleware, MiddlewareError, PendingTransaction,
+};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{
+    Address, BlockId, BlockNumber, Bytes, TransactionReceipt,
+    TransactionRequest, TxHash,
+};
 use rocket::tokio::{self, sync::oneshot};
-use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::error::SendError;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::{Mutex, Semaphore};
 use webb::evm::contract::protocol_solidity::erc20_preset_minter_pauser::ERC20PresetMinterPauserContract;
 use webb::evm::ethers;
 use webb::evm::ethers::types::U256;
+use webb::substrate::subxt::blocks::ExtrinsicEvents;
+use webb::substrate::subxt::tx::{TxInBlock, TxPayload, TxProgress, TxStatus};
 use webb::substrate::subxt::utils::{AccountId32, MultiAddress};
 use webb::substrate::subxt::{OnlineClient, PolkadotConfig};
 use webb::substrate::tangle_runtime::api as RuntimeApi;
+use webb_auth::model::ClaimAsset;
+use webb_proposals::TypedChainId;
 
+use crate::balances::Balances;
 use crate::error::Error;
+use crate::gas::{GasState, GasStates};
+use crate::signers::SubstrateSigner;
 
-use super::types::{Transaction, TxResult};
+use super::assets;
+use super::networks::{
+    DispenseMode, FeeStrategy, OverMaxFee, SubstrateFinality,
+};
+use super::types::{
+    AssetTransfer, FeeEstimate, FeeSettings, GasLimits, LateTransfer,
+    SubstrateTimeouts, SubstrateTransfer, TokenTransfer, Transaction,
+    TransferLeg, TxEvent, TxResult, WalletPool,
+};
+
+/// The number of times a transaction dropped from the mempool is re-sent.
+const MAX_EVM_REBROADCASTS: u32 = 3;
+
+/// The number of claims in a row a hot wallet has to fail before it is
+/// skipped.
+const MAX_WALLET_FAILURES: u32 = 3;
+
+/// How long a hot wallet that keeps failing is skipped.
+const UNHEALTHY_WALLET_COOLDOWN: Duration = Duration::from_secs(60);
 
 pub struct TransactionProcessingSystem {
     rx_receiver: UnboundedReceiver<Transaction>,
+    /// The number of transactions processed at once on each chain.
+    concurrency: HashMap<TypedChainId, usize>,
+    gas_states: GasStates,
+    /// The balances of the hot wallets, so drained ones are skipped.
+    balances: Balances,
 }
 
 impl TransactionProcessingSystem {
-    pub fn new(rx_receiver: UnboundedReceiver<Transaction>) -> Self {
-        Self { rx_receiver }
+    pub fn new(
+        rx_receiver: UnboundedReceiver<Transaction>,
+        concurrency: HashMap<TypedChainId, usize>,
+        gas_states: GasStates,
+        balances: Balances,
+    ) -> Self {
+        Self {
+            rx_receiver,
+            concurrency,
+            gas_states,
+            balances,
+        }
     }
 
+    /// Dispatches every transaction to the worker of its chain, so a slow
+    /// chain never holds up the others.
     pub fn run(mut self) {
         tokio::spawn(async move {
             println!("Transaction processing system started");
+            let mut workers: HashMap<
+                TypedChainId,
+                UnboundedSender<Transaction>,
+            > = HashMap::new();
             while let Some(transaction) = self.rx_receiver.recv().await {
-                match transaction {
-                    Transaction::Evm {
-                        provider,
-                        to,
-                        amount,
-                        native_token_amount,
-                        token_address,
-                        result_sender,
-                    } => {
-                        let res = handle_evm_tx(
-                            provider,
-                            to,
-                            amount,
-                            native_token_amount,
-                            token_address,
-                            result_sender,
-                        )
-                        .await;
-                        if let Err(e) = res {
-                            eprintln!("Error processing EVM transaction: {e}");
-                        }
-                    }
-                    Transaction::Substrate {
-                        api,
-                        to,
-                        native_token_amount,
-                        amount,
-                        asset_id,
-                        signer,
-                        result_sender,
-                        timeout,
-                    } => {
-                        let res = handle_substrate_tx(
-                            api,
-                            to,
-                            amount,
-                            native_token_amount,
-                            asset_id,
-                            signer,
-                            timeout,
-                            result_sender,
-                        )
-                        .await;
-                        if let Err(e) = res {
-                            eprintln!(
-                                "Error processing Substrate transaction: {e}"
-                            );
-                        }
-                    }
+                let typed_chain_id = transaction.typed_chain_id();
+                let spawn_worker = || {
+                    let concurrency = self
+                        .concurrency
+                        .get(&typed_chain_id)
+                        .copied()
+                        .unwrap_or(1);
+                    spawn_chain_worker(
+                        typed_chain_id,
+                        concurrency,
+                        self.gas_states.clone(),
+                        self.balances.clone(),
+                    )
+                };
+                let worker =
+                    workers.entry(typed_chain_id).or_insert_with(spawn_worker);
+                let Err(SendError(transaction)) = worker.send(transaction)
+                else {
+                    continue;
+                };
+                eprintln!(
+                    "Worker for chain {typed_chain_id:?} stopped, restarting it"
+                );
+                *worker = spawn_worker();
+                if let Err(SendError(transaction)) = worker.send(transaction) {
+                    transaction.fail(Error::Custom(format!(
+                        "The worker for chain {typed_chain_id:?} stopped, please try again"
+                    )));
                 }
             }
             eprintln!("Transaction processing system stopped");
@@ -86,118 +120,1163 @@ impl TransactionProcessingSystem {
     }
 }
 
-async fn handle_evm_tx<M: Middleware>(
+/// Spawns the worker processing the transactions of a single chain,
+/// with up to `concurrency` transactions in flight at once.
+///
+/// Every transaction is sent from the next usable hot wallet of the chain,
+/// and draws its nonce from the worker's [`EvmNonces`] or
+/// [`SubstrateNonces`] of that wallet.
+fn spawn_chain_worker(
+    typed_chain_id: TypedChainId,
+    concurrency: usize,
+    gas_states: GasStates,
+    balances: Balances,
+) -> UnboundedSender<Transaction> {
+    let (tx_sender, mut rx_receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        println!(
+            "Worker for chain {typed_chain_id:?} started with concurrency {concurrency}"
+        );
+        let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+        let evm_nonces = EvmNonces::default();
+        let nonces = SubstrateNonces::default();
+        let health = WalletHealth::default();
+        while let Some(transaction) = rx_receiver.recv().await {
+            let permit = permits
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("semaphore is never closed");
+            let evm_nonces = evm_nonces.clone();
+            let nonces = nonces.clone();
+            let gas_states = gas_states.clone();
+            let health = health.clone();
+            let balances = balances.clone();
+            tokio::spawn(async move {
+                process_transaction(
+                    transaction,
+                    evm_nonces,
+                    nonces,
+                    gas_states,
+                    health,
+                    balances,
+                )
+                .await;
+                drop(permit);
+            });
+        }
+        eprintln!("Worker for chain {typed_chain_id:?} stopped");
+    });
+    tx_sender
+}
+
+async fn process_transaction(
+    transaction: Transaction,
+    evm_nonces: EvmNonces,
+    nonces: SubstrateNonces,
+    gas_states: GasStates,
+    health: WalletHealth,
+    balances: Balances,
+) {
+    match transaction {
+        Transaction::Evm {
+            typed_chain_id,
+            wallets,
+            to,
+            native_token_amount,
+            tokens,
+            confirmations,
+            inclusion_timeout,
+            gas_limits,
+            fees,
+            event_sender,
+            result_sender,
+        } => {
+            let claimed = native_token_amount
+                .map(|_| ClaimAsset::Native)
+                .into_iter()
+                .chain(
+                    tokens.iter().map(|token| ClaimAsset::Token(token.address)),
+                )
+                .collect::<Vec<_>>();
+            let provider = match select_wallet(
+                &wallets,
+                typed_chain_id,
+                &claimed,
+                &health,
+                &balances,
+                |provider| format!("{:?}", provider.address()),
+            ) {
+                Ok(provider) => provider.clone(),
+                Err(e) => {
+                    let _ = result_sender.send(Err(e));
+                    return;
+                }
+            };
+            let res = handle_evm_tx(
+                typed_chain_id,
+                provider,
+                to,
+                native_token_amount,
+                tokens,
+                confirmations,
+                inclusion_timeout,
+                gas_limits,
+                fees,
+                evm_nonces,
+                gas_states,
+                health,
+                event_sender,
+                result_sender,
+            )
+            .await;
+            if let Err(e) = res {
+                eprintln!("Error processing EVM transaction: {e}");
+            }
+        }
+        Transaction::Substrate {
+            typed_chain_id,
+            api,
+            to,
+            native_token_amount,
+            assets,
+            signers,
+            decimals,
+            event_sender,
+            result_sender,
+            finality,
+            timeouts,
+        } => {
+            let claimed = native_token_amount
+                .map(|_| ClaimAsset::Native)
+                .into_iter()
+                .chain(
+                    assets
+                        .iter()
+                        .map(|asset| ClaimAsset::Asset(asset.asset_id)),
+                )
+                .collect::<Vec<_>>();
+            let signer = match select_wallet(
+                &signers,
+                typed_chain_id,
+                &claimed,
+                &health,
+                &balances,
+                |signer| signer.account_id().to_string(),
+            ) {
+                Ok(signer) => signer.clone(),
+                Err(e) => {
+                    let _ = result_sender.send(Err(e));
+                    return;
+                }
+            };
+            let res = handle_substrate_tx(
+                api,
+                to,
+                native_token_amount,
+                assets,
+                signer,
+                nonces,
+                health,
+                decimals,
+                finality,
+                timeouts,
+                event_sender,
+                result_sender,
+            )
+            .await;
+            if let Err(e) = res {
+                eprintln!("Error processing Substrate transaction: {e}");
+            }
+        }
+    }
+}
+
+/// Picks the hot wallet a claim of `claimed` is sent from, in round-robin
+/// order.
+///
+/// Healthy wallets that can pay out the claim are preferred. When every
+/// such wallet keeps failing, the unhealthy ones are tried anyway rather
+/// than refusing the claim.
+fn select_wallet<'a, T>(
+    wallets: &'a WalletPool<T>,
+    typed_chain_id: TypedChainId,
+    claimed: &[ClaimAsset],
+    health: &WalletHealth,
+    balances: &Balances,
+    wallet_id: impl Fn(&T) -> String,
+) -> Result<&'a T, Error> {
+    let funded = |wallet: &T| {
+        balances
+            .empty_asset(typed_chain_id, &wallet_id(wallet), claimed)
+            .is_none()
+    };
+    wallets
+        .next_usable(|wallet| {
+            funded(wallet) && health.is_healthy(&wallet_id(wallet))
+        })
+        .or_else(|| wallets.next_usable(funded))
+        .ok_or_else(|| Error::FaucetEmpty {
+            typed_chain_id,
+            asset: balances
+                .empty_asset(
+                    typed_chain_id,
+                    &wallet_id(wallets.primary()),
+                    claimed,
+                )
+                .unwrap_or(ClaimAsset::Native),
+        })
+}
+
+/// Tracks the claims the hot wallets of a chain failed in a row, so a
+/// wallet that keeps failing is skipped for a while.
+#[derive(Clone, Default)]
+struct WalletHealth {
+    failures: Arc<std::sync::Mutex<HashMap<String, (u32, Instant)>>>,
+}
+
+impl WalletHealth {
+    /// Returns `false` if the wallet failed its last claims and its
+    /// cooldown is not over yet.
+    fn is_healthy(&self, wallet: &str) -> bool {
+        let failures = self.failures.lock().expect("health lock poisoned");
+        match failures.get(wallet) {
+            Some((count, last)) if *count >= MAX_WALLET_FAILURES => {
+                last.elapsed() >= UNHEALTHY_WALLET_COOLDOWN
+            }
+            _ => true,
+        }
+    }
+
+    /// Records whether a claim sent from the wallet succeeded.
+    fn record(&self, wallet: String, succeeded: bool) {
+        let mut failures = self.failures.lock().expect("health lock poisoned");
+        if succeeded {
+            failures.remove(&wallet);
+            return;
+        }
+        let (count, last) = failures
+            .entry(wallet.clone())
+            .or_insert((0, Instant::now()));
+        *count += 1;
+        *last = Instant::now();
+        if *count == MAX_WALLET_FAILURES {
+            eprintln!(
+                "Hot wallet {wallet} failed {count} claims in a row, skipping it for {UNHEALTHY_WALLET_COOLDOWN:?}"
+            );
+        }
+    }
+}
+
+/// The nonces of the hot wallets of an EVM chain.
+pub(crate) type EvmNonces = Nonces<Address>;
+
+/// The nonces of the hot wallets of a Substrate chain.
+pub(crate) type SubstrateNonces = Nonces<AccountId32>;
+
+/// Hands out consecutive nonces for every hot wallet of a chain, so
+/// concurrent transactions never reuse a nonce.
+///
+/// Unlike `NonceManagerMiddleware`, the nonce of a wallet is resynced from
+/// the chain after errors, so a transaction that failed after its nonce was
+/// handed out does not hold up the later ones. The resync waits until no
+/// other nonce of the wallet is in flight, as the chain does not count the
+/// nonces of transactions that are still being sent, and would hand them
+/// out again.
+#[derive(Clone)]
+pub(crate) struct Nonces<K> {
+    wallets: Arc<Mutex<HashMap<K, WalletNonces>>>,
+}
+
+impl<K> Default for Nonces<K> {
+    fn default() -> Self {
+        Self {
+            wallets: Arc::default(),
+        }
+    }
+}
+
+/// The nonces handed out for a hot wallet.
+#[derive(Debug, Default)]
+struct WalletNonces {
+    /// The next nonce to hand out, fetched from the chain when unknown.
+    next: Option<u64>,
+    /// The nonces of the transactions that are still being sent.
+    in_flight: BTreeSet<u64>,
+    /// Whether the next nonce is resynced once none is in flight.
+    stale: bool,
+}
+
+impl WalletNonces {
+    /// Forgets the next nonce if it is stale and nothing is in flight.
+    fn settle(&mut self) {
+        if self.stale && self.in_flight.is_empty() {
+            self.next = None;
+            self.stale = false;
+        }
+    }
+}
+
+impl<K: Eq + std::hash::Hash> Nonces<K> {
+    /// Returns the next nonce of `wallet`, fetching it with `fetch` when
+    /// unknown. The nonce is in flight until it is marked as used or
+    /// failed.
+    async fn next<F, Fut>(&self, wallet: K, fetch: F) -> Result<u64, Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<u64, Error>>,
+    {
+        let mut wallets = self.wallets.lock().await;
+        let nonces = wallets.entry(wallet).or_default();
+        let nonce = match nonces.next {
+            Some(nonce) => nonce,
+            None => fetch().await?,
+        };
+        nonces.next = Some(nonce + 1);
+        nonces.in_flight.insert(nonce);
+        Ok(nonce)
+    }
+
+    /// Marks a nonce of `wallet` as used by a transaction that was sent.
+    async fn used(&self, wallet: &K, nonce: u64) {
+        if let Some(nonces) = self.wallets.lock().await.get_mut(wallet) {
+            nonces.in_flight.remove(&nonce);
+            nonces.settle();
+        }
+    }
+
+    /// Marks a nonce of `wallet` as failed. It may never have been used, so
+    /// the wallet is resynced before the nonce leaves a gap holding up
+    /// later transactions.
+    async fn failed(&self, wallet: &K, nonce: u64) {
+        if let Some(nonces) = self.wallets.lock().await.get_mut(wallet) {
+            nonces.in_flight.remove(&nonce);
+            nonces.stale = true;
+            nonces.settle();
+        }
+    }
+
+    /// Resyncs the next nonce of `wallet` from the chain, once none is in
+    /// flight.
+    async fn resync(&self, wallet: &K) {
+        if let Some(nonces) = self.wallets.lock().await.get_mut(wallet) {
+            nonces.stale = true;
+            nonces.settle();
+        }
+    }
+
+    /// Returns `true` if a nonce of `wallet` other than `nonce` is in
+    /// flight.
+    async fn others_in_flight(&self, wallet: &K, nonce: u64) -> bool {
+        self.wallets.lock().await.get(wallet).is_some_and(|nonces| {
+            nonces.in_flight.iter().any(|in_flight| *in_flight != nonce)
+        })
+    }
+
+    /// Forgets every nonce of `wallet`, after the transactions its nonces
+    /// in flight were handed out for were abandoned, so that the next one
+    /// is fetched from the chain.
+    pub(crate) async fn reset(&self, wallet: &K) {
+        self.wallets.lock().await.remove(wallet);
+    }
+}
+
+/// Returns the `eth_getTransactionCount` of an account including its
+/// pending transactions.
+pub(crate) async fn pending_nonce<M: Middleware>(
+    provider: &M,
+    from: Address,
+) -> Result<U256, Error> {
+    provider
+        .get_transaction_count(from, Some(BlockNumber::Pending.into()))
+        .await
+        .map_err(|e| Error::Custom(format!("Failed to fetch nonce: {e}")))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_evm_tx<M: Middleware + Clone + 'static>(
+    typed_chain_id: TypedChainId,
     provider: M,
     to: Address,
-    amount: U256,
-    native_token_amount: U256,
-    token_address: Option<Address>,
+    native_token_amount: Option<U256>,
+    tokens: Vec<TokenTransfer>,
+    confirmations: u64,
+    inclusion_timeout: Duration,
+    gas_limits: GasLimits,
+    fees: FeeSettings,
+    nonces: EvmNonces,
+    gas_states: GasStates,
+    health: WalletHealth,
+    event_sender: mpsc::UnboundedSender<TxEvent>,
     result_sender: oneshot::Sender<Result<TxResult, Error>>,
-) -> Result<TransactionReceipt, Error> {
-    if let Some(token_address) = token_address {
-        handle_evm_token_tx(provider, to, amount, token_address, result_sender)
+) -> Result<(), Error> {
+    let wallet = provider.default_sender();
+    let sender = EvmSender {
+        typed_chain_id,
+        provider,
+        confirmations,
+        inclusion_timeout,
+        gas_limits,
+        fees,
+        nonces,
+        gas_states,
+        event_sender,
+    };
+    let res = match (native_token_amount, tokens.as_slice()) {
+        (Some(amount), []) => handle_evm_native_tx(&sender, to, amount)
             .await
-    } else {
-        // Only send native token if no token address is provided
-        handle_evm_native_tx(provider, to, native_token_amount, result_sender)
+            .map(TxResult::Evm),
+        (None, [token]) => handle_evm_token_tx(&sender, to, token)
             .await
+            .map(TxResult::Evm),
+        _ => {
+            handle_evm_combined_tx(&sender, to, native_token_amount, tokens)
+                .await
+        }
+    };
+    if let Err(e) = &res {
+        eprintln!("Error sending EVM transaction: {e}");
+    }
+    if let Some(wallet) = wallet {
+        health.record(format!("{wallet:?}"), res.is_ok());
     }
+    result_sender.send(res).map_err(|e| {
+        Error::Custom(format!("Failed to send transaction result: {:?}", e))
+    })
 }
 
-async fn handle_evm_native_tx<M: Middleware>(
-    provider: M,
+/// Sends the native token for gas first and then every ERC20 token. A
+/// failed transfer does not prevent the others from being sent, so the
+/// claim only fails if all of its transfers fail.
+async fn handle_evm_combined_tx<M: Middleware + Clone + 'static>(
+    sender: &EvmSender<M>,
     to: Address,
-    amount: U256,
-    result_sender: oneshot::Sender<Result<TxResult, Error>>,
-) -> Result<TransactionReceipt, Error> {
-    // Craft the tx
-    let has_signer = provider.is_signer().await;
-    assert!(has_signer, "Provider must have signer");
-    let tx = TransactionRequest::new()
-        .to(to)
-        .value(amount)
-        .gas(U256::from(22000u64)); // TODO: Make this configurable
-
-    let tx_receipt = provider
-        .send_transaction(tx, None)
-        .await
-        .map_err(|e| Error::Custom(e.to_string()))?
-        .await
-        .map_err(|e| Error::Custom(e.to_string()))?;
-    match tx_receipt {
-        Some(receipt) => {
-            result_sender
-                .send(Ok(TxResult::Evm(receipt.clone())))
+    native_token_amount: Option<U256>,
+    tokens: Vec<TokenTransfer>,
+) -> Result<TxResult, Error> {
+    let mut results = Vec::new();
+    if let Some(amount) = native_token_amount {
+        let res = handle_evm_native_tx(sender, to, amount).await;
+        results.push((ClaimAsset::Native, res));
+    }
+    for token in tokens {
+        let res = handle_evm_token_tx(sender, to, &token).await;
+        results.push((ClaimAsset::Token(token.address), res));
+    }
+
+    let mut native = None;
+    let mut token_legs = BTreeMap::new();
+    for (asset, leg) in combine_legs(results)? {
+        match asset {
+            ClaimAsset::Token(address) => {
+                token_legs.insert(address, leg);
+            }
+            _ => native = Some(leg),
+        }
+    }
+    Ok(TxResult::EvmCombined {
+        native,
+        tokens: token_legs,
+    })
+}
+
+/// Turns the results of the transfers of a combined claim into its legs.
+///
+/// Fails if none of the transfers succeeded, so nothing was paid out: with
+/// the error of a transfer that is still tracked if there is one, so that
+/// the claim stays reserved until it is settled, or else with an error
+/// listing every failed transfer.
+fn combine_legs<R>(
+    results: Vec<(ClaimAsset, Result<R, Error>)>,
+) -> Result<Vec<(ClaimAsset, TransferLeg<R>)>, Error> {
+    if results.iter().any(|(_, res)| res.is_ok()) {
+        return Ok(results
+            .into_iter()
+            .map(|(asset, res)| (asset, res.into()))
+            .collect());
+    }
+    let mut failures = Vec::new();
+    for (asset, e) in results
+        .into_iter()
+        .filter_map(|(asset, res)| Some((asset, res.err()?)))
+    {
+        if e.is_still_tracked() {
+            return Err(e);
+        }
+        failures.push(format!("Transfer of {asset} failed: {e}"));
+    }
+    Err(Error::Custom(if failures.is_empty() {
+        "Nothing to transfer".to_string()
+    } else {
+        failures.join("; ")
+    }))
+}
+
+/// Everything needed to send transactions on an EVM chain.
+pub(crate) struct EvmSender<M> {
+    pub(crate) typed_chain_id: TypedChainId,
+    pub(crate) provider: M,
+    /// The number of blocks a transaction has to be buried under, counting
+    /// its own block, before it is considered final.
+    pub(crate) confirmations: u64,
+    /// The time to wait for a transaction to be mined, and then for its
+    /// confirmations.
+    pub(crate) inclusion_timeout: Duration,
+    pub(crate) gas_limits: GasLimits,
+    pub(crate) fees: FeeSettings,
+    pub(crate) nonces: EvmNonces,
+    /// Where the fees of the chain are published whenever they are
+    /// estimated.
+    pub(crate) gas_states: GasStates,
+    pub(crate) event_sender: mpsc::UnboundedSender<TxEvent>,
+}
+
+impl<M: Middleware + Clone + 'static> EvmSender<M> {
+    /// Sends a transaction paying out `asset` and waits for it to be
+    /// confirmed.
+    ///
+    /// A transaction that is not mined, or still short of its
+    /// confirmations, after the inclusion timeout keeps being tracked in the
+    /// background, and is reported as a [`TxEvent::LateInclusion`] if it
+    /// gets them after all.
+    pub(crate) async fn send(
+        &self,
+        tx: TypedTransaction,
+        asset: ClaimAsset,
+    ) -> Result<TransactionReceipt, Error> {
+        let has_signer = self.provider.is_signer().await;
+        assert!(has_signer, "Provider must have signer");
+        let mut tx = self.fees.typed(tx);
+        if tx.from().is_none() {
+            if let Some(from) = self.provider.default_sender() {
+                tx.set_from(from);
+            }
+        }
+        let estimated_gas =
+            self.provider.estimate_gas(&tx, None).await.map_err(|e| {
+                Error::Custom(format!("Failed to estimate gas: {e}"))
+            })?;
+        tx.set_gas(self.gas_limits.gas_limit(estimated_gas));
+        let fees = self.current_fees().await?;
+        set_fees(&mut tx, fees);
+        let from = *tx
+            .from()
+            .ok_or(Error::Custom("Provider must have signer".to_string()))?;
+        let nonce = self
+            .nonces
+            .next(from, || async move {
+                Ok(pending_nonce(&self.provider, from).await?.low_u64())
+            })
+            .await?;
+        tx.set_nonce(nonce);
+        // Fill the transaction up front, so it can be re-sent with the same
+        // nonce if it is dropped by a reorg or not mined in time.
+        if let Err(e) = self.provider.fill_transaction(&mut tx, None).await {
+            self.nonces.failed(&from, nonce).await;
+            return Err(Error::Custom(format!("Failed to fill tx: {e}")));
+        }
+        let mut sent = Vec::new();
+        let broadcast = tokio::time::timeout(
+            self.inclusion_timeout,
+            self.broadcast(&mut tx, asset, &mut sent),
+        )
+        .await;
+        let receipt = match broadcast {
+            Ok(Ok(receipt)) => receipt,
+            Ok(Err(e)) => {
+                self.nonces.failed(&from, nonce).await;
+                // A transaction that was sent may still be mined, unless it
+                // was mined and reverted, so its claim is left reserved.
+                return match sent.last().copied() {
+                    Some(tx_hash)
+                        if !matches!(e, Error::TransactionReverted { .. }) =>
+                    {
+                        eprintln!("Lost track of transaction {tx_hash:?}: {e}");
+                        self.track_late_confirmations(sent, asset);
+                        Err(Error::InclusionTimeout { tx_hash })
+                    }
+                    _ => Err(e),
+                };
+            }
+            Err(_) => {
+                let Some(tx_hash) = sent.last().copied() else {
+                    self.nonces.failed(&from, nonce).await;
+                    return Err(Error::SubmissionTimeout);
+                };
+                // The nonce stays taken by the transactions in the mempool.
+                self.nonces.used(&from, nonce).await;
+                self.track_late_confirmations(sent, asset);
+                return Err(Error::InclusionTimeout { tx_hash });
+            }
+        };
+        self.nonces.used(&from, nonce).await;
+        let confirmed = tokio::time::timeout(
+            self.inclusion_timeout,
+            self.wait_for_confirmations(&mut tx, asset, receipt.clone()),
+        )
+        .await;
+        match confirmed {
+            Ok(res) => res,
+            Err(_) => {
+                self.track_late_confirmations(
+                    vec![receipt.transaction_hash],
+                    asset,
+                );
+                Err(Error::ConfirmationTimeout {
+                    tx_hash: receipt.transaction_hash,
+                    confirmations: self.confirmations,
+                })
+            }
+        }
+    }
+
+    /// Keeps following the transactions sent with the nonce of a
+    /// transaction that timed out, and reports whichever of them is mined
+    /// and gets its confirmations.
+    fn track_late_confirmations(&self, sent: Vec<TxHash>, asset: ClaimAsset) {
+        let provider = self.provider.clone();
+        let confirmations = self.confirmations;
+        let event_sender = self.event_sender.clone();
+        let tx_hash = *sent.last().expect("a transaction was sent");
+        tokio::spawn(async move {
+            let tracking = async {
+                let interval = provider.provider().get_interval();
+                let mined = loop {
+                    if let Some(receipt) =
+                        mined_receipt(&provider, &sent).await?
+                    {
+                        break receipt.transaction_hash;
+                    }
+                    tokio::time::sleep(interval).await;
+                };
+                wait_for_depth(&provider, mined, confirmations).await
+            };
+            match tokio::time::timeout(LATE_INCLUSION_TRACKING, tracking).await
+            {
+                Ok(Ok(receipt)) => {
+                    println!("Transaction {tx_hash:?} was confirmed late");
+                    let _ = event_sender.send(TxEvent::LateInclusion {
+                        asset,
+                        transfer: LateTransfer::Evm(receipt),
+                    });
+                }
+                Ok(Err(e)) => {
+                    eprintln!("Late transaction {tx_hash:?} failed: {e}");
+                }
+                Err(_) => {
+                    eprintln!("Stopped tracking transaction {tx_hash:?}");
+                }
+            }
+        });
+    }
+
+    /// Returns the fees to send a transaction with.
+    ///
+    /// While the fees of the chain are above its `max_fee_per_gas`, the
+    /// transaction is either rejected, or held until they drop for up to
+    /// `max_fee_hold`.
+    async fn current_fees(&self) -> Result<FeeEstimate, Error> {
+        let held_since = Instant::now();
+        loop {
+            let fees =
+                estimate_fees(&self.provider, self.fees.strategy).await?;
+            let state = GasState::new(fees, self.fees.max_fee_per_gas);
+            self.gas_states.update(self.typed_chain_id, state);
+            let max_fee_per_gas = match self.fees.max_fee_per_gas {
+                Some(max) if fees.fee_per_gas > max => max,
+                _ => return Ok(fees),
+            };
+            let error = Error::FeeTooHigh {
+                fee_per_gas: fees.fee_per_gas,
+                max_fee_per_gas,
+            };
+            if self.fees.over_max_fee == OverMaxFee::Reject
+                || held_since.elapsed() >= self.fees.max_fee_hold
+            {
+                return Err(error);
+            }
+            eprintln!(
+                "Holding transaction on chain {:?}: {error}",
+                self.typed_chain_id
+            );
+            tokio::time::sleep(self.provider.provider().get_interval()).await;
+        }
+    }
+
+    /// Broadcasts a filled transaction and waits for its receipt.
+    ///
+    /// A transaction that is not mined within the escalator period is
+    /// replaced by one with the same nonce and bumped fees, until its fees
+    /// reach `max_fee_per_gas`. Whichever of the replacements is mined is
+    /// the one returned. A transaction dropped from the mempool is re-sent,
+    /// and one still not mined after `stuck_after` has the nonce gaps
+    /// holding it up filled.
+    ///
+    /// It waits until one of the transactions is mined, so it is bounded by
+    /// the inclusion timeout in [`Self::send`].
+    ///
+    /// Every transaction sent is added to `sent` and reported as a
+    /// [`TxEvent::Submitted`], so that its claim can be settled from it
+    /// after a timeout or a restart. That includes a transaction whose
+    /// sending failed before the node answered, as it may have been
+    /// received.
+    async fn broadcast(
+        &self,
+        tx: &mut TypedTransaction,
+        asset: ClaimAsset,
+        sent: &mut Vec<TxHash>,
+    ) -> Result<TransactionReceipt, Error> {
+        let first_sent_at = Instant::now();
+        let mut drops = 0;
+        let mut gaps_checked = false;
+        let from = tx.from().copied().unwrap_or_default();
+        loop {
+            // Sign the transaction here rather than in the provider, so its
+            // hash is known even if sending it fails.
+            let signature =
+                self.provider.sign_transaction(tx, from).await.map_err(
+                    |e| Error::Custom(format!("Failed to sign tx: {e}")),
+                )?;
+            let tx_hash = tx.hash(&signature);
+            let raw = tx.rlp_signed(&signature);
+            let accepted = match self.provider.send_raw_transaction(raw).await {
+                Ok(_) => true,
+                // The node could not be reached, and may have received the
+                // transaction, so it is followed like one that was sent.
+                Err(e) if e.as_error_response().is_none() => {
+                    eprintln!(
+                        "Failed to send tx {tx_hash:?}, following it in case it was sent: {e}"
+                    );
+                    true
+                }
+                Err(e) if sent.is_empty() => {
+                    return Err(Error::Custom(format!(
+                        "Failed to send tx: {e}"
+                    )));
+                }
+                // A replacement is rejected once an earlier one is mined, or
+                // if its fees are not raised enough. Keep waiting for the
+                // earlier ones.
+                Err(e) => {
+                    eprintln!("Failed to replace tx: {e}");
+                    false
+                }
+            };
+            if accepted && !sent.contains(&tx_hash) {
+                sent.push(tx_hash);
+                let _ = self.event_sender.send(TxEvent::Submitted {
+                    asset,
+                    tx_hash,
+                    from: format!("{from:?}"),
+                    nonce: tx.nonce().copied().unwrap_or_default().low_u64(),
+                });
+            }
+            let tx_hash = *sent.last().expect("a transaction was sent");
+            loop {
+                let pending_tx =
+                    PendingTransaction::new(tx_hash, self.provider.provider());
+                let res =
+                    tokio::time::timeout(self.fees.escalator.every, pending_tx)
+                        .await;
+                let timed_out = res.is_err();
+                // The latest replacement is dropped if an earlier one is
+                // mined, so every one of them is checked.
+                let receipt = match res {
+                    Ok(Ok(Some(receipt))) => Some(receipt),
+                    Ok(Ok(None)) | Err(_) => {
+                        mined_receipt(&self.provider, sent).await?
+                    }
+                    Ok(Err(e)) => {
+                        return Err(Error::Custom(format!(
+                            "Failed to await tx: {e}"
+                        )))
+                    }
+                };
+                if receipt.is_some() {
+                    return check_receipt(
+                        &self.provider,
+                        receipt,
+                        &self.event_sender,
+                    )
+                    .await;
+                }
+                if !timed_out {
+                    drops += 1;
+                    if drops > MAX_EVM_REBROADCASTS {
+                        return Err(Error::Custom(format!(
+                            "Transaction {tx_hash:?} was dropped from the mempool"
+                        )));
+                    }
+                    eprintln!(
+                        "Transaction {tx_hash:?} was dropped from the mempool, re-sending it"
+                    );
+                    break;
+                }
+                if !gaps_checked
+                    && first_sent_at.elapsed()
+                        >= self.fees.escalator.stuck_after
+                {
+                    match self.fill_nonce_gaps(tx).await {
+                        Ok(checked) => gaps_checked = checked,
+                        Err(e) => {
+                            gaps_checked = true;
+                            eprintln!("Failed to fill nonce gaps: {e}");
+                        }
+                    }
+                }
+                if self.fees.bump(tx) {
+                    eprintln!(
+                        "Transaction {tx_hash:?} was not mined in time, replacing it with higher fees"
+                    );
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Fills the nonces below a stuck transaction that no transaction is
+    /// pending with, which hold it up. They are left behind by transactions
+    /// that failed after their nonce was handed out.
+    ///
+    /// Each gap is filled with an empty transfer to the faucet account,
+    /// priced like the stuck transaction. The nonces are resynced
+    /// afterwards.
+    ///
+    /// Nothing is filled while another nonce of the wallet is in flight, as
+    /// its transaction may not have reached the mempool yet, and would be
+    /// replaced by the filler. Returns `false` if the gaps were not checked
+    /// for that reason.
+    async fn fill_nonce_gaps(
+        &self,
+        tx: &TypedTransaction,
+    ) -> Result<bool, Error> {
+        let (Some(from), Some(nonce)) =
+            (tx.from().copied(), tx.nonce().copied())
+        else {
+            return Ok(true);
+        };
+        if self.nonces.others_in_flight(&from, nonce.low_u64()).await {
+            return Ok(false);
+        }
+        // Every nonce below the pending transaction count is mined or
+        // pending, so the first gap starts there.
+        let mut gap = pending_nonce(&self.provider, from).await?;
+        while gap < nonce {
+            eprintln!(
+                "Nonce {gap} of {from:?} is missing on chain {:?}, filling it",
+                self.typed_chain_id
+            );
+            let mut filler = tx.clone();
+            filler
+                .set_to(from)
+                .set_value(U256::zero())
+                .set_data(Bytes::default())
+                .set_gas(21_000u64)
+                .set_nonce(gap);
+            self.provider
+                .send_transaction(filler, None)
+                .await
+                .map_err(|e| Error::Custom(e.to_string()))?;
+            let next = pending_nonce(&self.provider, from).await?;
+            if next <= gap {
+                break;
+            }
+            gap = next;
+        }
+        self.nonces.resync(&from).await;
+        Ok(true)
+    }
+
+    /// Waits until the block including a transaction is `confirmations`
+    /// blocks deep, re-checking its receipt on every poll.
+    ///
+    /// A transaction that moved to another block is followed there, and
+    /// one that was dropped by a reorg is re-sent with the same nonce.
+    async fn wait_for_confirmations(
+        &self,
+        tx: &mut TypedTransaction,
+        asset: ClaimAsset,
+        mut receipt: TransactionReceipt,
+    ) -> Result<TransactionReceipt, Error> {
+        if self.confirmations <= 1 {
+            return Ok(receipt);
+        }
+        let interval = self.provider.provider().get_interval();
+        loop {
+            tokio::time::sleep(interval).await;
+            let tx_hash = receipt.transaction_hash;
+            let current_receipt = self
+                .provider
+                .get_transaction_receipt(tx_hash)
+                .await
+                .map_err(|e| Error::Custom(e.to_string()))?;
+            match current_receipt {
+                Some(current) if current.block_hash == receipt.block_hash => {
+                    let current_block = self
+                        .provider
+                        .get_block_number()
+                        .await
+                        .map_err(|e| Error::Custom(e.to_string()))?;
+                    let included_at = receipt.block_number.unwrap_or_default();
+                    let depth =
+                        (current_block + 1u64).saturating_sub(included_at);
+                    if depth.as_u64() >= self.confirmations {
+                        return Ok(current);
+                    }
+                }
+                Some(current) => {
+                    eprintln!(
+                        "Transaction {tx_hash:?} was moved to block {:?} by a reorg",
+                        current.block_hash
+                    );
+                    receipt = check_receipt(
+                        &self.provider,
+                        Some(current),
+                        &self.event_sender,
+                    )
+                    .await?;
+                }
+                None => {
+                    eprintln!(
+                        "Transaction {tx_hash:?} was dropped by a reorg, re-sending it"
+                    );
+                    receipt = self.rebroadcast(tx, asset, tx_hash).await?;
+                }
+            }
+        }
+    }
+
+    /// Re-sends a transaction dropped by a reorg, or waits for it again if
+    /// the node still holds it in its mempool.
+    async fn rebroadcast(
+        &self,
+        tx: &mut TypedTransaction,
+        asset: ClaimAsset,
+        tx_hash: TxHash,
+    ) -> Result<TransactionReceipt, Error> {
+        let pending = self
+            .provider
+            .get_transaction(tx_hash)
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?;
+        if pending.is_none() {
+            return self.broadcast(tx, asset, &mut Vec::new()).await;
+        }
+        let tx_receipt =
+            PendingTransaction::new(tx_hash, self.provider.provider())
+                .await
                 .map_err(|e| {
-                    Error::Custom(format!("Failed to send receipt: {:?}", e))
+                    Error::Custom(format!("Failed to await tx: {e}"))
                 })?;
-            Ok(receipt)
+        check_receipt(&self.provider, tx_receipt, &self.event_sender).await
+    }
+}
+
+/// Returns the fees the chain currently asks for, following its fee
+/// strategy.
+pub async fn estimate_fees<M: Middleware>(
+    provider: &M,
+    strategy: FeeStrategy,
+) -> Result<FeeEstimate, Error> {
+    match strategy {
+        FeeStrategy::Eip1559 => {
+            let (max_fee, priority_fee) =
+                provider.estimate_eip1559_fees(None).await.map_err(|e| {
+                    Error::Custom(format!("Failed to estimate fees: {e}"))
+                })?;
+            Ok(FeeEstimate {
+                fee_per_gas: max_fee,
+                priority_fee_per_gas: Some(priority_fee.min(max_fee)),
+            })
+        }
+        FeeStrategy::Legacy => {
+            let gas_price = provider.get_gas_price().await.map_err(|e| {
+                Error::Custom(format!("Failed to fetch gas price: {e}"))
+            })?;
+            Ok(FeeEstimate {
+                fee_per_gas: gas_price,
+                priority_fee_per_gas: None,
+            })
+        }
+        FeeStrategy::Fixed { gas_price } => Ok(FeeEstimate {
+            fee_per_gas: gas_price.into(),
+            priority_fee_per_gas: None,
+        }),
+    }
+}
+
+/// Prices a transaction with the given fees.
+fn set_fees(tx: &mut TypedTransaction, fees: FeeEstimate) {
+    match tx.as_eip1559_mut() {
+        Some(inner) => {
+            inner.max_fee_per_gas = Some(fees.fee_per_gas);
+            inner.max_priority_fee_per_gas = fees.priority_fee_per_gas;
         }
         None => {
-            result_sender
-                .send(Err(Error::Custom(
-                    "Failed to send transaction".to_string(),
-                )))
-                .map_err(|e| {
-                    Error::Custom(format!(
-                        "Failed to send transaction: {:?}",
-                        e
-                    ))
-                })?;
-            Err(Error::Custom("Failed to send transaction".to_string()))
+            tx.set_gas_price(fees.fee_per_gas);
         }
     }
 }
 
-async fn handle_evm_token_tx<M: Middleware>(
-    provider: M,
+pub(crate) async fn handle_evm_native_tx<M: Middleware + Clone + 'static>(
+    sender: &EvmSender<M>,
     to: Address,
     amount: U256,
-    token_address: Address,
-    result_sender: oneshot::Sender<Result<TxResult, Error>>,
 ) -> Result<TransactionReceipt, Error> {
-    let has_signer = provider.is_signer().await;
-    assert!(has_signer, "Provider must have signer");
-    let contract =
-        ERC20PresetMinterPauserContract::new(token_address, Arc::new(provider));
+    // Craft the tx
+    let tx = TransactionRequest::new().to(to).value(amount);
+    sender.send(tx.into(), ClaimAsset::Native).await
+}
+
+async fn handle_evm_token_tx<M: Middleware + Clone + 'static>(
+    sender: &EvmSender<M>,
+    to: Address,
+    token: &TokenTransfer,
+) -> Result<TransactionReceipt, Error> {
+    let contract = ERC20PresetMinterPauserContract::new(
+        token.address,
+        Arc::new(sender.provider.clone()),
+    );
 
     // Fetch the decimals used by the contract so we can compute the decimal amount to send.
     let decimals = contract.decimals().call().await.map_err(|e| {
         Error::Custom(format!("Failed to fetch decimals: {:?}", e))
     })?;
-    let decimal_amount = amount * U256::exp10(decimals as usize);
+    let decimal_amount = token.amount * U256::exp10(decimals as usize);
 
-    // Transfer the desired amount of tokens to the `to_address`
-    let tx: ContractCall<M, _> = contract.transfer(to, decimal_amount).legacy();
-    let pending_tx = tx
-        .send()
-        .await
-        .map_err(|e| Error::Custom(format!("Failed to send tx: {:?}", e)))?;
-    match pending_tx
-        .await
-        .map_err(|e| Error::Custom(format!("Failed to await tx: {:?}", e)))?
-    {
-        Some(receipt) => {
-            result_sender
-                .send(Ok(TxResult::Evm(receipt.clone())))
-                .map_err(|e| {
-                    Error::Custom(format!("Failed to send receipt: {:?}", e))
-                })?;
-            Ok(receipt)
+    // Transfer or mint the desired amount of tokens to the `to_address`
+    let tx: TypedTransaction = match token.mode {
+        DispenseMode::Transfer => contract.transfer(to, decimal_amount).tx,
+        DispenseMode::Mint => contract.mint(to, decimal_amount).tx,
+    };
+    sender.send(tx, ClaimAsset::Token(token.address)).await
+}
+
+/// Returns the receipt of whichever of the sent transactions was mined.
+async fn mined_receipt<M: Middleware>(
+    provider: &M,
+    sent: &[TxHash],
+) -> Result<Option<TransactionReceipt>, Error> {
+    for tx_hash in sent {
+        let receipt = provider
+            .get_transaction_receipt(*tx_hash)
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?;
+        if receipt.is_some() {
+            return Ok(receipt);
         }
-        None => {
-            result_sender
-                .send(Err(Error::Custom(
-                    "Failed to send transaction".to_string(),
-                )))
-                .map_err(|e| {
-                    Error::Custom(format!(
-                        "Failed to send transaction: {:?}",
-                        e
-                    ))
-                })?;
-            Err(Error::Custom("Failed to send transaction".to_string()))
+    }
+    Ok(None)
+}
+
+/// Waits until a mined transaction is `confirmations` blocks deep, failing
+/// if a reorg drops or reverts it.
+async fn wait_for_depth<M: Middleware>(
+    provider: &M,
+    tx_hash: TxHash,
+    confirmations: u64,
+) -> Result<TransactionReceipt, Error> {
+    let interval = provider.provider().get_interval();
+    loop {
+        let receipt = provider
+            .get_transaction_receipt(tx_hash)
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?
+            .ok_or(Error::Custom(format!(
+                "Transaction {tx_hash:?} was dropped by a reorg"
+            )))?;
+        if receipt.status == Some(0u64.into()) {
+            return Err(Error::TransactionReverted {
+                tx_hash,
+                reason: None,
+            });
         }
+        let current_block = provider
+            .get_block_number()
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?;
+        let included_at = receipt.block_number.unwrap_or_default();
+        let depth = (current_block + 1u64).saturating_sub(included_at);
+        if depth.as_u64() >= confirmations {
+            return Ok(receipt);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Checks that a transaction was included and did not revert.
+///
+/// A reverted transaction is replayed on the state it was executed on to
+/// recover its revert reason, which is reported in a
+/// [`Error::TransactionReverted`].
+async fn check_receipt<M: Middleware>(
+    provider: &M,
+    tx_receipt: Option<TransactionReceipt>,
+    event_sender: &mpsc::UnboundedSender<TxEvent>,
+) -> Result<TransactionReceipt, Error> {
+    let receipt = tx_receipt
+        .ok_or(Error::Custom("Failed to send transaction".to_string()))?;
+    if let Some(block_hash) = receipt.block_hash {
+        let _ = event_sender.send(TxEvent::InBlock { block_hash });
+    }
+    if receipt.status == Some(0u64.into()) {
+        let tx_hash = receipt.transaction_hash;
+        let reason = revert_reason(provider, &receipt).await;
+        return Err(Error::TransactionReverted { tx_hash, reason });
+    }
+    Ok(receipt)
+}
+
+/// Recovers the reason a transaction reverted with by replaying it with an
+/// `eth_call` on top of the block preceding it.
+///
+/// Returns `None` if the transaction could not be replayed, or if it did not
+/// revert with an `Error(string)`, e.g. because it ran out of gas.
+async fn revert_reason<M: Middleware>(
+    provider: &M,
+    receipt: &TransactionReceipt,
+) -> Option<String> {
+    let tx = provider
+        .get_transaction(receipt.transaction_hash)
+        .await
+        .ok()??;
+    let block = receipt
+        .block_number
+        .map(|number| BlockId::from(number.saturating_sub(1u64.into())));
+    let e = provider.call(&(&tx).into(), block).await.err()?;
+    let data = e
+        .as_error_response()
+        .and_then(JsonRpcError::as_revert_data)?;
+    String::decode_with_selector(&data)
+}
+
+/// Checks that the faucet account of `provider` holds the `MINTER_ROLE`
+/// of the token, so that it can mint the token.
+pub async fn verify_minter_role<M: Middleware>(
+    provider: M,
+    token_address: Address,
+) -> Result<(), Error> {
+    let account = provider
+        .default_sender()
+        .ok_or(Error::Custom("Provider must have signer".to_string()))?;
+    let contract =
+        ERC20PresetMinterPauserContract::new(token_address, Arc::new(provider));
+    let minter_role = contract.minter_role().call().await.map_err(|e| {
+        Error::Custom(format!("Failed to fetch MINTER_ROLE: {:?}", e))
+    })?;
+    let has_role = contract
+        .has_role(minter_role, account)
+        .call()
+        .await
+        .map_err(|e| {
+            Error::Custom(format!("Failed to check MINTER_ROLE: {:?}", e))
+        })?;
+    if has_role {
+        Ok(())
+    } else {
+        Err(Error::Custom(format!(
+            "Account {account:?} does not hold the MINTER_ROLE of token {token_address:?}"
+        )))
     }
 }
 
@@ -205,29 +1284,47 @@ async fn handle_evm_token_tx<M: Middleware>(
 async fn handle_substrate_tx(
     api: OnlineClient<PolkadotConfig>,
     to: AccountId32,
-    _amount: u128,
-    native_token_amount: u128,
-    asset_id: Option<u32>,
-    signer: subxt_signer::sr25519::Keypair,
-    timeout: std::time::Duration,
+    native_token_amount: Option<u128>,
+    assets: Vec<AssetTransfer>,
+    signer: Arc<dyn SubstrateSigner>,
+    nonces: SubstrateNonces,
+    health: WalletHealth,
+    decimals: u8,
+    finality: SubstrateFinality,
+    timeouts: SubstrateTimeouts,
+    event_sender: mpsc::UnboundedSender<TxEvent>,
     result_sender: oneshot::Sender<Result<TxResult, Error>>,
 ) -> Result<(), Error> {
-    let res = match asset_id {
-        Some(asset_id) => Err(Error::Custom(format!(
-            "Substrate only supports sending native tokens. Asset ID {} not supported",
-            asset_id
-        ))),
-        None => {
-            handle_substrate_native_tx(
-                api,
-                to,
+    let wallet = signer.account_id().to_string();
+    let submitter = SubstrateSubmitter {
+        api,
+        signer,
+        nonces,
+        finality,
+        timeouts,
+        decimals,
+        event_sender,
+    };
+    let res = match (native_token_amount, assets.as_slice()) {
+        (Some(amount), []) => {
+            handle_substrate_native_tx(&submitter, &to, amount)
+                .await
+                .map(Into::into)
+        }
+        (None, [asset]) => handle_substrate_asset_tx(&submitter, &to, asset)
+            .await
+            .map(Into::into),
+        _ => {
+            handle_substrate_combined_tx(
+                &submitter,
+                &to,
                 native_token_amount,
-                signer,
-                timeout,
+                assets,
             )
             .await
         }
     };
+    health.record(wallet, res.is_ok());
 
     // Return the transaction hash.
     result_sender.send(res).map_err(|e| {
@@ -236,13 +1333,305 @@ async fn handle_substrate_tx(
     Ok(())
 }
 
-async fn handle_substrate_native_tx(
-    api: OnlineClient<PolkadotConfig>,
-    to: AccountId32,
-    amount: u128,
-    signer: subxt_signer::sr25519::Keypair,
-    timeout: std::time::Duration,
+/// Sends the native token first and then every asset. A failed transfer
+/// does not prevent the others from being sent, so the claim only fails if
+/// all of its transfers fail.
+async fn handle_substrate_combined_tx(
+    submitter: &SubstrateSubmitter,
+    to: &AccountId32,
+    native_token_amount: Option<u128>,
+    assets: Vec<AssetTransfer>,
 ) -> Result<TxResult, Error> {
+    let mut results = Vec::new();
+    if let Some(amount) = native_token_amount {
+        let res = handle_substrate_native_tx(submitter, to, amount).await;
+        results.push((ClaimAsset::Native, res));
+    }
+    for asset in assets {
+        let res = handle_substrate_asset_tx(submitter, to, &asset).await;
+        results.push((ClaimAsset::Asset(asset.asset_id), res));
+    }
+
+    let mut native = None;
+    let mut asset_legs = BTreeMap::new();
+    for (asset, leg) in combine_legs(results)? {
+        match asset {
+            ClaimAsset::Asset(asset_id) => {
+                asset_legs.insert(asset_id, leg);
+            }
+            _ => native = Some(leg),
+        }
+    }
+    Ok(TxResult::SubstrateCombined {
+        native,
+        assets: asset_legs,
+    })
+}
+
+type SubstrateTxProgress =
+    TxProgress<PolkadotConfig, OnlineClient<PolkadotConfig>>;
+type SubstrateTxInBlock =
+    TxInBlock<PolkadotConfig, OnlineClient<PolkadotConfig>>;
+
+/// How long an extrinsic that timed out waiting for inclusion or finality
+/// keeps being tracked in the background.
+pub(crate) const LATE_INCLUSION_TRACKING: std::time::Duration =
+    std::time::Duration::from_secs(10 * 60);
+
+/// Everything needed to sign and submit extrinsics on a Substrate chain.
+pub(crate) struct SubstrateSubmitter {
+    pub(crate) api: OnlineClient<PolkadotConfig>,
+    pub(crate) signer: Arc<dyn SubstrateSigner>,
+    pub(crate) nonces: SubstrateNonces,
+    pub(crate) finality: SubstrateFinality,
+    pub(crate) timeouts: SubstrateTimeouts,
+    /// The decimals of the native token of the network.
+    pub(crate) decimals: u8,
+    pub(crate) event_sender: mpsc::UnboundedSender<TxEvent>,
+}
+
+impl SubstrateSubmitter {
+    /// Signs and submits an extrinsic paying out `asset`, waits for it to
+    /// reach the configured finality and checks its events with `verify`.
+    ///
+    /// An extrinsic that times out waiting for inclusion or finality keeps
+    /// being tracked in the background, and is reported as a
+    /// [`TxEvent::LateInclusion`] if it makes it after all.
+    async fn submit<Call, Verify>(
+        &self,
+        call: &Call,
+        asset: ClaimAsset,
+        verify: Verify,
+    ) -> Result<SubstrateTransfer, Error>
+    where
+        Call: TxPayload,
+        Verify: Fn(&ExtrinsicEvents<PolkadotConfig>) -> Result<(), Error>
+            + Send
+            + Sync
+            + 'static,
+    {
+        // Sign and submit the extrinsic.
+        let account_id = self.signer.account_id();
+        let account = &account_id;
+        let nonce = self
+            .nonces
+            .next(account_id.clone(), || async move {
+                self.api
+                    .tx()
+                    .account_nonce(account)
+                    .await
+                    .map_err(|e| Error::Custom(e.to_string()))
+            })
+            .await?;
+        let tx_result_fut = async {
+            let partial = self
+                .api
+                .tx()
+                .create_partial_signed_with_nonce(
+                    call,
+                    nonce,
+                    Default::default(),
+                )
+                .map_err(|e| Error::Custom(e.to_string()))?;
+            let signature = self.signer.sign(&partial.signer_payload()).await?;
+            let address = MultiAddress::Id(self.signer.account_id());
+            partial
+                .sign_with_address_and_signature(&address, &signature)
+                .submit_and_watch()
+                .await
+                .map_err(|e| Error::Custom(e.to_string()))
+        };
+
+        let timeout_fut = tokio::time::sleep(self.timeouts.submission);
+
+        let tx_result = tokio::select! {
+            res = tx_result_fut => res,
+            _ = timeout_fut => Err(Error::SubmissionTimeout),
+        };
+        let mut progress = match tx_result {
+            Ok(progress) => progress,
+            Err(e) => {
+                self.nonces.failed(&account_id, nonce).await;
+                return Err(e);
+            }
+        };
+        self.nonces.used(&account_id, nonce).await;
+
+        let tx_hash = progress.extrinsic_hash();
+
+        println!("Tranasction sent with TxHash: {:?}", tx_hash);
+        let _ = self.event_sender.send(TxEvent::Submitted {
+            asset,
+            tx_hash: tx_hash.0.into(),
+            from: account_id.to_string(),
+            nonce,
+        });
+
+        let inclusion = tokio::time::timeout(
+            self.timeouts.inclusion,
+            wait_for_finality(
+                &mut progress,
+                SubstrateFinality::InBlock,
+                &self.event_sender,
+            ),
+        )
+        .await;
+        let (reached, tx_block) = match inclusion {
+            Ok(res) => res?,
+            Err(_) => {
+                self.track_late_inclusion(progress, asset, verify);
+                return Err(Error::InclusionTimeout {
+                    tx_hash: tx_hash.0.into(),
+                });
+            }
+        };
+
+        let waiting_for_finality = self.finality
+            == SubstrateFinality::Finalized
+            && reached == SubstrateFinality::InBlock;
+        let tx_block = if waiting_for_finality {
+            let finalization = tokio::time::timeout(
+                self.timeouts.finalization,
+                wait_for_finality(
+                    &mut progress,
+                    SubstrateFinality::Finalized,
+                    &self.event_sender,
+                ),
+            )
+            .await;
+            match finalization {
+                Ok(res) => res?.1,
+                Err(_) => {
+                    self.track_late_inclusion(progress, asset, verify);
+                    return Err(Error::FinalizationTimeout {
+                        tx_hash: tx_hash.0.into(),
+                        block_hash: tx_block.block_hash().0.into(),
+                    });
+                }
+            }
+        } else {
+            tx_block
+        };
+        verify_included(&tx_block, &verify).await
+    }
+
+    /// Keeps following an extrinsic that timed out until it reaches the
+    /// configured finality, and reports it if it does.
+    fn track_late_inclusion<Verify>(
+        &self,
+        mut progress: SubstrateTxProgress,
+        asset: ClaimAsset,
+        verify: Verify,
+    ) where
+        Verify: Fn(&ExtrinsicEvents<PolkadotConfig>) -> Result<(), Error>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let finality = self.finality;
+        let event_sender = self.event_sender.clone();
+        tokio::spawn(async move {
+            let tx_hash = progress.extrinsic_hash();
+            let tracking = async {
+                let (_, tx_block) =
+                    wait_for_finality(&mut progress, finality, &event_sender)
+                        .await?;
+                verify_included(&tx_block, &verify).await
+            };
+            match tokio::time::timeout(LATE_INCLUSION_TRACKING, tracking).await
+            {
+                Ok(Ok(transfer)) => {
+                    println!("Transaction {tx_hash:?} was included late");
+                    let _ = event_sender.send(TxEvent::LateInclusion {
+                        asset,
+                        transfer: LateTransfer::Substrate(transfer),
+                    });
+                }
+                Ok(Err(e)) => {
+                    eprintln!("Late transaction {tx_hash:?} failed: {e}");
+                }
+                Err(_) => {
+                    eprintln!("Stopped tracking transaction {tx_hash:?}");
+                }
+            }
+        });
+    }
+}
+
+/// Follows the progress of an extrinsic until it is included in a block,
+/// or until it is finalized if `finality` is
+/// [`SubstrateFinality::Finalized`], reporting the block it is included in.
+///
+/// Returns the finality that was reached, along with the block.
+async fn wait_for_finality(
+    progress: &mut SubstrateTxProgress,
+    finality: SubstrateFinality,
+    event_sender: &mpsc::UnboundedSender<TxEvent>,
+) -> Result<(SubstrateFinality, SubstrateTxInBlock), Error> {
+    while let Some(status) = progress.next_item().await {
+        match status.map_err(|e| Error::Custom(e.to_string()))? {
+            TxStatus::InBlock(tx_block) => {
+                let _ = event_sender.send(TxEvent::InBlock {
+                    block_hash: tx_block.block_hash().0.into(),
+                });
+                if finality == SubstrateFinality::InBlock {
+                    return Ok((SubstrateFinality::InBlock, tx_block));
+                }
+            }
+            TxStatus::Finalized(tx_block) => {
+                return Ok((SubstrateFinality::Finalized, tx_block));
+            }
+            TxStatus::FinalityTimeout(_) => {
+                return Err(Error::Custom(
+                    "The block of the tx was not finalized".to_string(),
+                ));
+            }
+            TxStatus::Invalid => {
+                return Err(Error::Custom("The tx is invalid".to_string()));
+            }
+            TxStatus::Usurped(_) => {
+                return Err(Error::Custom(
+                    "The tx was replaced by another one with the same nonce"
+                        .to_string(),
+                ));
+            }
+            TxStatus::Dropped => {
+                return Err(Error::Custom(
+                    "The tx was dropped from the pool".to_string(),
+                ));
+            }
+            TxStatus::Future
+            | TxStatus::Ready
+            | TxStatus::Broadcast(_)
+            | TxStatus::Retracted(_) => {}
+        }
+    }
+    Err(Error::Custom(
+        "The subscription to the tx status was dropped".to_string(),
+    ))
+}
+
+/// Checks the events of an extrinsic included in a block with `verify`.
+async fn verify_included<Verify>(
+    tx_block: &SubstrateTxInBlock,
+    verify: &Verify,
+) -> Result<SubstrateTransfer, Error>
+where
+    Verify: Fn(&ExtrinsicEvents<PolkadotConfig>) -> Result<(), Error>,
+{
+    let events = extrinsic_events(tx_block).await?;
+    verify(&events)?;
+    Ok(SubstrateTransfer {
+        tx_hash: tx_block.extrinsic_hash(),
+        block_hash: tx_block.block_hash(),
+    })
+}
+
+pub(crate) async fn handle_substrate_native_tx(
+    submitter: &SubstrateSubmitter,
+    to: &AccountId32,
+    amount: u128,
+) -> Result<SubstrateTransfer, Error> {
     let to_address = MultiAddress::Id(to.clone());
     let balance_transfer_tx =
         RuntimeApi::tx().balances().transfer(to_address, amount);
@@ -253,45 +1642,209 @@ async fn handle_substrate_native_tx(
         to,
         balance_transfer_tx.call_data().value
     );
-    // Sign and submit the extrinsic.
-    let tx_api = api.tx();
-    let tx_result_fut = tx_api
-        .sign_and_submit_then_watch_default(&balance_transfer_tx, &signer)
-        .map_err(|e| Error::Custom(e.to_string()));
+    let from = submitter.signer.account_id();
+    let to = to.clone();
+    let decimals = submitter.decimals;
+    submitter
+        .submit(&balance_transfer_tx, ClaimAsset::Native, move |events| {
+            verify_native_transfer(events, &from, &to, amount, decimals)
+        })
+        .await
+}
 
-    let timeout_fut = tokio::time::sleep(timeout);
+/// Checks that the events of an extrinsic contain the transfer of `amount`
+/// native tokens, with `decimals` decimals, from `from` to `to`.
+fn verify_native_transfer(
+    events: &ExtrinsicEvents<PolkadotConfig>,
+    from: &AccountId32,
+    to: &AccountId32,
+    amount: u128,
+    decimals: u8,
+) -> Result<(), Error> {
+    let transfers = events
+        .find::<RuntimeApi::balances::events::Transfer>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::Custom(e.to_string()))?;
+    let transferred = transfers.iter().any(|event| {
+        event.from == *from && event.to == *to && event.amount == amount
+    });
+    if !transferred {
+        return Err(Error::Custom(format!(
+            "No transfer of {amount} from {from} to {to} found in the extrinsic events"
+        )));
+    }
+    match ethers::utils::format_units(U256::from(amount), u32::from(decimals)) {
+        Ok(tokens) => println!("Transfered {tokens} tokens {from} -> {to}"),
+        Err(_) => println!("Transfered {amount} base units {from} -> {to}"),
+    }
+    Ok(())
+}
 
-    let tx_result = tokio::select! {
-        res = tx_result_fut => res,
-        _ = timeout_fut => Err(Error::Custom("Timed out waiting for tx to be sent to the network, please try again".to_string())),
-    }?;
+async fn handle_substrate_asset_tx(
+    submitter: &SubstrateSubmitter,
+    to: &AccountId32,
+    asset: &AssetTransfer,
+) -> Result<SubstrateTransfer, Error> {
+    let asset_transfer_tx = assets::transfer_call(asset, to);
+    println!(
+        "Sending tx: {}.{}({}, {}, {})",
+        asset_transfer_tx.pallet_name(),
+        asset_transfer_tx.call_name(),
+        asset.asset_id,
+        to,
+        asset.amount
+    );
+    let from = submitter.signer.account_id();
+    let to = to.clone();
+    let transfer = asset.clone();
+    submitter
+        .submit(
+            &asset_transfer_tx,
+            ClaimAsset::Asset(asset.asset_id),
+            move |events| {
+                assets::verify_transfer(events, &transfer, &from, &to)
+            },
+        )
+        .await
+}
 
-    let tx_hash = tx_result.extrinsic_hash();
+/// Fetches the events of an extrinsic included in a block.
+///
+/// Fails with the decoded dispatch error if the extrinsic emitted an
+/// `ExtrinsicFailed` event.
+async fn extrinsic_events(
+    tx_block: &SubstrateTxInBlock,
+) -> Result<ExtrinsicEvents<PolkadotConfig>, Error> {
+    tx_block.wait_for_success().await.map_err(|e| match e {
+        webb::substrate::subxt::Error::Runtime(dispatch_error) => {
+            Error::ExtrinsicFailed {
+                tx_hash: tx_block.extrinsic_hash().0.into(),
+                reason: dispatch_error.to_string(),
+            }
+        }
+        e => Error::Custom(e.to_string()),
+    })
+}
 
-    println!("Tranasction sent with TxHash: {:?}", tx_hash);
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
 
-    let tx_block = tx_result
-        .wait_for_in_block()
-        .await
-        .map_err(|e| Error::Custom(e.to_string()))?;
-    let block_hash = tx_block.block_hash();
-
-    // Find a Transfer event and print it.
-    let transfer_event = tx_block
-        .fetch_events()
-        .map_err(|e| Error::Custom(e.to_string()))
-        .await?
-        .find_first::<RuntimeApi::balances::events::Transfer>()
-        .map_err(|e| Error::Custom(e.to_string()))?;
-    if let Some(event) = transfer_event {
-        let from = event.from;
-        let to = event.to;
-        let amount = event.amount / (10u128.pow(18));
-        println!("Transfered {amount} tokens {from} -> {to}");
+    use super::*;
+
+    /// Hands out the next nonce of `nonces`, counting the fetches from the
+    /// chain, which is at `chain_nonce`.
+    async fn next(
+        nonces: &Nonces<u8>,
+        fetches: &AtomicU64,
+        chain_nonce: u64,
+    ) -> u64 {
+        nonces
+            .next(0, || async move {
+                fetches.fetch_add(1, Ordering::Relaxed);
+                Ok(chain_nonce)
+            })
+            .await
+            .unwrap()
     }
 
-    Ok(TxResult::Substrate {
-        tx_hash,
-        block_hash,
-    })
+    #[rocket::async_test]
+    async fn hands_out_consecutive_nonces() {
+        let nonces = Nonces::default();
+        let fetches = AtomicU64::new(0);
+        assert_eq!(next(&nonces, &fetches, 5).await, 5);
+        assert_eq!(next(&nonces, &fetches, 5).await, 6);
+        nonces.used(&0, 5).await;
+        nonces.used(&0, 6).await;
+        assert_eq!(next(&nonces, &fetches, 5).await, 7);
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+    }
+
+    #[rocket::async_test]
+    async fn resyncs_failed_nonces_once_none_is_in_flight() {
+        let nonces = Nonces::default();
+        let fetches = AtomicU64::new(0);
+        assert_eq!(next(&nonces, &fetches, 5).await, 5);
+        assert_eq!(next(&nonces, &fetches, 5).await, 6);
+
+        // Nonce 6 is still in flight, and not counted by the chain yet.
+        nonces.failed(&0, 5).await;
+        assert_eq!(next(&nonces, &fetches, 6).await, 7);
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+
+        nonces.used(&0, 6).await;
+        nonces.used(&0, 7).await;
+        assert_eq!(next(&nonces, &fetches, 5).await, 5);
+        assert_eq!(fetches.load(Ordering::Relaxed), 2);
+    }
+
+    #[rocket::async_test]
+    async fn resyncs_idle_wallets_right_away() {
+        let nonces = Nonces::default();
+        let fetches = AtomicU64::new(0);
+        assert_eq!(next(&nonces, &fetches, 5).await, 5);
+        nonces.failed(&0, 5).await;
+        assert_eq!(next(&nonces, &fetches, 5).await, 5);
+        nonces.used(&0, 5).await;
+        nonces.resync(&0).await;
+        assert_eq!(next(&nonces, &fetches, 6).await, 6);
+        assert_eq!(fetches.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn combined_claims_fail_with_the_error_of_a_tracked_transfer() {
+        let tx_hash = TxHash::repeat_byte(1);
+        let results: Vec<(_, Result<(), _>)> = vec![
+            (ClaimAsset::Native, Err(Error::Custom("no gas".to_string()))),
+            (
+                ClaimAsset::Asset(5),
+                Err(Error::InclusionTimeout { tx_hash }),
+            ),
+        ];
+        let e = combine_legs(results).unwrap_err();
+        assert!(e.is_still_tracked());
+
+        let results = vec![
+            (ClaimAsset::Native, Ok(())),
+            (
+                ClaimAsset::Asset(5),
+                Err(Error::InclusionTimeout { tx_hash }),
+            ),
+            (
+                ClaimAsset::Asset(6),
+                Err(Error::Custom("frozen".to_string())),
+            ),
+        ];
+        let legs = combine_legs(results).unwrap();
+        assert!(legs[0].1.succeeded());
+        assert!(legs[1].1.still_tracked());
+        assert!(!legs[2].1.succeeded() && !legs[2].1.still_tracked());
+    }
+
+    #[rocket::async_test]
+    async fn reports_the_other_nonces_in_flight() {
+        let nonces = Nonces::default();
+        let fetches = AtomicU64::new(0);
+        assert_eq!(next(&nonces, &fetches, 5).await, 5);
+        assert!(!nonces.others_in_flight(&0, 5).await);
+        assert_eq!(next(&nonces, &fetches, 5).await, 6);
+        assert!(nonces.others_in_flight(&0, 5).await);
+        nonces.used(&0, 6).await;
+        assert!(!nonces.others_in_flight(&0, 5).await);
+        assert!(!nonces.others_in_flight(&1, 5).await);
+    }
+
+    #[rocket::async_test]
+    async fn refetches_nonces_of_abandoned_transactions() {
+        let nonces = Nonces::default();
+        let fetches = AtomicU64::new(0);
+        assert_eq!(next(&nonces, &fetches, 5).await, 5);
+        // The transaction of nonce 5 was abandoned while in flight, and
+        // the chain counts it as pending.
+        nonces.reset(&0).await;
+        assert_eq!(next(&nonces, &fetches, 6).await, 6);
+        nonces.failed(&0, 6).await;
+        assert_eq!(next(&nonces, &fetches, 6).await, 6);
+        assert_eq!(fetches.load(Ordering::Relaxed), 3);
+    }
 }
@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -8,26 +9,32 @@ use ethers::{
     providers::{Http, Provider},
     types::{Address, TransactionReceipt},
 };
-use rocket::tokio::sync::oneshot;
+use rocket::tokio::sync::{mpsc, oneshot};
 use serde::{Deserialize, Serialize};
 use webb::evm::ethers;
 use webb::substrate::subxt::{
     utils::AccountId32, OnlineClient, PolkadotConfig,
 };
+use webb_auth::model::ClaimAsset;
+use webb_proposals::TypedChainId;
 
 use crate::error::Error;
+use crate::signers::{EthersSigner, SubstrateSigner};
 
-pub type EthersClient = Arc<
-    NonceManagerMiddleware<
-        SignerMiddleware<
-            gas_oracle::GasOracleMiddleware<
-                gas_escalator::GasEscalatorMiddleware<Provider<Http>>,
-                gas_oracle::GasNow,
-            >,
-            LocalWallet,
-        >,
-    >,
->;
+use super::failover::FailoverClient;
+use super::networks::{
+    AssetPallet, DispenseMode, EscalatorConfig, FeeStrategy, Network,
+    OverMaxFee, SubstrateFinality,
+};
+
+pub type EthersClient =
+    Arc<SignerMiddleware<Provider<FailoverClient<Http>>, EthersSigner>>;
+
+/// The hot wallets of an EVM chain.
+pub type EvmWallets = Arc<WalletPool<EthersClient>>;
+
+/// The hot wallets of a Substrate chain.
+pub type SubstrateSigners = Arc<WalletPool<Arc<dyn SubstrateSigner>>>;
 
 pub struct EvmProviders<T> {
     pub providers: HashMap<u64, T>,
@@ -37,72 +44,483 @@ pub struct SubstrateProviders<T> {
     pub providers: HashMap<u64, T>,
 }
 
+/// The hot wallets of every Substrate chain.
+pub struct SubstrateWallets {
+    pub wallets: HashMap<u64, SubstrateSigners>,
+}
+
+/// The hot wallets claims on a chain are spread across, in round-robin
+/// order.
+#[derive(Debug)]
+pub struct WalletPool<T> {
+    wallets: Vec<T>,
+    next: AtomicUsize,
+}
+
+impl<T> WalletPool<T> {
+    /// Creates a pool of at least one wallet.
+    pub fn new(wallets: Vec<T>) -> Result<Self, Error> {
+        if wallets.is_empty() {
+            return Err(Error::Custom("A wallet pool needs a wallet".into()));
+        }
+        Ok(Self {
+            wallets,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Returns the first wallet, used where any wallet will do, such as for
+    /// reading the chain.
+    pub fn primary(&self) -> &T {
+        &self.wallets[0]
+    }
+
+    /// Returns every wallet of the pool.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.wallets.iter()
+    }
+
+    /// Returns the next wallet in round-robin order that `usable` accepts.
+    pub fn next_usable(&self, usable: impl Fn(&T) -> bool) -> Option<&T> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let len = self.wallets.len();
+        (0..len)
+            .map(|offset| &self.wallets[(start + offset) % len])
+            .find(|wallet| usable(wallet))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum TxResult {
     Evm(TransactionReceipt),
+    /// The result of a claim that sent more than one transfer, such as the
+    /// native token along with ERC20 tokens.
+    EvmCombined {
+        native: Option<TransferLeg>,
+        tokens: BTreeMap<Address, TransferLeg>,
+    },
     Substrate {
         block_hash: webb::substrate::subxt::utils::H256,
         tx_hash: webb::substrate::subxt::utils::H256,
     },
+    /// The result of a claim that sent more than one transfer, such as the
+    /// native token along with assets.
+    SubstrateCombined {
+        native: Option<TransferLeg<SubstrateTransfer>>,
+        assets: BTreeMap<u32, TransferLeg<SubstrateTransfer>>,
+    },
+}
+
+impl TxResult {
+    /// Returns `true` if the claim of `asset` was paid out.
+    pub fn paid(&self, asset: &ClaimAsset) -> bool {
+        match (self, asset) {
+            (Self::EvmCombined { native, .. }, ClaimAsset::Native) => {
+                native.as_ref().is_some_and(TransferLeg::succeeded)
+            }
+            (Self::EvmCombined { tokens, .. }, ClaimAsset::Token(address)) => {
+                tokens.get(address).is_some_and(TransferLeg::succeeded)
+            }
+            (Self::SubstrateCombined { native, .. }, ClaimAsset::Native) => {
+                native.as_ref().is_some_and(TransferLeg::succeeded)
+            }
+            (
+                Self::SubstrateCombined { assets, .. },
+                ClaimAsset::Asset(asset_id),
+            ) => assets.get(asset_id).is_some_and(TransferLeg::succeeded),
+            (Self::EvmCombined { .. } | Self::SubstrateCombined { .. }, _) => {
+                false
+            }
+            // A single transfer pays out every asset of the claim.
+            (Self::Evm(_) | Self::Substrate { .. }, _) => true,
+        }
+    }
+
+    /// Returns `true` if the transfer of `asset` timed out, but is still
+    /// tracked as it may yet be included.
+    pub fn still_tracked(&self, asset: &ClaimAsset) -> bool {
+        match (self, asset) {
+            (Self::EvmCombined { native, .. }, ClaimAsset::Native) => {
+                native.as_ref().is_some_and(TransferLeg::still_tracked)
+            }
+            (Self::EvmCombined { tokens, .. }, ClaimAsset::Token(address)) => {
+                tokens.get(address).is_some_and(TransferLeg::still_tracked)
+            }
+            (Self::SubstrateCombined { native, .. }, ClaimAsset::Native) => {
+                native.as_ref().is_some_and(TransferLeg::still_tracked)
+            }
+            (
+                Self::SubstrateCombined { assets, .. },
+                ClaimAsset::Asset(asset_id),
+            ) => assets.get(asset_id).is_some_and(TransferLeg::still_tracked),
+            _ => false,
+        }
+    }
+}
+
+impl From<SubstrateTransfer> for TxResult {
+    fn from(transfer: SubstrateTransfer) -> Self {
+        Self::Substrate {
+            block_hash: transfer.block_hash,
+            tx_hash: transfer.tx_hash,
+        }
+    }
+}
+
+/// An extrinsic included in a block.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubstrateTransfer {
+    pub block_hash: webb::substrate::subxt::utils::H256,
+    pub tx_hash: webb::substrate::subxt::utils::H256,
+}
+
+/// The outcome of one of the transfers of a combined claim.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum TransferLeg<R = TransactionReceipt> {
+    Succeeded(R),
+    Failed(String),
+    /// The transfer timed out, but is still tracked as it may yet be
+    /// included.
+    TimedOut(String),
+}
+
+impl<R> TransferLeg<R> {
+    /// Returns `true` if the transfer was included on chain.
+    pub fn succeeded(&self) -> bool {
+        matches!(self, Self::Succeeded(_))
+    }
+
+    /// Returns `true` if the transfer timed out, but is still tracked.
+    pub fn still_tracked(&self) -> bool {
+        matches!(self, Self::TimedOut(_))
+    }
+}
+
+impl<R> From<Result<R, Error>> for TransferLeg<R> {
+    fn from(res: Result<R, Error>) -> Self {
+        match res {
+            Ok(receipt) => Self::Succeeded(receipt),
+            Err(e) if e.is_still_tracked() => Self::TimedOut(e.to_string()),
+            Err(e) => Self::Failed(e.to_string()),
+        }
+    }
+}
+
+/// Progress of a transaction reported by the processor before its result.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TxEvent {
+    /// A transaction paying out `asset` was sent to the network from the
+    /// hot wallet `from`.
+    Submitted {
+        asset: ClaimAsset,
+        tx_hash: H256,
+        from: String,
+        nonce: u64,
+    },
+    /// The transaction was included in a block.
+    InBlock { block_hash: H256 },
+    /// A transfer paying out `asset` was included after timing out, and
+    /// after the result of its claim was reported.
+    LateInclusion {
+        asset: ClaimAsset,
+        transfer: LateTransfer,
+    },
+}
+
+/// A transfer that was included after timing out.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum LateTransfer {
+    /// A transaction that got its confirmations late.
+    Evm(TransactionReceipt),
+    /// An extrinsic that was included, or finalized, late.
+    Substrate(SubstrateTransfer),
+}
+
+/// An ERC20 transfer of a claim.
+#[derive(Clone, Debug)]
+pub struct TokenTransfer {
+    pub address: Address,
+    /// The amount in whole tokens, scaled by the decimals of the token.
+    pub amount: U256,
+    pub mode: DispenseMode,
+}
+
+/// A Substrate asset transfer of a claim.
+#[derive(Clone, Debug)]
+pub struct AssetTransfer {
+    pub pallet: AssetPallet,
+    pub asset_id: u32,
+    /// The amount in the smallest unit of the asset.
+    pub amount: u128,
+}
+
+/// How the gas limit of EVM transactions is derived from their estimated
+/// gas.
+#[derive(Clone, Copy, Debug)]
+pub struct GasLimits {
+    /// The factor the estimated gas is multiplied by.
+    pub multiplier: f64,
+    /// The lowest gas limit to send transactions with.
+    pub floor: Option<U256>,
+    /// The highest gas limit to send transactions with.
+    pub ceiling: Option<U256>,
+}
+
+impl GasLimits {
+    /// Returns the gas limits of a network, whose gas is multiplied by
+    /// `default_multiplier` unless it sets its own.
+    pub fn of(network: &Network, default_multiplier: f64) -> Self {
+        Self {
+            multiplier: network
+                .gas_limit_multiplier
+                .unwrap_or(default_multiplier),
+            floor: network.min_gas_limit.map(Into::into),
+            ceiling: network.max_gas_limit.map(Into::into),
+        }
+    }
+
+    /// Returns the gas limit for a transaction with the given estimated
+    /// gas.
+    pub fn gas_limit(&self, estimated_gas: U256) -> U256 {
+        let mut gas_limit = scale(estimated_gas, self.multiplier);
+        if let Some(floor) = self.floor {
+            gas_limit = gas_limit.max(floor);
+        }
+        if let Some(ceiling) = self.ceiling {
+            gas_limit = gas_limit.min(ceiling);
+        }
+        gas_limit
+    }
+}
+
+/// How EVM transactions are priced, and re-priced when they are not mined.
+#[derive(Clone, Copy, Debug)]
+pub struct FeeSettings {
+    pub strategy: FeeStrategy,
+    pub escalator: EscalatorConfig,
+    /// The highest gas price, or max fee per gas, to send transactions
+    /// with.
+    pub max_fee_per_gas: Option<U256>,
+    /// What happens to transactions while the fees are above
+    /// `max_fee_per_gas`.
+    pub over_max_fee: OverMaxFee,
+    /// The longest time a transaction is held for, when it is held.
+    pub max_fee_hold: Duration,
+}
+
+impl FeeSettings {
+    /// Returns the fee settings of a network.
+    pub fn of(network: &Network) -> Self {
+        Self {
+            strategy: network.fee_strategy,
+            escalator: network.escalator,
+            max_fee_per_gas: network.max_fee_per_gas.map(Into::into),
+            over_max_fee: network.over_max_fee,
+            max_fee_hold: network.max_fee_hold,
+        }
+    }
+
+    /// Converts a transaction to the type the fee strategy sends.
+    pub fn typed(&self, tx: TypedTransaction) -> TypedTransaction {
+        match self.strategy {
+            FeeStrategy::Eip1559 => Eip1559TransactionRequest::from(tx).into(),
+            FeeStrategy::Legacy | FeeStrategy::Fixed { .. } => {
+                TransactionRequest::from(tx).into()
+            }
+        }
+    }
+
+    /// Caps a gas price or max fee per gas to `max_fee_per_gas`.
+    pub fn cap(&self, fee: U256) -> U256 {
+        self.max_fee_per_gas.map_or(fee, |max| fee.min(max))
+    }
+
+    /// Raises the fees of a transaction by the escalator coefficient, up to
+    /// `max_fee_per_gas`. Returns `false` if they cannot be raised further.
+    pub fn bump(&self, tx: &mut TypedTransaction) -> bool {
+        let coefficient = self.escalator.coefficient;
+        match tx {
+            TypedTransaction::Eip1559(inner) => {
+                let (Some(max_fee), Some(priority_fee)) =
+                    (inner.max_fee_per_gas, inner.max_priority_fee_per_gas)
+                else {
+                    return false;
+                };
+                let bumped = self.cap(scale(max_fee, coefficient));
+                if bumped <= max_fee {
+                    return false;
+                }
+                inner.max_fee_per_gas = Some(bumped);
+                inner.max_priority_fee_per_gas =
+                    Some(scale(priority_fee, coefficient).min(bumped));
+                true
+            }
+            _ => {
+                let Some(gas_price) = tx.gas_price() else {
+                    return false;
+                };
+                let bumped = self.cap(scale(gas_price, coefficient));
+                if bumped <= gas_price {
+                    return false;
+                }
+                tx.set_gas_price(bumped);
+                true
+            }
+        }
+    }
+}
+
+/// The fees a network currently asks for.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct FeeEstimate {
+    /// The gas price, or the max fee per gas of EIP-1559 transactions.
+    pub fee_per_gas: U256,
+    /// The max priority fee per gas of EIP-1559 transactions.
+    pub priority_fee_per_gas: Option<U256>,
+}
+
+/// Multiplies `value` by `factor`, in thousandths to keep the arithmetic on
+/// integers.
+fn scale(value: U256, factor: f64) -> U256 {
+    let per_mille = U256::from((factor * 1000.0).round() as u64);
+    value * per_mille / 1000u64
+}
+
+/// The time the processor waits for each step of a Substrate extrinsic.
+#[derive(Clone, Copy, Debug)]
+pub struct SubstrateTimeouts {
+    /// The time to wait for the node to accept the extrinsic.
+    pub submission: Duration,
+    /// The time to wait for the extrinsic to be included in a block.
+    pub inclusion: Duration,
+    /// The time to wait for the block of the extrinsic to be finalized.
+    pub finalization: Duration,
+}
+
+impl SubstrateTimeouts {
+    /// Returns the timeouts of a network, which waits `default_submission`
+    /// for the node unless it sets its own `submission_timeout`.
+    pub fn of(network: &Network, default_submission: Duration) -> Self {
+        Self {
+            submission: network
+                .submission_timeout
+                .unwrap_or(default_submission),
+            inclusion: network.inclusion_timeout,
+            finalization: network.finalization_timeout,
+        }
+    }
 }
 
 #[allow(clippy::large_enum_variant)]
 pub enum Transaction {
     Evm {
-        provider: EthersClient,
+        typed_chain_id: TypedChainId,
+        /// The hot wallets the transaction can be sent from.
+        wallets: EvmWallets,
         to: Address,
-        amount: U256,
-        native_token_amount: U256,
-        token_address: Option<Address>,
+        /// The amount of native tokens to send, if any.
+        native_token_amount: Option<U256>,
+        /// The ERC20 tokens to send.
+        tokens: Vec<TokenTransfer>,
+        /// The number of confirmations to wait for.
+        confirmations: u64,
+        /// The time to wait for the transaction to be mined, and then for
+        /// its confirmations.
+        inclusion_timeout: Duration,
+        gas_limits: GasLimits,
+        fees: FeeSettings,
+        event_sender: mpsc::UnboundedSender<TxEvent>,
         result_sender: oneshot::Sender<Result<TxResult, Error>>,
     },
     Substrate {
+        typed_chain_id: TypedChainId,
         api: OnlineClient<PolkadotConfig>,
         to: AccountId32,
-        amount: u128,
-        native_token_amount: u128,
-        asset_id: Option<u32>,
-        signer: subxt_signer::sr25519::Keypair,
-        timeout: Duration,
+        /// The amount of native tokens to send, if any.
+        native_token_amount: Option<u128>,
+        /// The assets to send.
+        assets: Vec<AssetTransfer>,
+        /// The hot wallets the transaction can be signed by.
+        signers: SubstrateSigners,
+        /// The decimals of the native token of the network.
+        decimals: u8,
+        finality: SubstrateFinality,
+        timeouts: SubstrateTimeouts,
+        event_sender: mpsc::UnboundedSender<TxEvent>,
         result_sender: oneshot::Sender<Result<TxResult, Error>>,
     },
 }
 
+impl Transaction {
+    /// Returns the chain the transaction is sent on.
+    pub fn typed_chain_id(&self) -> TypedChainId {
+        match self {
+            Self::Evm { typed_chain_id, .. }
+            | Self::Substrate { typed_chain_id, .. } => *typed_chain_id,
+        }
+    }
+
+    /// Answers the transaction with `error` without sending it.
+    pub fn fail(self, error: Error) {
+        match self {
+            Self::Evm { result_sender, .. }
+            | Self::Substrate { result_sender, .. } => {
+                let _ = result_sender.send(Err(error));
+            }
+        }
+    }
+}
+
 impl std::fmt::Debug for Transaction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Evm {
-                provider,
+                typed_chain_id,
+                wallets,
                 to,
-                amount,
                 native_token_amount,
-                token_address,
+                tokens,
+                confirmations,
+                inclusion_timeout,
+                gas_limits,
+                fees,
                 result_sender,
+                ..
             } => f
                 .debug_struct("Evm")
-                .field("provider", provider)
+                .field("typed_chain_id", typed_chain_id)
+                .field("wallets", wallets)
                 .field("to", to)
-                .field("amount", amount)
                 .field("native_token_amount", native_token_amount)
-                .field("token_address", token_address)
+                .field("tokens", tokens)
+                .field("confirmations", confirmations)
+                .field("inclusion_timeout", inclusion_timeout)
+                .field("gas_limits", gas_limits)
+                .field("fees", fees)
                 .field("result_sender", result_sender)
                 .finish(),
             Self::Substrate {
+                typed_chain_id,
                 api,
                 to,
-                amount,
-                asset_id,
+                assets,
                 result_sender,
                 native_token_amount,
+                decimals,
+                finality,
+                timeouts,
                 ..
             } => f
                 .debug_struct("Substrate")
+                .field("typed_chain_id", typed_chain_id)
                 .field("api", api)
                 .field("to", to)
                 .field("native_token_amount", native_token_amount)
-                .field("amount", amount)
-                .field("asset_id", asset_id)
-                .field("signer", &"<hidden>")
+                .field("assets", assets)
+                .field("signers", &"<hidden>")
+                .field("decimals", decimals)
+                .field("finality", finality)
+                .field("timeouts", timeouts)
                 .field("result_sender", result_sender)
                 .finish(),
         }